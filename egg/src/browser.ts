@@ -0,0 +1,132 @@
+import * as core from "./hammeregg_core"
+import { FileTransfers } from "./files"
+
+/** jquery is dead, long live jquery! */
+let $ = (id: string) => document.getElementById(id)
+
+/**
+ * Formats a file size for humans.
+ */
+function formatSize(size: number) {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"]
+    let unit = 0
+    while (size >= 1024 && unit < units.length - 1) {
+        size /= 1024
+        unit++
+    }
+    return `${unit === 0 ? size : size.toFixed(1)} ${units[unit]}`
+}
+
+/**
+ * Saves a downloaded file to the remote's computer.
+ */
+function save(name: string, blob: Blob) {
+    let url = URL.createObjectURL(blob)
+    let link = document.createElement("a")
+    link.href = url
+    link.download = name
+    link.click()
+    URL.revokeObjectURL(url)
+}
+
+/**
+ * Sets up the file browser, which lists the home
+ * computer's shared folders and downloads files
+ * from them. Files dropped onto `streamVideo` are
+ * uploaded to the folder that's open in the browser,
+ * or to the first shared folder if none is open.
+ */
+export function setup(
+    transfers: FileTransfers,
+    streamVideo: HTMLVideoElement
+) {
+    let browser = $("files-browser")
+    let list = $("files-list")
+    // the open folder, where "" is the list of shared folders
+    let path = ""
+
+    let showError = (e: any) => ($("files-err").innerText = `Error: ${e}`)
+
+    let open = async (newPath: string) => {
+        let entries: core.FileEntry[]
+        try {
+            entries = await transfers.list(newPath)
+        } catch (e) {
+            showError(e)
+            return
+        }
+        path = newPath
+        $("files-path").innerText = "/" + path
+        $("files-err").innerHTML = "&nbsp;"
+
+        list.replaceChildren()
+        if (path !== "") {
+            let parent = path.split("/").slice(0, -1).join("/")
+            list.append(item("..", "", () => open(parent)))
+        }
+        for (let entry of entries) {
+            let entryPath = path === "" ? entry.name : `${path}/${entry.name}`
+            if (entry.is_dir) {
+                list.append(item(entry.name + "/", "", () => open(entryPath)))
+            } else {
+                list.append(
+                    item(entry.name, formatSize(entry.size), () =>
+                        transfers
+                            .download(entryPath)
+                            .then(blob => save(entry.name, blob))
+                            .catch(showError)
+                    )
+                )
+            }
+        }
+    }
+
+    let item = (name: string, detail: string, onclick: () => void) => {
+        let li = document.createElement("li")
+        li.className = "flex justify-between cursor-pointer text-secondary"
+        let nameSpan = document.createElement("span")
+        nameSpan.innerText = name
+        let detailSpan = document.createElement("span")
+        detailSpan.innerText = detail
+        li.append(nameSpan, detailSpan)
+        li.onclick = onclick
+        return li
+    }
+
+    $("files-toggle").onclick = () => {
+        if (browser.classList.toggle("hidden")) {
+            // give focus back to the stream
+            streamVideo.focus()
+        } else {
+            open(path)
+        }
+    }
+
+    streamVideo.addEventListener("dragover", e => e.preventDefault())
+    streamVideo.addEventListener("drop", async e => {
+        e.preventDefault()
+        let folder = path
+        if (folder === "") {
+            let folders = await transfers.list("").catch(e => {
+                showError(e)
+                return []
+            })
+            if (folders.length === 0) return
+            folder = folders[0].name
+        }
+        for (let file of Array.from(e.dataTransfer.files)) {
+            transfers
+                .upload(`${folder}/${file.name}`, file)
+                .then(() => {
+                    // show the new file if its folder is open
+                    if (
+                        !browser.classList.contains("hidden") &&
+                        path === folder
+                    ) {
+                        open(path)
+                    }
+                })
+                .catch(e => showError(`couldn't upload ${file.name}: ${e}`))
+        }
+    })
+}
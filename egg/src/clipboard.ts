@@ -0,0 +1,64 @@
+import * as core from "./hammeregg_core"
+
+/**
+ * Sets up clipboard synchronization with a home computer.
+ */
+export function setup(channel: RTCDataChannel, codec: core.Codec) {
+    channel.binaryType = "arraybuffer"
+
+    // the last text that both sides have seen, so that
+    // clipboard changes aren't echoed back and forth
+    let lastText: string = null
+    // text from the home computer that couldn't be written
+    // yet because Egg didn't have focus
+    let pendingText: string = null
+
+    let send = (text: string) => {
+        if (text === lastText || channel.readyState != "open") return
+        try {
+            channel.send(core.encodeClipboardPacket(codec, { text: text }))
+            lastText = text
+        } catch (e) {
+            console.warn("Couldn't send clipboard:", e)
+        }
+    }
+
+    let write = async (text: string) => {
+        try {
+            await navigator.clipboard.writeText(text)
+            pendingText = null
+        } catch (e) {
+            pendingText = text
+        }
+    }
+
+    channel.onmessage = async e => {
+        try {
+            let packet = core.decodeClipboardPacket(
+                codec,
+                new Uint8Array(e.data)
+            )
+            lastText = packet.text
+            await write(packet.text)
+        } catch (e) {
+            console.error("Invalid clipboard packet:", e)
+        }
+    }
+
+    // browsers only let pages use the clipboard while they
+    // have focus, so catch up whenever Egg regains focus
+    window.addEventListener("focus", async () => {
+        if (pendingText !== null) {
+            await write(pendingText)
+        } else {
+            try {
+                send(await navigator.clipboard.readText())
+            } catch (e) {
+                console.warn("Couldn't read clipboard:", e)
+            }
+        }
+    })
+    document.addEventListener("paste", e =>
+        send(e.clipboardData.getData("text"))
+    )
+}
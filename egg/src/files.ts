@@ -0,0 +1,248 @@
+import * as core from "./hammeregg_core"
+
+/**
+ * How many bytes can be queued on the files channel
+ * before uploads wait for it to drain.
+ */
+const MAX_BUFFERED_AMOUNT = 1024 * 1024
+
+interface Upload {
+    kind: "upload"
+    file: File
+    resolve: () => void
+    reject: (reason: any) => void
+}
+
+interface Download {
+    kind: "download"
+    path: string
+    size: number
+    chunks: Uint8Array[]
+    received: number
+    resolve: (blob: Blob) => void
+    reject: (reason: any) => void
+}
+
+interface List {
+    kind: "list"
+    entries: core.FileEntry[]
+    resolve: (entries: core.FileEntry[]) => void
+    reject: (reason: any) => void
+}
+
+interface Stat {
+    kind: "stat"
+    resolve: (entry: core.FileEntry) => void
+    reject: (reason: any) => void
+}
+
+/**
+ * Transfers files to and from the home
+ * computer's shared folders. The first
+ * component of every path is the name
+ * of a shared folder.
+ */
+export interface FileTransfers {
+    /**
+     * Uploads a file to the given path in the shared
+     * folder. If an earlier upload to the same path was
+     * interrupted, it's resumed where it left off.
+     */
+    upload(path: string, file: File): Promise<void>
+    /**
+     * Downloads a file from the given path
+     * in the shared folder.
+     */
+    download(path: string): Promise<Blob>
+    /**
+     * Lists a folder. An empty path lists
+     * the shared folders themselves.
+     */
+    list(path: string): Promise<core.FileEntry[]>
+    /**
+     * Looks up a single file or folder.
+     */
+    stat(path: string): Promise<core.FileEntry>
+}
+
+/**
+ * Sets up file transfers with a home computer.
+ */
+export function setup(
+    channel: RTCDataChannel,
+    codec: core.Codec
+): FileTransfers {
+    channel.binaryType = "arraybuffer"
+    channel.bufferedAmountLowThreshold = MAX_BUFFERED_AMOUNT / 2
+
+    let nextId = 0
+    let pending = new Map<number, Upload | Download | List | Stat>()
+
+    let send = (packet: core.FileTransferPacket) =>
+        channel.send(core.encodeFileTransferPacket(codec, packet))
+
+    let finish = (id: number) => {
+        let transfer = pending.get(id)
+        pending.delete(id)
+        return transfer
+    }
+
+    let fail = (id: number, reason: any) => {
+        finish(id)?.reject(reason)
+    }
+
+    let drained = () =>
+        new Promise<void>(resolve => {
+            if (channel.bufferedAmount <= MAX_BUFFERED_AMOUNT) {
+                resolve()
+            } else {
+                channel.addEventListener("bufferedamountlow", () => resolve(), {
+                    once: true,
+                })
+            }
+        })
+
+    let sendChunks = async (id: number, upload: Upload, offset: number) => {
+        let chunkSize = core.fileChunkSize()
+        while (offset < upload.file.size && pending.get(id) === upload) {
+            let end = Math.min(offset + chunkSize, upload.file.size)
+            let data = new Uint8Array(
+                await upload.file.slice(offset, end).arrayBuffer()
+            )
+            await drained()
+            channel.send(core.encodeFileChunk(codec, id, offset, data))
+            offset = end
+        }
+    }
+
+    let receiveChunk = (
+        id: number,
+        download: Download,
+        chunk: { offset: number; data: number[]; sha256: number[] }
+    ) => {
+        let data = new Uint8Array(chunk.data)
+        if (!core.verifyFileChunk(data, new Uint8Array(chunk.sha256))) {
+            send({ cancel: { id, error: { code: "ChecksumMismatch" } } })
+            fail(id, "file chunk was corrupted")
+            return
+        }
+        if (chunk.offset !== download.received) {
+            send({ cancel: { id, error: { code: "Other" } } })
+            fail(id, "got a file chunk out of order")
+            return
+        }
+        download.chunks.push(data)
+        download.received += data.length
+        if (download.received >= download.size) {
+            send({ complete: { id } })
+            finish(id)
+            download.resolve(new Blob(download.chunks))
+        }
+    }
+
+    channel.onmessage = e => {
+        let packet: core.FileTransferPacket
+        try {
+            packet = core.decodeFileTransferPacket(
+                codec,
+                new Uint8Array(e.data)
+            )
+        } catch (e) {
+            console.error("Invalid file transfer packet:", e)
+            return
+        }
+
+        if ("accepted" in packet) {
+            let { id, size, offset } = packet.accepted
+            let transfer = pending.get(id)
+            if (transfer?.kind === "upload") {
+                sendChunks(id, transfer, offset).catch(e => {
+                    send({ cancel: { id } })
+                    fail(id, e)
+                })
+            } else if (transfer?.kind === "download") {
+                transfer.size = size
+                if (size === 0) {
+                    send({ complete: { id } })
+                    finish(id)
+                    transfer.resolve(new Blob([]))
+                }
+            }
+        } else if ("chunk" in packet) {
+            let transfer = pending.get(packet.chunk.id)
+            if (transfer?.kind === "download") {
+                receiveChunk(packet.chunk.id, transfer, packet.chunk)
+            }
+        } else if ("complete" in packet) {
+            let transfer = finish(packet.complete.id)
+            if (transfer?.kind === "upload") {
+                transfer.resolve()
+            }
+        } else if ("listing" in packet) {
+            let { id, entries, done } = packet.listing
+            let transfer = pending.get(id)
+            if (transfer?.kind === "list") {
+                transfer.entries.push(...entries)
+                if (done) {
+                    finish(id)
+                    transfer.resolve(transfer.entries)
+                }
+            }
+        } else if ("entry" in packet) {
+            let transfer = finish(packet.entry.id)
+            if (transfer?.kind === "stat") {
+                transfer.resolve(packet.entry.entry)
+            }
+        } else if ("cancel" in packet) {
+            let error = packet.cancel.error
+            fail(
+                packet.cancel.id,
+                error ? core.formatErrorMsg(error) : "cancelled"
+            )
+        }
+    }
+
+    // anything still in flight can't finish once the channel closes
+    channel.onclose = () => {
+        for (let id of [...pending.keys()]) {
+            fail(id, "connection closed")
+        }
+    }
+
+    let transfers: FileTransfers = {
+        upload: (path, file) =>
+            new Promise((resolve, reject) => {
+                let id = nextId++
+                pending.set(id, { kind: "upload", file, resolve, reject })
+                send({ upload: { id, path, size: file.size } })
+            }),
+        download: path =>
+            new Promise((resolve, reject) => {
+                let id = nextId++
+                pending.set(id, {
+                    kind: "download",
+                    path,
+                    size: 0,
+                    chunks: [],
+                    received: 0,
+                    resolve,
+                    reject,
+                })
+                send({ download: { id, path, offset: 0 } })
+            }),
+        list: path =>
+            new Promise((resolve, reject) => {
+                let id = nextId++
+                pending.set(id, { kind: "list", entries: [], resolve, reject })
+                send({ list: { id, path } })
+            }),
+        stat: path =>
+            new Promise((resolve, reject) => {
+                let id = nextId++
+                pending.set(id, { kind: "stat", resolve, reject })
+                send({ stat: { id, path } })
+            }),
+    }
+
+    return transfers
+}
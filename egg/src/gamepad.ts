@@ -0,0 +1,82 @@
+import * as core from "./hammeregg_core"
+import { GamepadAxis, GamepadButton } from "./hammeregg_core"
+
+/**
+ * The buttons of the browser's standard gamepad layout,
+ * by index. Buttons 6 and 7 are the triggers, which
+ * are sent as axes instead.
+ */
+const BUTTONS: (GamepadButton | null)[] = [
+    "A",
+    "B",
+    "X",
+    "Y",
+    "LeftBumper",
+    "RightBumper",
+    null,
+    null,
+    "Back",
+    "Start",
+    "LeftStick",
+    "RightStick",
+    "DPadUp",
+    "DPadDown",
+    "DPadLeft",
+    "DPadRight",
+    "Guide",
+]
+
+const STICK_AXES: GamepadAxis[] = [
+    "LeftStickX",
+    "LeftStickY",
+    "RightStickX",
+    "RightStickY",
+]
+
+/**
+ * Sends the first connected gamepad's input to the home
+ * computer. Browsers don't have events for gamepad input,
+ * so this polls the gamepad every frame and only sends
+ * what changed, until the input channel closes.
+ */
+export function pollGamepad(
+    channel: RTCDataChannel,
+    send: (packet: core.InputPacket) => void
+) {
+    let buttons = new Map<GamepadButton, boolean>()
+    let axes = new Map<GamepadAxis, number>()
+
+    let sendAxis = (axis: GamepadAxis, value: number, min: number) => {
+        value = Math.min(Math.max(value, min), 1)
+        if (axes.get(axis) === value) return
+        axes.set(axis, value)
+        send({ gamepad_axis: { axis: axis, value: value } })
+    }
+
+    let poll = () => {
+        if (channel.readyState === "closed") return
+        requestAnimationFrame(poll)
+        if (channel.readyState !== "open") return
+
+        let gamepad = Array.from(navigator.getGamepads()).find(
+            gamepad => gamepad !== null && gamepad.mapping === "standard"
+        )
+        if (!gamepad) return
+
+        BUTTONS.forEach((button, i) => {
+            if (button === null || i >= gamepad.buttons.length) return
+            let pressed = gamepad.buttons[i].pressed
+            if ((buttons.get(button) || false) === pressed) return
+            buttons.set(button, pressed)
+            send({ gamepad_button: { button: button, pressed: pressed } })
+        })
+        STICK_AXES.forEach((axis, i) => {
+            if (i < gamepad.axes.length) sendAxis(axis, gamepad.axes[i], -1)
+        })
+        if (gamepad.buttons.length > 7) {
+            sendAxis("LeftTrigger", gamepad.buttons[6].value, 0)
+            sendAxis("RightTrigger", gamepad.buttons[7].value, 0)
+        }
+    }
+    requestAnimationFrame(poll)
+}
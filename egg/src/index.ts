@@ -1,9 +1,12 @@
-import * as BSON from "bson"
 import { isIP } from "range_check"
 import { StateMachine, StateMachineInstance } from "ts-state-machines"
+import * as browser from "./browser"
+import * as clipboard from "./clipboard"
+import * as files from "./files"
 import * as core from "./hammeregg_core"
 import * as key from "./hammeregg_key"
 import * as input from "./input"
+import * as terminal from "./terminal"
 
 /** jquery is dead, long live jquery! */
 let $ = (id: string) => document.getElementById(id)
@@ -38,6 +41,7 @@ $("fake_egg_password").onclick = e => {
 $("setup").onsubmit = async e => {
     e.preventDefault()
     ;($("setup") as HTMLFormElement).enabled = false
+    await core.ready
 
     let desktopName = ($("desktop_name") as HTMLInputElement).value
     let signallingAddr = ($("signalling_server_ip") as HTMLInputElement).value
@@ -45,10 +49,9 @@ $("setup").onsubmit = async e => {
     // Validate
     let errors = []
 
-    if (desktopName.length == 0) {
-        errors.push("desktop name cannot be empty")
-    } else if (desktopName.includes("\0")) {
-        errors.push("desktop name cannot contain '\\0'")
+    let desktopNameError = core.validateHomeName(desktopName)
+    if (desktopNameError !== undefined) {
+        errors.push(desktopNameError)
     }
 
     signallingAddr = tryParseSignallingServerAddr(signallingAddr)
@@ -61,9 +64,9 @@ $("setup").onsubmit = async e => {
     let eggPasswordFiles = ($("egg_password") as HTMLInputElement).files
     if (eggPasswordFiles.length > 0) {
         try {
-            let password = BSON.deserialize(
-                await eggPasswordFiles[0].arrayBuffer()
-            ) as key.RemotePassword
+            let password = core.decodeRemotePassword(
+                new Uint8Array(await eggPasswordFiles[0].arrayBuffer())
+            )
             homePublicKey = await key.importRSAPublicKey(
                 password.home_public_key
             )
@@ -115,7 +118,7 @@ function tryParseSignallingServerAddr(ipAndPort: string): string {
     let ip = split[0]
     if (!isIP(ip)) return null
 
-    let port = split.length == 2 ? split[1] : core.DEFAULT_HAMMEREGG_PORT
+    let port = split.length == 2 ? split[1] : core.defaultHammereggPort()
     if (port < 0 || port >= 1 << 16) return null
 
     return `${ip}:${port}`
@@ -169,30 +172,79 @@ function initSignallingConnection(
         id: 0,
         negotiated: true,
     })
-    let allCandidatesGathered = new Promise<RTCSessionDescription>(
-        (resolve, _) => {
-            peerConnection.onicecandidate = e => {
-                if (e.candidate === null) {
-                    resolve(peerConnection.localDescription)
-                }
-            }
+    let clipboardChannel = peerConnection.createDataChannel(
+        "hammeregg-clipboard",
+        {
+            id: 1,
+            negotiated: true,
         }
     )
-    peerConnection
+    let filesChannel = peerConnection.createDataChannel("hammeregg-files", {
+        id: 2,
+        negotiated: true,
+    })
+    let terminalChannel = peerConnection.createDataChannel(
+        "hammeregg-terminal",
+        {
+            id: 3,
+            negotiated: true,
+        }
+    )
+    let localDescriptionSet = peerConnection
         .createOffer()
         .then(d => peerConnection.setLocalDescription(d))
 
+    // ICE candidates are trickled over the signalling connection
+    // once the offer is sent, encrypted with the offer's AES key.
+    // The home computer does the same with the answer's AES key.
+    let offerKey: CryptoKey = null
+    let answerKey: CryptoKey = null
+    let pendingCandidates: RTCIceCandidate[] = []
+    let sendCandidate = async (candidate: RTCIceCandidate) => {
+        let aesIV = key.generateIV()
+        let encryptedCandidate = (await crypto.subtle.encrypt(
+            { name: "AES-GCM", iv: aesIV },
+            offerKey,
+            key.string2Buffer(JSON.stringify(candidate))
+        )) as ArrayBuffer
+        signallingConnection.send(
+            core.encodeHandshakePacket(codec, {
+                type: "RemoteIceCandidate",
+                peer: 0, // this is filled in by Rooster
+                iv: key.buffer2Array(aesIV),
+                payload: key.buffer2Array(encryptedCandidate),
+            })
+        )
+    }
+    peerConnection.onicecandidate = e => {
+        // a null candidate means that gathering is done
+        if (e.candidate === null) return
+        if (offerKey === null) {
+            pendingCandidates.push(e.candidate)
+        } else if (signallingConnection.readyState == WebSocket.OPEN) {
+            sendCandidate(e.candidate)
+        }
+    }
+
+    // the signalling connection is only needed until
+    // the two computers find a way to reach each other
+    peerConnection.onconnectionstatechange = e => {
+        if (peerConnection.connectionState == "connected") {
+            signallingConnection.close()
+        }
+    }
+
+    // the init response is always BSON, everything
+    // after it uses the codec that Rooster picks
+    let codec: core.Codec = "Bson"
+
     // init signalling connection
     let signallingConnection = new WebSocket("wss://" + signallingAddr)
     signallingConnection.binaryType = "arraybuffer"
     signallingConnection.onopen = e => {
-        let initPacket = BSON.serialize(<core.HandshakeInitPacket>{
-            magic: core.MAGIC,
-            version: core.VERSION_1_0,
-            packet: <core.RemoteInitHandshakePacket>{
-                type: core.HandshakePacketType.REMOTE_INIT,
-                home_name: desktopName,
-            },
+        let initPacket = core.encodeInitPacket({
+            type: "RemoteInit",
+            home_name: desktopName,
         })
         signallingConnection.send(initPacket)
         state = state.next()
@@ -200,22 +252,30 @@ function initSignallingConnection(
 
     signallingConnection.onmessage = async e => {
         try {
-            let packet = BSON.deserialize(
+            let packet = core.decodeHandshakePacket(
+                codec,
                 new Uint8Array(e.data)
-            ) as core.HandshakePacket
+            )
             console.log("Recieved packet:", packet)
 
             switch (packet.type) {
-                case core.HandshakePacketType.REMOTE_INIT_RESPONSE: {
+                case "RemoteInitResponse": {
                     assertState("waitRemoteInitResponse")
-                    let initResponse =
-                        packet as core.RemoteInitResponseHandshakePacket
-                    if (initResponse.response.hasOwnProperty("Err")) {
-                        throw (initResponse.response as core.ResultErr).Err
+                    if ("Err" in packet.response) {
+                        throw core.formatErrorMsg(packet.response.Err)
                     } else {
+                        codec = packet.codec ?? "Bson"
+                        console.log(
+                            "Negotiated protocol version",
+                            packet.version,
+                            "and codec",
+                            codec
+                        )
+
                         // wait for our session description
+                        await localDescriptionSet
                         let localSessionDescription = JSON.stringify(
-                            await allCandidatesGathered
+                            peerConnection.localDescription
                         )
 
                         // generate a random aes key and encrypt our payload
@@ -233,38 +293,44 @@ function initSignallingConnection(
                             key.HAMMEREGG_RSA_PARAMS
                         )
 
-                        let out: core.RemoteOfferHandshakePacket = {
-                            type: core.HandshakePacketType.REMOTE_OFFER,
+                        let out: core.HandshakePacket = {
+                            type: "RemoteOffer",
                             peer: 0, // this is filled in by Rooster
                             key: key.buffer2Array(exportedKey),
                             iv: key.buffer2Array(aesIV),
                             payload: key.buffer2Array(encryptedLocalSD),
                         }
                         console.log("Sending remote offer ", out)
-                        signallingConnection.send(BSON.serialize(out))
+                        signallingConnection.send(
+                            core.encodeHandshakePacket(codec, out)
+                        )
                         state = state.next()
+
+                        // send the candidates gathered so far
+                        offerKey = aesKey
+                        pendingCandidates.forEach(sendCandidate)
+                        pendingCandidates = []
                     }
                     break
                 }
-                case core.HandshakePacketType.HOME_ANSWER_SUCCESS: {
+                case "HomeAnswerSuccess": {
                     assertState("waitHomeAnswerResponse")
-                    let answer = packet as core.HomeAnswerSuccessHandshakePacket
 
                     // decrypt payload
                     let aesKey = await crypto.subtle.unwrapKey(
                         "raw",
-                        key.array2Buffer(answer.key),
+                        key.array2Buffer(packet.key),
                         remotePrivateKey,
                         key.HAMMEREGG_RSA_PARAMS,
                         key.HAMMEREGG_AES_PARAMS,
                         false,
                         ["decrypt"]
                     )
-                    let aesIV = key.array2Buffer(answer.iv)
+                    let aesIV = key.array2Buffer(packet.iv)
                     let decryptedAnswer = (await crypto.subtle.decrypt(
                         { name: "AES-GCM", iv: aesIV },
                         aesKey,
-                        key.array2Buffer(answer.payload)
+                        key.array2Buffer(packet.payload)
                     )) as ArrayBuffer
 
                     let remoteSessionDescription = JSON.parse(
@@ -276,20 +342,48 @@ function initSignallingConnection(
                     )
 
                     // set remote description!
-                    peerConnection.setRemoteDescription(
+                    answerKey = aesKey
+                    await peerConnection.setRemoteDescription(
                         new RTCSessionDescription(remoteSessionDescription)
                     )
                     state = state.next()
-                    signallingConnection.close()
 
                     // show the actual desktop
-                    showStream(inputChannel)
+                    showStream(
+                        inputChannel,
+                        clipboardChannel,
+                        filesChannel,
+                        terminalChannel,
+                        codec
+                    )
+                    break
+                }
+                case "HomeIceCandidate": {
+                    assertState("done")
+
+                    // decrypt payload
+                    let aesIV = key.array2Buffer(packet.iv)
+                    let decryptedCandidate = (await crypto.subtle.decrypt(
+                        { name: "AES-GCM", iv: aesIV },
+                        answerKey,
+                        key.array2Buffer(packet.payload)
+                    )) as ArrayBuffer
+
+                    let candidate = JSON.parse(
+                        key.buffer2String(decryptedCandidate)
+                    )
+                    console.log("Received ICE candidate ", candidate)
+                    await peerConnection
+                        .addIceCandidate(candidate)
+                        .catch(e => console.warn("Invalid ICE candidate:", e))
                     break
                 }
-                case core.HandshakePacketType.HOME_ANSWER_FAILURE: {
+                case "HomeAnswerFailure": {
                     assertState("waitHomeAnswerResponse")
-                    let answer = packet as core.HomeAnswerFailureHandshakePacket
-                    throw answer.error
+                    if (packet.error.code === "DecryptFailed") {
+                        throw "the home computer couldn't read the offer, is the password correct?"
+                    }
+                    throw core.formatErrorMsg(packet.error)
                 }
             }
         } catch (e) {
@@ -301,12 +395,21 @@ function initSignallingConnection(
     }
 }
 
-function showStream(inputChannel: RTCDataChannel) {
+function showStream(
+    inputChannel: RTCDataChannel,
+    clipboardChannel: RTCDataChannel,
+    filesChannel: RTCDataChannel,
+    terminalChannel: RTCDataChannel,
+    codec: core.Codec
+) {
     $("setup-wrapper").classList.add("hidden")
     $("stream-wrapper").classList.remove("hidden")
 
     let streamVideo = $("stream-video") as HTMLVideoElement
     streamVideo.autoplay = true
     streamVideo.controls = false
-    input.setup(inputChannel, streamVideo)
+    input.setup(inputChannel, streamVideo, codec)
+    clipboard.setup(clipboardChannel, codec)
+    browser.setup(files.setup(filesChannel, codec), streamVideo)
+    terminal.setup(terminalChannel, codec, streamVideo)
 }
@@ -1,78 +1,266 @@
-import * as BSON from "bson"
-import { Input, KeyInput, MouseButton, SpecialKeyInput } from "./hammeregg_core"
+import * as core from "./hammeregg_core"
+import { KeyInput, MouseButton, SpecialKeyInput } from "./hammeregg_core"
+import { pollGamepad } from "./gamepad"
+
+/** jquery is dead, long live jquery! */
+let $ = (id: string) => document.getElementById(id)
 
 /**
  * Sets up sending input to a home computer.
  */
-export function setup(channel: RTCDataChannel, video: HTMLVideoElement) {
-    let keyHandler = (e: KeyboardEvent, ty: string) => {
-        if (!e.isComposing) {
-            let keyInput = keyEventToKeyInput(e)
-            if (keyInput !== null) {
-                channel.send(BSON.serialize({ [ty]: keyInput }))
-            }
+export function setup(
+    channel: RTCDataChannel,
+    video: HTMLVideoElement,
+    codec: core.Codec
+) {
+    let send = (packet: core.InputPacket) =>
+        channel.send(core.encodeInputPacket(codec, packet))
+    pollGamepad(channel, send)
+    let specialKeyMap = browser2EnigoSpecialKeyMap()
+    let keyCodes = new Set<string>(core.keyCodes())
+    // physical keys press the key at the same position on
+    // the home keyboard, which is what games and shortcuts
+    // want, but types the wrong characters if the two
+    // keyboards have different layouts
+    let physicalKeys = false
+
+    $("physical-keys").onclick = () => {
+        physicalKeys = !physicalKeys
+        $("physical-keys").innerText = `Physical Keys: ${
+            physicalKeys ? "On" : "Off"
+        }`
+        // give focus back to the stream
+        video.focus()
+    }
+
+    video.onkeydown = e => {
+        if (physicalKeys && keyCodes.has(e.code)) {
+            send({ key_down: { code: e.code as core.KeyCode } })
+            return
+        }
+        if (isTextKey(e)) {
+            // typed all at once, so there's nothing to release
+            send({ text: e.key })
+            return
         }
+        let keyInput = keyEventToKeyInput(e, specialKeyMap)
+        if (keyInput !== null) send({ key_down: keyInput })
+    }
+    video.onkeyup = e => {
+        if (physicalKeys && keyCodes.has(e.code)) {
+            send({ key_up: { code: e.code as core.KeyCode } })
+            return
+        }
+        if (isTextKey(e)) return
+        let keyInput = keyEventToKeyInput(e, specialKeyMap)
+        if (keyInput !== null) send({ key_up: keyInput })
+    }
+
+    // keys and buttons that are released while the stream
+    // doesn't have focus never get a keyup or mouseup, so
+    // release everything rather than leaving them stuck
+    video.onblur = () => send({ release_all: {} })
+
+    // pointer lock hides the cursor and sends how far the mouse
+    // moved instead of where it is, so that games and 3D tools
+    // that keep moving the cursor back to the middle of the
+    // screen still work. The stream is locked when it's clicked.
+    let pointerLock = false
+    let isLocked = () => document.pointerLockElement === video
+    // whether the button that locked the pointer is still down
+    let lockingClick = false
+    // the movement that's been rounded off so far, since
+    // browsers can report movement in fractions of a pixel
+    let remainderX = 0
+    let remainderY = 0
+
+    $("pointer-lock").onclick = () => {
+        pointerLock = !pointerLock
+        $("pointer-lock").innerText = `Pointer Lock: ${
+            pointerLock ? "On" : "Off"
+        }`
+        // give focus back to the stream
+        video.focus()
     }
-    video.onkeydown = e => keyHandler(e, "key_down")
-    video.onkeyup = e => keyHandler(e, "key_up")
 
-    let buttHandler = (e: MouseEvent, ty: string) => {
-        let button: MouseButton
+    $("type-clipboard").onclick = async () => {
+        try {
+            typeText(send, await navigator.clipboard.readText())
+        } catch (e) {
+            console.error("Couldn't read clipboard:", e)
+        }
+        // give focus back to the stream
+        video.focus()
+    }
+
+    let mouseEventToButton = (e: MouseEvent): MouseButton | null => {
         switch (e.button) {
             case 0:
-                button = MouseButton.Left
-                break
+                return "Left"
             case 1:
-                button = MouseButton.Middle
-                break
+                return "Middle"
             case 2:
-                button = MouseButton.Right
-                break
+                return "Right"
             default:
-                return
+                return null
         }
-        channel.send(BSON.serialize({ [ty]: button }))
     }
-    video.onmousedown = e => buttHandler(e, "mouse_down")
-    video.onmouseup = e => buttHandler(e, "mouse_up")
+    video.onmousedown = e => {
+        if (pointerLock && !isLocked()) {
+            // this click only locks the pointer
+            video.requestPointerLock()
+            lockingClick = true
+            return
+        }
+        let button = mouseEventToButton(e)
+        if (button !== null) send({ mouse_down: button })
+    }
+    video.onmouseup = e => {
+        if (lockingClick) {
+            lockingClick = false
+            return
+        }
+        let button = mouseEventToButton(e)
+        if (button !== null) send({ mouse_up: button })
+    }
 
+    // one wheel click per event, since how far each event
+    // scrolls depends on the browser and the mouse
     video.onwheel = e =>
-        channel.send(
-            BSON.serialize({ mouse_scroll: { x: e.clientX, y: e.clientY } })
-        )
+        send({
+            mouse_scroll: { x: Math.sign(e.deltaX), y: Math.sign(e.deltaY) },
+        })
 
     video.onmousemove = e => {
-        // calculate actual video bounds
-        let windowRatio = window.innerHeight / window.innerWidth
-        let videoRatio = video.videoHeight / video.videoWidth
-        let minX: number
-        let minY: number
-        let scaledW: number
-        let scaledH: number
-        if (videoRatio >= windowRatio) {
-            minX = (window.innerWidth - window.innerHeight / videoRatio) / 2
-            minY = 0
-            scaledW = window.innerWidth - minX * 2
-            scaledH = window.innerHeight
-        } else {
-            minX = 0
-            minY = (window.innerHeight - window.innerWidth * videoRatio) / 2
-            scaledW = window.innerWidth
-            scaledH = window.innerHeight - minY * 2
+        if (isLocked()) {
+            let dx = e.movementX + remainderX
+            let dy = e.movementY + remainderY
+            remainderX = dx - Math.round(dx)
+            remainderY = dy - Math.round(dy)
+            dx = Math.round(dx)
+            dy = Math.round(dy)
+            if (dx !== 0 || dy !== 0) {
+                send({ mouse_move_relative: { dx: dx, dy: dy } })
+            }
+            return
+        }
+
+        let { x, y } = videoPosition(video, e)
+        send({ mouse_move: { x: x, y: y } })
+    }
+
+    // touches and pens are sent as they are instead of as
+    // mouse clicks, so that gestures and pressure work
+    video.style.touchAction = "none"
+    let onPointer = (e: PointerEvent, state: core.ContactState) => {
+        if (e.pointerType === "mouse") return
+        // stop the browser from also sending mouse events
+        e.preventDefault()
+        let { x, y } = videoPosition(video, e)
+        let pressure = Math.min(Math.max(e.pressure, 0), 1)
+        if (e.pointerType === "touch") {
+            // fingers don't hover, so their moves are contacts
+            if (state === "Hover") state = "Contact"
+            let id = e.pointerId
+            send({ touch: { id, x, y, pressure, state } })
+        } else if (e.pointerType === "pen") {
+            let tilt_x = e.tiltX
+            let tilt_y = e.tiltY
+            send({ pen: { x, y, pressure, tilt_x, tilt_y, state } })
         }
+    }
+    video.onpointerdown = e => onPointer(e, "Contact")
+    video.onpointermove = e => onPointer(e, e.buttons & 1 ? "Contact" : "Hover")
+    video.onpointerup = e =>
+        onPointer(e, e.pointerType === "pen" ? "Hover" : "Lifted")
+    video.onpointercancel = e => onPointer(e, "Lifted")
+    video.onpointerleave = e => {
+        if (e.pointerType === "pen") onPointer(e, "Lifted")
+    }
+}
+
+/**
+ * Gets where a mouse event happened on the stream,
+ * normalized so that the stream goes from 0 to 1.
+ */
+function videoPosition(
+    video: HTMLVideoElement,
+    e: MouseEvent
+): { x: number; y: number } {
+    // calculate actual video bounds
+    let windowRatio = window.innerHeight / window.innerWidth
+    let videoRatio = video.videoHeight / video.videoWidth
+    let minX: number
+    let minY: number
+    let scaledW: number
+    let scaledH: number
+    if (videoRatio >= windowRatio) {
+        minX = (window.innerWidth - window.innerHeight / videoRatio) / 2
+        minY = 0
+        scaledW = window.innerWidth - minX * 2
+        scaledH = window.innerHeight
+    } else {
+        minX = 0
+        minY = (window.innerHeight - window.innerWidth * videoRatio) / 2
+        scaledW = window.innerWidth
+        scaledH = window.innerHeight - minY * 2
+    }
 
-        let x = (e.clientX - minX) / scaledW
-        let y = (e.clientY - minY) / scaledH
-        channel.send(BSON.serialize({ mouse_move: { x: x, y: y } }))
+    return {
+        x: (e.clientX - minX) / scaledW,
+        y: (e.clientY - minY) / scaledH,
     }
 }
 
+/**
+ * Types text on the home computer, split into packets
+ * that are small enough to send. Control characters
+ * other than newlines and tabs are left out, since
+ * they can't be typed.
+ */
+function typeText(
+    send: (packet: core.InputPacket) => void,
+    text: string
+) {
+    let maxSize = core.maxInputTextSize()
+    let encoder = new TextEncoder()
+    let chunk = ""
+    let chunkSize = 0
+    for (let c of text.replace(/\r\n?/g, "\n")) {
+        let code = c.codePointAt(0)
+        let isControl = code < 0x20 || (code >= 0x7f && code <= 0x9f)
+        if (isControl && c !== "\n" && c !== "\t") continue
+
+        let size = encoder.encode(c).length
+        if (chunkSize + size > maxSize) {
+            send({ text: chunk })
+            chunk = ""
+            chunkSize = 0
+        }
+        chunk += c
+        chunkSize += size
+    }
+    if (chunk !== "") send({ text: chunk })
+}
+
+/**
+ * Checks if a key event types a single character that
+ * has no key of its own, such as an accented letter
+ * from a dead key or a character from another script.
+ * These are sent as text so that they come out the
+ * same on any keyboard layout.
+ */
+function isTextKey(e: KeyboardEvent): boolean {
+    if (e.isComposing || e.ctrlKey || e.altKey || e.metaKey) return false
+    let chars = Array.from(e.key)
+    return chars.length === 1 && chars[0] > "~"
+}
+
 // see https://developer.mozilla.org/en-US/docs/Web/API/KeyboardEvent/key/Key_Values
 // for all key values
-const BROWSER2ENIGO_SPECIAL_KEY_MAP = new Map(
-    Object.keys(SpecialKeyInput)
-        .filter(item => isNaN(Number(item)))
-        .map(enigoKey => {
+function browser2EnigoSpecialKeyMap(): Map<string, SpecialKeyInput> {
+    return new Map(
+        core.specialKeys().map(enigoKey => {
             let browserKey: string
 
             if (enigoKey.endsWith("Arrow")) {
@@ -87,15 +275,28 @@ const BROWSER2ENIGO_SPECIAL_KEY_MAP = new Map(
                 browserKey = enigoKey
             }
 
-            return [browserKey, enigoKey as SpecialKeyInput]
+            return [browserKey, enigoKey]
         })
-)
+    )
+}
 
-function keyEventToKeyInput(e: KeyboardEvent): KeyInput | null {
-    if (e.key >= "!" && e.key <= "~") {
+function keyEventToKeyInput(
+    e: KeyboardEvent,
+    specialKeyMap: Map<string, SpecialKeyInput>
+): KeyInput | null {
+    if (e.isComposing) {
+        return null
+    } else if (
+        e.location === KeyboardEvent.DOM_KEY_LOCATION_NUMPAD &&
+        specialKeyMap.has(e.code)
+    ) {
+        // numpad keys have the same key values as the main
+        // keyboard, so they're told apart by their codes
+        return { special_key: specialKeyMap.get(e.code) }
+    } else if (e.key >= "!" && e.key <= "~") {
         return { alpha_key: e.key }
-    } else if (BROWSER2ENIGO_SPECIAL_KEY_MAP.has(e.key)) {
-        return { special_key: BROWSER2ENIGO_SPECIAL_KEY_MAP.get(e.key) }
+    } else if (specialKeyMap.has(e.key)) {
+        return { special_key: specialKeyMap.get(e.key) }
     } else if (e.hasOwnProperty("keyCode")) {
         // @ts-ignore
         return { raw_key: e.keyCode }
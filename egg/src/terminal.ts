@@ -0,0 +1,135 @@
+import * as core from "./hammeregg_core"
+
+/** jquery is dead, long live jquery! */
+let $ = (id: string) => document.getElementById(id)
+
+const COLS = 80
+const ROWS = 24
+
+/**
+ * Matches the escape sequences that a shell sends to
+ * move the cursor or change colors, which this plain
+ * text terminal doesn't understand.
+ */
+const ESCAPE_SEQUENCE =
+    /\x1b(\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(\x07|\x1b\\)|[()][0-9A-Za-z]|[=>78])/g
+
+/**
+ * Gets the bytes that a key press sends to the
+ * shell, or null if it doesn't send anything.
+ */
+function keyBytes(e: KeyboardEvent): string {
+    if (e.ctrlKey && e.key.length === 1) {
+        let code = e.key.toUpperCase().charCodeAt(0)
+        if (code >= 0x40 && code <= 0x5f) {
+            return String.fromCharCode(code - 0x40)
+        }
+    }
+    switch (e.key) {
+        case "Enter":
+            return "\r"
+        case "Backspace":
+            return "\x7f"
+        case "Tab":
+            return "\t"
+        case "Escape":
+            return "\x1b"
+        case "ArrowUp":
+            return "\x1b[A"
+        case "ArrowDown":
+            return "\x1b[B"
+        case "ArrowRight":
+            return "\x1b[C"
+        case "ArrowLeft":
+            return "\x1b[D"
+    }
+    return e.key.length === 1 ? e.key : null
+}
+
+/**
+ * Sets up the remote terminal, which runs a shell on
+ * the home computer. This only shows the shell's text,
+ * so programs that draw on the whole screen won't look
+ * right, but it's enough to run a few commands.
+ */
+export function setup(
+    channel: RTCDataChannel,
+    codec: core.Codec,
+    streamVideo: HTMLVideoElement
+) {
+    channel.binaryType = "arraybuffer"
+
+    let panel = $("terminal")
+    let output = $("terminal-output")
+    let decoder = new TextDecoder()
+    let open = false
+
+    let send = (packet: core.TerminalPacket) =>
+        channel.send(core.encodeTerminalPacket(codec, packet))
+
+    let write = (text: string) => {
+        for (let c of text.replace(ESCAPE_SEQUENCE, "")) {
+            if (c === "\b") {
+                output.textContent = output.textContent.slice(0, -1)
+            } else if (c !== "\r" && c !== "\x07") {
+                output.textContent += c
+            }
+        }
+        output.scrollTop = output.scrollHeight
+    }
+
+    channel.onmessage = e => {
+        let packet: core.TerminalPacket
+        try {
+            packet = core.decodeTerminalPacket(codec, new Uint8Array(e.data))
+        } catch (e) {
+            console.error("Invalid terminal packet:", e)
+            return
+        }
+
+        if ("data" in packet) {
+            let data = new Uint8Array(packet.data)
+            write(decoder.decode(data, { stream: true }))
+        } else if ("exit" in packet) {
+            open = false
+            let { success, error } = packet.exit
+            if (error) {
+                write(`\n[${core.formatErrorMsg(error)}]\n`)
+            } else {
+                write(`\n[shell exited${success ? "" : " with an error"}]\n`)
+            }
+        }
+    }
+
+    output.onkeydown = e => {
+        let bytes = keyBytes(e)
+        if (!open || bytes === null) return
+        e.preventDefault()
+        channel.send(
+            core.encodeTerminalData(codec, new TextEncoder().encode(bytes))
+        )
+    }
+
+    output.onpaste = e => {
+        e.preventDefault()
+        if (!open) return
+        let text = e.clipboardData.getData("text/plain")
+        channel.send(
+            core.encodeTerminalData(codec, new TextEncoder().encode(text))
+        )
+    }
+
+    $("terminal-toggle").onclick = () => {
+        if (panel.classList.toggle("hidden")) {
+            // give focus back to the stream
+            streamVideo.focus()
+            return
+        }
+        if (!open) {
+            open = true
+            output.textContent = ""
+            send({ open: { cols: COLS, rows: ROWS } })
+        }
+        output.focus()
+    }
+}
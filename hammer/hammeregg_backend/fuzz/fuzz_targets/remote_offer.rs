@@ -0,0 +1,104 @@
+//! Runs remote offers through the same checks that
+//! Desktop does before starting a stream: decoding,
+//! validation, and then decrypting the offer.
+//!
+//! If the first byte is even, the rest of the input
+//! is an encoded packet straight from the network.
+//! Otherwise, the input is used as an init vector and
+//! offer that are properly encrypted with a fixed AES
+//! key first, and then sent through the same checks
+//! in a remote offer, so that the fuzzer can reach
+//! past decryption. The AES key is only RSA-encrypted
+//! once, since RSA-4096 would make every run far
+//! slower.
+
+#![no_main]
+#![feature(once_cell)]
+
+#[allow(dead_code)]
+#[path = "../../src/key.rs"]
+mod key;
+
+use std::lazy::SyncLazy;
+
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, Key as AesGcmKey, NewAead, Nonce};
+use hammeregg_core::limits::AES_IV_SIZE;
+use hammeregg_core::{deserialize_packet, serialize_packet, HandshakePacket, SUPPORTED_CODECS, VERSION_1_1};
+use libfuzzer_sys::fuzz_target;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use rsa::{PublicKey, RsaPrivateKey, RsaPublicKey};
+use validator::Validate;
+
+static HOME_PRIVATE_KEY: SyncLazy<RsaPrivateKey> = SyncLazy::new(|| key::gen_home_and_remote_keys().0);
+
+/// The AES key that round-tripped offers are encrypted with.
+const AES_KEY: [u8; key::AES_KEY_SIZE] = [0x42; key::AES_KEY_SIZE];
+
+/// [`AES_KEY`], encrypted with the home public key.
+static ENCRYPTED_AES_KEY: SyncLazy<Vec<u8>> = SyncLazy::new(|| {
+    RsaPublicKey::from(&*HOME_PRIVATE_KEY)
+        .encrypt(&mut ChaCha20Rng::from_entropy(), key::padding_scheme(), &AES_KEY)
+        .unwrap()
+});
+
+fuzz_target!(|data: &[u8]| {
+    let (selector, data) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let codec = SUPPORTED_CODECS[(*selector as usize / 2) % SUPPORTED_CODECS.len()];
+    if selector % 2 == 0 {
+        if let Ok(packet) = deserialize_packet::<_, HandshakePacket>(&codec, data) {
+            if let (
+                Ok(()),
+                HandshakePacket::RemoteOffer {
+                    key: encrypted_key,
+                    iv,
+                    payload,
+                    ..
+                },
+            ) = (packet.validate(), &packet)
+            {
+                let _ = key::decrypt_offer(&HOME_PRIVATE_KEY, encrypted_key, iv, payload);
+            }
+        }
+    } else if data.len() >= AES_IV_SIZE {
+        let (iv, offer) = data.split_at(AES_IV_SIZE);
+
+        let payload = Aes256Gcm::new(AesGcmKey::from_slice(&AES_KEY))
+            .encrypt(Nonce::from_slice(iv), offer)
+            .unwrap();
+        let packet = HandshakePacket::RemoteOffer {
+            peer: 0,
+            key: ENCRYPTED_AES_KEY.clone(),
+            iv: iv.to_vec(),
+            payload,
+            codec,
+            version: VERSION_1_1,
+        };
+
+        let encoded = serialize_packet(&codec, &packet).unwrap();
+        let decoded = deserialize_packet::<_, HandshakePacket>(&codec, &encoded).unwrap();
+        assert_eq!(decoded, packet);
+        // Offers that are too large are rejected here
+        if decoded.validate().is_err() {
+            return;
+        }
+        if let HandshakePacket::RemoteOffer {
+            key: encrypted_key,
+            iv,
+            payload,
+            ..
+        } = &decoded
+        {
+            let decrypted = key::decrypt_offer(&HOME_PRIVATE_KEY, encrypted_key, iv, payload);
+            assert_eq!(
+                decrypted.ok().map(|(_, offer)| offer),
+                String::from_utf8(offer.to_vec()).ok()
+            );
+        }
+    }
+});
@@ -0,0 +1,78 @@
+//! Clipboard synchronization between the home
+//! computer and the remote.
+
+use std::os::raw::c_void;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use anyhow::Result;
+use copypasta::{ClipboardContext, ClipboardProvider};
+use hammeregg_core::{serialize_packet, ClipboardPacket, Codec};
+use validator::Validate;
+
+use crate::pion;
+use crate::pion::PeerConnection;
+
+/// How often the home clipboard is checked for changes.
+/// Not every platform can notify us when the clipboard
+/// changes, so it's polled instead.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Synchronizes the home clipboard with the remote until
+/// `clipboard_rx` hangs up. Clipboard packets received from
+/// `clipboard_rx` are written to the home clipboard, and
+/// changes to the home clipboard are sent to the remote
+/// over the connection's clipboard channel.
+pub fn sync_clipboard(connection: PeerConnection, codec: Codec, clipboard_rx: Receiver<ClipboardPacket>) {
+    let mut clipboard = match ClipboardContext::new() {
+        Ok(clipboard) => clipboard,
+        Err(err) => {
+            eprintln!("Couldn't open clipboard: {}", err);
+            return;
+        }
+    };
+
+    // The last text that both sides have seen, so that
+    // clipboard changes aren't echoed back to the remote
+    let mut last_text = None;
+    loop {
+        match clipboard_rx.recv_timeout(POLL_INTERVAL) {
+            Ok(ClipboardPacket::Text(text)) => match clipboard.set_contents(text.clone()) {
+                Ok(()) => last_text = Some(text),
+                Err(err) => eprintln!("Couldn't set clipboard: {}", err),
+            },
+            Err(RecvTimeoutError::Timeout) => {
+                // Clipboards that don't hold text can't be read
+                let text = match clipboard.get_contents() {
+                    Ok(text) => text,
+                    Err(_) => continue,
+                };
+                if last_text.as_ref() == Some(&text) {
+                    continue;
+                }
+
+                let packet = ClipboardPacket::Text(text.clone());
+                if let Err(errors) = packet.validate() {
+                    eprintln!("Not sending clipboard to remote: {}", errors);
+                } else {
+                    match send_clipboard(connection, codec, &packet) {
+                        Ok(true) => {}
+                        // The channel isn't open yet, try again later
+                        Ok(false) => continue,
+                        Err(err) => eprintln!("Couldn't send clipboard to remote: {:?}", err),
+                    }
+                }
+                last_text = Some(text);
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// Sends a clipboard packet to the remote, returning
+/// whether the clipboard channel was open.
+fn send_clipboard(connection: PeerConnection, codec: Codec, packet: &ClipboardPacket) -> Result<bool> {
+    let bytes = serialize_packet(&codec, packet)?;
+    let sent = unsafe { pion::hammer_rtp2rtc_send_clipboard(connection, bytes.as_ptr() as *const c_void, bytes.len()) };
+    Ok(sent != 0)
+}
@@ -0,0 +1,205 @@
+//! Virtual gamepads, which let remotes play games on
+//! the home computer with their own controllers.
+
+pub use self::platform::VirtualGamepad;
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use anyhow::Result;
+    use hammeregg_core::{GamepadAxis, GamepadButton};
+    use nix::libc::input_id;
+
+    use crate::uinput::{AbsAxis, Capabilities, UinputDevice, ABS_X, ABS_Y, BUS_USB, EV_ABS, EV_KEY};
+
+    const BTN_A: u16 = 0x130;
+    const BTN_B: u16 = 0x131;
+    const BTN_X: u16 = 0x133;
+    const BTN_Y: u16 = 0x134;
+    const BTN_TL: u16 = 0x136;
+    const BTN_TR: u16 = 0x137;
+    const BTN_SELECT: u16 = 0x13a;
+    const BTN_START: u16 = 0x13b;
+    const BTN_MODE: u16 = 0x13c;
+    const BTN_THUMBL: u16 = 0x13d;
+    const BTN_THUMBR: u16 = 0x13e;
+
+    const ABS_Z: u16 = 0x02;
+    const ABS_RX: u16 = 0x03;
+    const ABS_RY: u16 = 0x04;
+    const ABS_RZ: u16 = 0x05;
+    const ABS_HAT0X: u16 = 0x10;
+    const ABS_HAT0Y: u16 = 0x11;
+
+    const BUTTONS: [u16; 11] = [
+        BTN_A, BTN_B, BTN_X, BTN_Y, BTN_TL, BTN_TR, BTN_SELECT, BTN_START, BTN_MODE, BTN_THUMBL, BTN_THUMBR,
+    ];
+
+    const STICK_MAX: i32 = 32767;
+    const TRIGGER_MAX: i32 = 255;
+
+    /// The axes of an Xbox 360 controller, as reported
+    /// by Linux's xpad driver.
+    const AXES: [AbsAxis; 8] = [
+        stick(ABS_X),
+        stick(ABS_Y),
+        stick(ABS_RX),
+        stick(ABS_RY),
+        trigger(ABS_Z),
+        trigger(ABS_RZ),
+        dpad(ABS_HAT0X),
+        dpad(ABS_HAT0Y),
+    ];
+
+    const fn stick(code: u16) -> AbsAxis {
+        AbsAxis {
+            code,
+            min: -STICK_MAX - 1,
+            max: STICK_MAX,
+            fuzz: 16,
+            flat: 128,
+            resolution: 0,
+        }
+    }
+
+    const fn trigger(code: u16) -> AbsAxis {
+        AbsAxis {
+            code,
+            min: 0,
+            max: TRIGGER_MAX,
+            fuzz: 0,
+            flat: 0,
+            resolution: 0,
+        }
+    }
+
+    const fn dpad(code: u16) -> AbsAxis {
+        AbsAxis {
+            code,
+            min: -1,
+            max: 1,
+            fuzz: 0,
+            flat: 0,
+            resolution: 0,
+        }
+    }
+
+    /// A virtual Xbox 360 controller. It uses the same
+    /// ids and layout as a real one, so games and SDL
+    /// recognize it without any extra setup.
+    pub struct VirtualGamepad {
+        device: UinputDevice,
+        /// Which of up, down, left and right are held. The
+        /// D-pad is reported as two axes, so opposite
+        /// directions cancel out.
+        dpad: [bool; 4],
+    }
+
+    impl VirtualGamepad {
+        pub fn create() -> Result<Self> {
+            let id = input_id {
+                bustype: BUS_USB,
+                vendor: 0x045e,
+                product: 0x028e,
+                version: 0x0110,
+            };
+            let capabilities = Capabilities {
+                keys: &BUTTONS,
+                axes: &AXES,
+                ..Default::default()
+            };
+            let device = UinputDevice::create("Hammeregg Virtual Gamepad", id, capabilities)?;
+            Ok(Self {
+                device,
+                dpad: [false; 4],
+            })
+        }
+
+        /// Presses or releases a button.
+        pub fn button(&mut self, button: GamepadButton, pressed: bool) -> Result<()> {
+            let code = match button {
+                GamepadButton::A => BTN_A,
+                GamepadButton::B => BTN_B,
+                GamepadButton::X => BTN_X,
+                GamepadButton::Y => BTN_Y,
+                GamepadButton::LeftBumper => BTN_TL,
+                GamepadButton::RightBumper => BTN_TR,
+                GamepadButton::Back => BTN_SELECT,
+                GamepadButton::Start => BTN_START,
+                GamepadButton::Guide => BTN_MODE,
+                GamepadButton::LeftStick => BTN_THUMBL,
+                GamepadButton::RightStick => BTN_THUMBR,
+                GamepadButton::DPadUp => return self.dpad(0, pressed),
+                GamepadButton::DPadDown => return self.dpad(1, pressed),
+                GamepadButton::DPadLeft => return self.dpad(2, pressed),
+                GamepadButton::DPadRight => return self.dpad(3, pressed),
+            };
+            self.device.emit(EV_KEY, code, pressed as i32)?;
+            self.device.sync()
+        }
+
+        /// Moves an axis. Values outside of the
+        /// axis's range are clamped.
+        pub fn axis(&mut self, axis: GamepadAxis, value: f32) -> Result<()> {
+            let (code, max) = match axis {
+                GamepadAxis::LeftStickX => (ABS_X, STICK_MAX),
+                GamepadAxis::LeftStickY => (ABS_Y, STICK_MAX),
+                GamepadAxis::RightStickX => (ABS_RX, STICK_MAX),
+                GamepadAxis::RightStickY => (ABS_RY, STICK_MAX),
+                GamepadAxis::LeftTrigger => (ABS_Z, TRIGGER_MAX),
+                GamepadAxis::RightTrigger => (ABS_RZ, TRIGGER_MAX),
+            };
+            let min = if axis.is_trigger() { 0 } else { -max - 1 };
+            let value = ((value * max as f32).round() as i32).clamp(min, max);
+            self.device.emit(EV_ABS, code, value)?;
+            self.device.sync()
+        }
+
+        /// Releases every button and moves
+        /// every axis back to the middle.
+        pub fn reset(&mut self) -> Result<()> {
+            for button in BUTTONS {
+                self.device.emit(EV_KEY, button, 0)?;
+            }
+            for axis in AXES {
+                self.device.emit(EV_ABS, axis.code, 0)?;
+            }
+            self.dpad = [false; 4];
+            self.device.sync()
+        }
+
+        fn dpad(&mut self, direction: usize, pressed: bool) -> Result<()> {
+            self.dpad[direction] = pressed;
+            let [up, down, left, right] = self.dpad;
+            self.device.emit(EV_ABS, ABS_HAT0X, right as i32 - left as i32)?;
+            self.device.emit(EV_ABS, ABS_HAT0Y, down as i32 - up as i32)?;
+            self.device.sync()
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use anyhow::{anyhow, Result};
+    use hammeregg_core::{GamepadAxis, GamepadButton};
+
+    /// Virtual gamepads are only supported on Linux for now.
+    pub struct VirtualGamepad;
+
+    impl VirtualGamepad {
+        pub fn create() -> Result<Self> {
+            Err(anyhow!("Gamepads are only supported on Linux"))
+        }
+
+        pub fn button(&mut self, _button: GamepadButton, _pressed: bool) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn axis(&mut self, _axis: GamepadAxis, _value: f32) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn reset(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! The virtual gamepad, touchscreen and pen that
+//! every backend uses, since Enigo can't send them.
+
+use anyhow::Result;
+use hammeregg_core::{ContactState, GamepadAxis, GamepadButton};
+
+use crate::gamepad::VirtualGamepad;
+use crate::touch::{PenTablet, TouchScreen};
+
+/// A virtual device, which is created the
+/// first time that the remote uses it.
+enum LazyDevice<T> {
+    NotCreated,
+    Created(T),
+    /// Creating the device failed, so
+    /// it isn't tried again.
+    Failed,
+}
+
+impl<T> Default for LazyDevice<T> {
+    fn default() -> Self {
+        LazyDevice::NotCreated
+    }
+}
+
+impl<T> LazyDevice<T> {
+    /// Gets the device, creating it if
+    /// this is the first time it's used.
+    /// Returns `None` if creating it failed before.
+    fn get_or_create(&mut self, create: impl FnOnce() -> Result<T>) -> Result<Option<&mut T>> {
+        if let LazyDevice::NotCreated = self {
+            match create() {
+                Ok(device) => *self = LazyDevice::Created(device),
+                Err(err) => {
+                    *self = LazyDevice::Failed;
+                    return Err(err.context("Couldn't create virtual device"));
+                }
+            }
+        }
+        Ok(self.get())
+    }
+
+    /// Gets the device if it's been created.
+    fn get(&mut self) -> Option<&mut T> {
+        match self {
+            LazyDevice::Created(device) => Some(device),
+            _ => None,
+        }
+    }
+}
+
+/// A remote's gamepad, touchscreen and pen.
+#[derive(Default)]
+pub struct VirtualDevices {
+    gamepad: LazyDevice<VirtualGamepad>,
+    touch_screen: LazyDevice<TouchScreen>,
+    pen_tablet: LazyDevice<PenTablet>,
+}
+
+impl VirtualDevices {
+    pub fn gamepad_button(&mut self, button: GamepadButton, pressed: bool) -> Result<()> {
+        self.gamepad
+            .get_or_create(VirtualGamepad::create)?
+            .map_or(Ok(()), |gamepad| gamepad.button(button, pressed))
+    }
+
+    pub fn gamepad_axis(&mut self, axis: GamepadAxis, value: f32) -> Result<()> {
+        self.gamepad
+            .get_or_create(VirtualGamepad::create)?
+            .map_or(Ok(()), |gamepad| gamepad.axis(axis, value))
+    }
+
+    pub fn touch(&mut self, id: u32, x: f32, y: f32, pressure: f32, state: ContactState) -> Result<()> {
+        self.touch_screen
+            .get_or_create(TouchScreen::create)?
+            .map_or(Ok(()), |touch_screen| touch_screen.touch(id, x, y, pressure, state))
+    }
+
+    pub fn pen(&mut self, x: f32, y: f32, pressure: f32, tilt_x: f32, tilt_y: f32, state: ContactState) -> Result<()> {
+        self.pen_tablet
+            .get_or_create(PenTablet::create)?
+            .map_or(Ok(()), |pen_tablet| {
+                pen_tablet.pen(x, y, pressure, tilt_x, tilt_y, state)
+            })
+    }
+
+    /// Resets the gamepad and lifts every finger and the
+    /// pen, skipping any devices that weren't created.
+    pub fn reset(&mut self) -> Result<()> {
+        if let Some(gamepad) = self.gamepad.get() {
+            gamepad.reset()?;
+        }
+        if let Some(touch_screen) = self.touch_screen.get() {
+            touch_screen.lift_all()?;
+        }
+        if let Some(pen_tablet) = self.pen_tablet.get() {
+            pen_tablet.lift()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+//! Sends input with Enigo, which works everywhere
+//! except for Wayland on Linux.
+
+use anyhow::{anyhow, Result};
+use enigo::{Enigo, KeyboardControllable, MouseControllable};
+use hammeregg_core::{ContactState, GamepadAxis, GamepadButton};
+
+use super::devices::VirtualDevices;
+use super::{BackendKey, InputBackend};
+use crate::keyboard::PhysicalKeyboard;
+
+/// Sends keyboard and mouse input with Enigo, and
+/// physical keys with [`PhysicalKeyboard`].
+pub struct EnigoBackend {
+    enigo: Enigo,
+    /// Presses physical keys, which can't
+    /// be pressed if this is `None`.
+    keyboard: Option<PhysicalKeyboard>,
+    devices: VirtualDevices,
+}
+
+impl EnigoBackend {
+    pub fn new() -> Self {
+        let keyboard = PhysicalKeyboard::open()
+            .map_err(|err| eprintln!("Couldn't open keyboard for physical keys: {:?}", err))
+            .ok();
+        Self {
+            enigo: Enigo::new(),
+            keyboard,
+            devices: VirtualDevices::default(),
+        }
+    }
+}
+
+impl Default for EnigoBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputBackend for EnigoBackend {
+    fn key(&mut self, key: BackendKey, down: bool) -> Result<()> {
+        match (key, self.keyboard.as_mut()) {
+            (BackendKey::Physical(code), Some(keyboard)) => keyboard.key(code, down),
+            (BackendKey::Physical(_), None) => return Err(anyhow!("Physical keys aren't supported on this computer")),
+            (BackendKey::Enigo(key), _) if down => self.enigo.key_down(key),
+            (BackendKey::Enigo(key), _) => self.enigo.key_up(key),
+        }
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> Result<()> {
+        // This returns once the whole text has been typed,
+        // so it stays in order with the keys around it
+        self.enigo.key_sequence(text);
+        Ok(())
+    }
+
+    fn mouse_button(&mut self, button: enigo::MouseButton, down: bool) -> Result<()> {
+        if down {
+            self.enigo.mouse_down(button);
+        } else {
+            self.enigo.mouse_up(button);
+        }
+        Ok(())
+    }
+
+    fn mouse_move_to(&mut self, x: i32, y: i32) -> Result<()> {
+        self.enigo.mouse_move_to(x, y);
+        Ok(())
+    }
+
+    fn mouse_move_relative(&mut self, dx: i32, dy: i32) -> Result<()> {
+        self.enigo.mouse_move_relative(dx, dy);
+        Ok(())
+    }
+
+    fn mouse_scroll(&mut self, x: i32, y: i32) -> Result<()> {
+        if x != 0 {
+            self.enigo.mouse_scroll_x(x);
+        }
+        if y != 0 {
+            self.enigo.mouse_scroll_y(y);
+        }
+        Ok(())
+    }
+
+    fn gamepad_button(&mut self, button: GamepadButton, pressed: bool) -> Result<()> {
+        self.devices.gamepad_button(button, pressed)
+    }
+
+    fn gamepad_axis(&mut self, axis: GamepadAxis, value: f32) -> Result<()> {
+        self.devices.gamepad_axis(axis, value)
+    }
+
+    fn touch(&mut self, id: u32, x: f32, y: f32, pressure: f32, state: ContactState) -> Result<()> {
+        self.devices.touch(id, x, y, pressure, state)
+    }
+
+    fn pen(&mut self, x: f32, y: f32, pressure: f32, tilt_x: f32, tilt_y: f32, state: ContactState) -> Result<()> {
+        self.devices.pen(x, y, pressure, tilt_x, tilt_y, state)
+    }
+
+    fn reset_devices(&mut self) -> Result<()> {
+        self.devices.reset()
+    }
+}
@@ -0,0 +1,219 @@
+//! Input macros, which are recordings of the input that
+//! a remote sent during a session. They can be replayed
+//! later, to reproduce bugs or to repeat chores.
+//!
+//! Macros are stored as JSON lines. The first line holds
+//! the bounds of the monitor that was being shared, and
+//! every line after it holds a packet and the number of
+//! milliseconds after the start of the recording that it
+//! was received.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use hammeregg_core::InputPacket;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use super::{open_backend, InputBackend, RemoteInput};
+use crate::stream::MonitorBounds;
+
+/// The slowest that macros can be replayed at,
+/// relative to the speed they were recorded at.
+pub const MIN_REPLAY_SPEED: f64 = 0.01;
+
+#[derive(Serialize, Deserialize)]
+struct MacroHeader {
+    monitor_bounds: MonitorBounds,
+}
+
+/// A packet in a macro.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MacroEntry {
+    /// Milliseconds since the start of the recording.
+    pub time_ms: u64,
+    pub packet: InputPacket,
+}
+
+/// Records a session's input to a macro.
+pub struct MacroRecorder<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl MacroRecorder<LineWriter<File>> {
+    /// Creates a macro file at `path`, replacing
+    /// any file that's already there. Each packet
+    /// is written as soon as it's recorded, so
+    /// nothing is lost if the backend crashes.
+    pub fn create(path: &Path, monitor_bounds: MonitorBounds) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Couldn't create {}", path.display()))?;
+        Self::new(LineWriter::new(file), monitor_bounds)
+    }
+}
+
+impl<W: Write> MacroRecorder<W> {
+    pub fn new(mut writer: W, monitor_bounds: MonitorBounds) -> Result<Self> {
+        serde_json::to_writer(&mut writer, &MacroHeader { monitor_bounds })?;
+        writer.write_all(b"\n")?;
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    /// Records a packet, timestamped with
+    /// when this was called.
+    pub fn record(&mut self, packet: &InputPacket) -> Result<()> {
+        let entry = MacroEntry {
+            time_ms: self.start.elapsed().as_millis() as u64,
+            packet: packet.clone(),
+        };
+        serde_json::to_writer(&mut self.writer, &entry)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Reads a macro, returning the bounds of the monitor
+/// that it was recorded on and its packets. Packets
+/// are validated just like packets from remotes.
+pub fn read_macro(reader: impl BufRead) -> Result<(MonitorBounds, Vec<MacroEntry>)> {
+    let mut lines = reader.lines();
+    let header = lines.next().ok_or_else(|| anyhow!("Macro is empty"))??;
+    let header: MacroHeader = serde_json::from_str(&header).context("Invalid macro header")?;
+
+    let mut entries = vec![];
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: MacroEntry =
+            serde_json::from_str(&line).with_context(|| format!("Invalid packet on line {}", i + 2))?;
+        entry
+            .packet
+            .validate()
+            .with_context(|| format!("Invalid packet on line {}", i + 2))?;
+        entries.push(entry);
+    }
+    Ok((header.monitor_bounds, entries))
+}
+
+/// Replays the macro at `path` on this computer. Packets
+/// are spaced out like they were when recorded, divided
+/// by `speed`. Anything still held at the end is released.
+pub fn replay(path: &Path, speed: f64) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("Couldn't open {}", path.display()))?;
+    let (monitor_bounds, entries) = read_macro(BufReader::new(file))?;
+    let mut input = RemoteInput::new(open_backend(monitor_bounds), monitor_bounds);
+    play(&mut input, entries, speed)
+}
+
+/// Applies a macro's packets at `speed` times
+/// the speed they were recorded at. Stops with an
+/// error at the first packet that would be due too
+/// far in the future to wait for.
+pub fn play<B: InputBackend>(input: &mut RemoteInput<B>, entries: Vec<MacroEntry>, speed: f64) -> Result<()> {
+    let start = Instant::now();
+    for entry in entries {
+        let due = entry.time_ms as f64 / 1000.0 / speed;
+        if due >= u64::MAX as f64 {
+            return Err(anyhow!(
+                "Packet at {} ms is too late to replay at {}x speed",
+                entry.time_ms,
+                speed
+            ));
+        }
+        if let Some(wait) = Duration::from_secs_f64(due).checked_sub(start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+        input.handle_input(entry.packet);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hammeregg_core::{KeyInput, MouseButton, SpecialKeyInput};
+
+    use super::*;
+    use crate::input::{BackendKey, InputEvent, RecordingBackend};
+
+    const BOUNDS: MonitorBounds = MonitorBounds {
+        x: 100,
+        y: -50,
+        w: 800,
+        h: 600,
+    };
+
+    fn entries() -> Vec<MacroEntry> {
+        [
+            (0, InputPacket::MouseMove { x: 0.5, y: 0.5 }),
+            (5, InputPacket::MouseDown(MouseButton::Left)),
+            (10, InputPacket::KeyDown(KeyInput::SpecialKey(SpecialKeyInput::Shift))),
+            (15, InputPacket::Text("hi".to_owned())),
+        ]
+        .into_iter()
+        .map(|(time_ms, packet)| MacroEntry { time_ms, packet })
+        .collect()
+    }
+
+    #[test]
+    fn recorded_macros_can_be_read() {
+        let mut file = vec![];
+        let mut recorder = MacroRecorder::new(&mut file, BOUNDS).unwrap();
+        for entry in entries() {
+            recorder.record(&entry.packet).unwrap();
+        }
+        drop(recorder);
+
+        let (monitor_bounds, read) = read_macro(&file[..]).unwrap();
+        assert_eq!(monitor_bounds, BOUNDS);
+        let packets: Vec<_> = read.iter().map(|entry| entry.packet.clone()).collect();
+        let expected: Vec<_> = entries().into_iter().map(|entry| entry.packet).collect();
+        assert_eq!(packets, expected);
+        assert!(read.windows(2).all(|pair| pair[0].time_ms <= pair[1].time_ms));
+    }
+
+    #[test]
+    fn invalid_packets_are_rejected() {
+        let file = concat!(
+            r#"{"monitor_bounds":{"x":0,"y":0,"w":800,"h":600}}"#,
+            "\n",
+            r#"{"time_ms":0,"packet":{"text":""}}"#,
+            "\n",
+        );
+        let err = read_macro(file.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid packet on line 2");
+        assert!(read_macro(&b""[..]).is_err());
+    }
+
+    #[test]
+    fn playing_applies_every_packet() {
+        let mut input = RemoteInput::new(RecordingBackend::default(), BOUNDS);
+        let start = Instant::now();
+        play(&mut input, entries(), 0.5).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert_eq!(
+            input.backend.events,
+            [
+                InputEvent::MouseMoveTo(400, 300),
+                InputEvent::MouseButton(enigo::MouseButton::Left, true),
+                InputEvent::Key(BackendKey::Enigo(enigo::Key::Shift), true),
+                InputEvent::Text("hi".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn playing_too_slowly_is_an_error() {
+        let mut input = RemoteInput::new(RecordingBackend::default(), BOUNDS);
+        assert!(play(&mut input, entries(), 1e-300).is_err());
+        // Only the packet at 0 ms could be applied
+        assert_eq!(input.backend.events, [InputEvent::MouseMoveTo(400, 300)]);
+    }
+}
@@ -0,0 +1,750 @@
+//! Applies input from remotes to the home computer,
+//! through whichever [`InputBackend`] works here.
+
+mod devices;
+mod enigo_backend;
+mod macro_file;
+#[cfg(test)]
+mod recording_backend;
+#[cfg(target_os = "linux")]
+mod uinput_backend;
+
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use anyhow::Result;
+use enigo::Key;
+use hammeregg_core::{
+    ContactState, GamepadAxis, GamepadButton, InputPacket, KeyCode, KeyInput, MouseButton, SpecialKeyInput,
+};
+
+pub use self::enigo_backend::EnigoBackend;
+pub use self::macro_file::{play, read_macro, replay, MacroEntry, MacroRecorder, MIN_REPLAY_SPEED};
+#[cfg(test)]
+pub use self::recording_backend::{InputEvent, RecordingBackend};
+#[cfg(target_os = "linux")]
+pub use self::uinput_backend::UinputBackend;
+use crate::stream::MonitorBounds;
+
+/// Applies input packets from `input_rx` until it hangs
+/// up. Everything that the remote is holding down is
+/// released once it hangs up, or once the remote hasn't
+/// sent any input for `idle_timeout`. If `recording` is
+/// set, every packet is also recorded to a macro there.
+pub fn handle_inputs(
+    monitor_bounds: MonitorBounds,
+    idle_timeout: Option<Duration>,
+    recording: Option<PathBuf>,
+    input_rx: Receiver<InputPacket>,
+) {
+    let mut input = RemoteInput::new(open_backend(monitor_bounds), monitor_bounds);
+    let mut recorder = recording.and_then(|path| {
+        MacroRecorder::create(&path, monitor_bounds)
+            .map_err(|err| eprintln!("Couldn't start recording input: {:?}", err))
+            .ok()
+    });
+    loop {
+        let packet = match idle_timeout {
+            Some(idle_timeout) => input_rx.recv_timeout(idle_timeout),
+            None => input_rx.recv().map_err(RecvTimeoutError::from),
+        };
+        match packet {
+            Ok(packet) => {
+                if let Some(macro_recorder) = &mut recorder {
+                    if let Err(err) = macro_recorder.record(&packet) {
+                        eprintln!("Couldn't record input, so recording stopped: {:?}", err);
+                        recorder = None;
+                    }
+                }
+                input.handle_input(packet)
+            }
+            Err(RecvTimeoutError::Timeout) => input.release_all(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// Opens the input backend that works on this computer.
+/// Enigo only sends input to X11, so uinput is used
+/// instead under Wayland or without a display.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+pub fn open_backend(monitor_bounds: MonitorBounds) -> Box<dyn InputBackend> {
+    #[cfg(target_os = "linux")]
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_none() {
+        match UinputBackend::create(monitor_bounds) {
+            Ok(backend) => return Box::new(backend),
+            Err(err) => eprintln!("Couldn't create uinput devices, using Enigo instead: {:?}", err),
+        }
+    }
+    Box::new(EnigoBackend::new())
+}
+
+/// Something that sends input to the home computer. Each
+/// method returns once its input has been sent.
+pub trait InputBackend {
+    /// Presses or releases a key.
+    fn key(&mut self, key: BackendKey, down: bool) -> Result<()>;
+
+    /// Types text as-is, returning once
+    /// all of it has been typed.
+    fn text(&mut self, text: &str) -> Result<()>;
+
+    /// Presses or releases a mouse button.
+    fn mouse_button(&mut self, button: enigo::MouseButton, down: bool) -> Result<()>;
+
+    /// Moves the mouse to a position on
+    /// the screen, in pixels.
+    fn mouse_move_to(&mut self, x: i32, y: i32) -> Result<()>;
+
+    /// Moves the mouse by a number of pixels.
+    fn mouse_move_relative(&mut self, dx: i32, dy: i32) -> Result<()>;
+
+    /// Scrolls by a number of notches, where positive
+    /// values scroll right and down.
+    fn mouse_scroll(&mut self, x: i32, y: i32) -> Result<()>;
+
+    /// Presses or releases a button on the gamepad.
+    fn gamepad_button(&mut self, button: GamepadButton, pressed: bool) -> Result<()>;
+
+    /// Moves an axis on the gamepad.
+    fn gamepad_axis(&mut self, axis: GamepadAxis, value: f32) -> Result<()>;
+
+    /// Puts down, moves or lifts a finger, at
+    /// normalized coordinates.
+    fn touch(&mut self, id: u32, x: f32, y: f32, pressure: f32, state: ContactState) -> Result<()>;
+
+    /// Moves the pen, at normalized coordinates.
+    fn pen(&mut self, x: f32, y: f32, pressure: f32, tilt_x: f32, tilt_y: f32, state: ContactState) -> Result<()>;
+
+    /// Resets the gamepad and lifts every finger
+    /// and the pen.
+    fn reset_devices(&mut self) -> Result<()>;
+}
+
+impl<B: InputBackend + ?Sized> InputBackend for Box<B> {
+    fn key(&mut self, key: BackendKey, down: bool) -> Result<()> {
+        (**self).key(key, down)
+    }
+
+    fn text(&mut self, text: &str) -> Result<()> {
+        (**self).text(text)
+    }
+
+    fn mouse_button(&mut self, button: enigo::MouseButton, down: bool) -> Result<()> {
+        (**self).mouse_button(button, down)
+    }
+
+    fn mouse_move_to(&mut self, x: i32, y: i32) -> Result<()> {
+        (**self).mouse_move_to(x, y)
+    }
+
+    fn mouse_move_relative(&mut self, dx: i32, dy: i32) -> Result<()> {
+        (**self).mouse_move_relative(dx, dy)
+    }
+
+    fn mouse_scroll(&mut self, x: i32, y: i32) -> Result<()> {
+        (**self).mouse_scroll(x, y)
+    }
+
+    fn gamepad_button(&mut self, button: GamepadButton, pressed: bool) -> Result<()> {
+        (**self).gamepad_button(button, pressed)
+    }
+
+    fn gamepad_axis(&mut self, axis: GamepadAxis, value: f32) -> Result<()> {
+        (**self).gamepad_axis(axis, value)
+    }
+
+    fn touch(&mut self, id: u32, x: f32, y: f32, pressure: f32, state: ContactState) -> Result<()> {
+        (**self).touch(id, x, y, pressure, state)
+    }
+
+    fn pen(&mut self, x: f32, y: f32, pressure: f32, tilt_x: f32, tilt_y: f32, state: ContactState) -> Result<()> {
+        (**self).pen(x, y, pressure, tilt_x, tilt_y, state)
+    }
+
+    fn reset_devices(&mut self) -> Result<()> {
+        (**self).reset_devices()
+    }
+}
+
+/// Applies a remote's input, keeping track of the keys
+/// and mouse buttons that it's holding down. The home
+/// computer would be left with stuck keys if the remote
+/// went away without releasing them, so anything still
+/// held is released when this is dropped.
+pub struct RemoteInput<B: InputBackend> {
+    backend: B,
+    monitor_bounds: MonitorBounds,
+    /// Held keys, in the order they were pressed.
+    held_keys: Vec<BackendKey>,
+    /// Held mouse buttons, in the order
+    /// they were pressed.
+    held_buttons: Vec<MouseButton>,
+}
+
+impl<B: InputBackend> RemoteInput<B> {
+    pub fn new(backend: B, monitor_bounds: MonitorBounds) -> Self {
+        Self {
+            backend,
+            monitor_bounds,
+            held_keys: vec![],
+            held_buttons: vec![],
+        }
+    }
+
+    /// Applies an input packet.
+    pub fn handle_input(&mut self, input: InputPacket) {
+        let result = match input {
+            InputPacket::KeyDown(key) => self.press_key(convert_key(key), true),
+            InputPacket::KeyUp(key) => self.press_key(convert_key(key), false),
+            InputPacket::MouseDown(butt) => self.press_button(butt, true),
+            InputPacket::MouseUp(butt) => self.press_button(butt, false),
+            InputPacket::MouseMove { x, y } => {
+                // Only the monitor's size is used, not its
+                // position. Coordinates outside of 0 to 1 aren't
+                // clamped, so dragging past the edge of the
+                // video still moves the mouse.
+                let actual_x = (x * self.monitor_bounds.w as f32).round() as i32;
+                let actual_y = (y * self.monitor_bounds.h as f32).round() as i32;
+                self.backend.mouse_move_to(actual_x, actual_y)
+            }
+            InputPacket::MouseMoveRelative { dx, dy } => self.backend.mouse_move_relative(dx, dy),
+            InputPacket::MouseScroll { x, y } => self.backend.mouse_scroll(x, y),
+            InputPacket::Text(text) => self.backend.text(&text),
+            InputPacket::ReleaseAll {} => {
+                self.release_all();
+                Ok(())
+            }
+            InputPacket::GamepadButton { button, pressed } => self.backend.gamepad_button(button, pressed),
+            InputPacket::GamepadAxis { axis, value } => self.backend.gamepad_axis(axis, value),
+            InputPacket::Touch {
+                id,
+                x,
+                y,
+                pressure,
+                state,
+            } => self.backend.touch(id, x, y, pressure, state),
+            InputPacket::Pen {
+                x,
+                y,
+                pressure,
+                tilt_x,
+                tilt_y,
+                state,
+            } => self.backend.pen(x, y, pressure, tilt_x, tilt_y, state),
+        };
+        if let Err(err) = result {
+            eprintln!("Couldn't apply input: {:?}", err);
+        }
+    }
+
+    /// Releases every key and mouse button that the
+    /// remote is holding down, most recent first. This
+    /// also resets its gamepad and lifts its fingers
+    /// and pen.
+    pub fn release_all(&mut self) {
+        if let Err(err) = self.backend.reset_devices() {
+            eprintln!("Couldn't reset devices: {:?}", err);
+        }
+        while let Some(butt) = self.held_buttons.pop() {
+            if let Err(err) = self.backend.mouse_button(convert_button(butt), false) {
+                eprintln!("Couldn't release mouse button: {:?}", err);
+            }
+        }
+        while let Some(key) = self.held_keys.pop() {
+            if let Err(err) = self.backend.key(key, false) {
+                eprintln!("Couldn't release key: {:?}", err);
+            }
+        }
+    }
+
+    fn press_key(&mut self, key: BackendKey, down: bool) -> Result<()> {
+        self.held_keys.retain(|&held| held != key);
+        if down {
+            self.held_keys.push(key);
+        }
+        self.backend.key(key, down)
+    }
+
+    fn press_button(&mut self, butt: MouseButton, down: bool) -> Result<()> {
+        self.held_buttons.retain(|&held| held != butt);
+        if down {
+            self.held_buttons.push(butt);
+        }
+        self.backend.mouse_button(convert_button(butt), down)
+    }
+}
+
+impl<B: InputBackend> Drop for RemoteInput<B> {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+/// How a key is pressed. Keys that Enigo can't press
+/// are pressed as physical keys instead.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BackendKey {
+    Enigo(Key),
+    Physical(KeyCode),
+}
+
+fn convert_key(key_input: KeyInput) -> BackendKey {
+    macro_rules! convert_special_key {
+        ($s:ident {$($key:ident),*$(,)?} physical {$($code:ident),*$(,)?}) => {
+            match $s {
+                $(
+                    SpecialKeyInput::$key => BackendKey::Enigo(Key::$key),
+                )*
+                $(
+                    SpecialKeyInput::$code => BackendKey::Physical(KeyCode::$code),
+                )*
+            }
+        }
+    }
+
+    match key_input {
+        KeyInput::SpecialKey(s) => convert_special_key!(s {
+            Alt,
+            Backspace,
+            CapsLock,
+            Control,
+            Delete,
+            DownArrow,
+            End,
+            Escape,
+            F1,
+            F10,
+            F11,
+            F12,
+            F2,
+            F3,
+            F4,
+            F5,
+            F6,
+            F7,
+            F8,
+            F9,
+            Home,
+            LeftArrow,
+            Meta,
+            Option,
+            PageDown,
+            PageUp,
+            Return,
+            RightArrow,
+            Shift,
+            Space,
+            Tab,
+            UpArrow,
+        } physical {
+            Insert,
+            PrintScreen,
+            ScrollLock,
+            NumLock,
+            Pause,
+            ContextMenu,
+            F13,
+            F14,
+            F15,
+            F16,
+            F17,
+            F18,
+            F19,
+            F20,
+            F21,
+            F22,
+            F23,
+            F24,
+            Numpad0,
+            Numpad1,
+            Numpad2,
+            Numpad3,
+            Numpad4,
+            Numpad5,
+            Numpad6,
+            Numpad7,
+            Numpad8,
+            Numpad9,
+            NumpadAdd,
+            NumpadSubtract,
+            NumpadMultiply,
+            NumpadDivide,
+            NumpadDecimal,
+            NumpadEnter,
+            AudioVolumeMute,
+            AudioVolumeDown,
+            AudioVolumeUp,
+            MediaPlayPause,
+            MediaStop,
+            MediaTrackNext,
+            MediaTrackPrevious,
+        }),
+        KeyInput::AlphaKey(c) => BackendKey::Enigo(Key::Layout(c)),
+        KeyInput::RawKey(c) => BackendKey::Enigo(Key::Raw(c)),
+        KeyInput::Code(code) => BackendKey::Physical(code),
+    }
+}
+
+fn convert_button(butt: MouseButton) -> enigo::MouseButton {
+    match butt {
+        MouseButton::Left => enigo::MouseButton::Left,
+        MouseButton::Middle => enigo::MouseButton::Middle,
+        MouseButton::Right => enigo::MouseButton::Right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: MonitorBounds = MonitorBounds {
+        x: 0,
+        y: 0,
+        w: 1920,
+        h: 1080,
+    };
+
+    /// Applies packets to a monitor with `monitor_bounds`,
+    /// returning the events that they produced. Events
+    /// from releasing held input at the end are left out.
+    fn events(monitor_bounds: MonitorBounds, packets: Vec<InputPacket>) -> Vec<InputEvent> {
+        let mut input = RemoteInput::new(RecordingBackend::default(), monitor_bounds);
+        for packet in packets {
+            input.handle_input(packet);
+        }
+        std::mem::take(&mut input.backend.events)
+    }
+
+    fn special(key: SpecialKeyInput) -> KeyInput {
+        KeyInput::SpecialKey(key)
+    }
+
+    #[test]
+    fn new_special_keys_are_physical() {
+        let table = [
+            (SpecialKeyInput::Insert, KeyCode::Insert),
+            (SpecialKeyInput::PrintScreen, KeyCode::PrintScreen),
+            (SpecialKeyInput::ScrollLock, KeyCode::ScrollLock),
+            (SpecialKeyInput::NumLock, KeyCode::NumLock),
+            (SpecialKeyInput::Pause, KeyCode::Pause),
+            (SpecialKeyInput::ContextMenu, KeyCode::ContextMenu),
+            (SpecialKeyInput::F13, KeyCode::F13),
+            (SpecialKeyInput::F14, KeyCode::F14),
+            (SpecialKeyInput::F15, KeyCode::F15),
+            (SpecialKeyInput::F16, KeyCode::F16),
+            (SpecialKeyInput::F17, KeyCode::F17),
+            (SpecialKeyInput::F18, KeyCode::F18),
+            (SpecialKeyInput::F19, KeyCode::F19),
+            (SpecialKeyInput::F20, KeyCode::F20),
+            (SpecialKeyInput::F21, KeyCode::F21),
+            (SpecialKeyInput::F22, KeyCode::F22),
+            (SpecialKeyInput::F23, KeyCode::F23),
+            (SpecialKeyInput::F24, KeyCode::F24),
+            (SpecialKeyInput::Numpad0, KeyCode::Numpad0),
+            (SpecialKeyInput::Numpad1, KeyCode::Numpad1),
+            (SpecialKeyInput::Numpad2, KeyCode::Numpad2),
+            (SpecialKeyInput::Numpad3, KeyCode::Numpad3),
+            (SpecialKeyInput::Numpad4, KeyCode::Numpad4),
+            (SpecialKeyInput::Numpad5, KeyCode::Numpad5),
+            (SpecialKeyInput::Numpad6, KeyCode::Numpad6),
+            (SpecialKeyInput::Numpad7, KeyCode::Numpad7),
+            (SpecialKeyInput::Numpad8, KeyCode::Numpad8),
+            (SpecialKeyInput::Numpad9, KeyCode::Numpad9),
+            (SpecialKeyInput::NumpadAdd, KeyCode::NumpadAdd),
+            (SpecialKeyInput::NumpadSubtract, KeyCode::NumpadSubtract),
+            (SpecialKeyInput::NumpadMultiply, KeyCode::NumpadMultiply),
+            (SpecialKeyInput::NumpadDivide, KeyCode::NumpadDivide),
+            (SpecialKeyInput::NumpadDecimal, KeyCode::NumpadDecimal),
+            (SpecialKeyInput::NumpadEnter, KeyCode::NumpadEnter),
+            (SpecialKeyInput::AudioVolumeMute, KeyCode::AudioVolumeMute),
+            (SpecialKeyInput::AudioVolumeDown, KeyCode::AudioVolumeDown),
+            (SpecialKeyInput::AudioVolumeUp, KeyCode::AudioVolumeUp),
+            (SpecialKeyInput::MediaPlayPause, KeyCode::MediaPlayPause),
+            (SpecialKeyInput::MediaStop, KeyCode::MediaStop),
+            (SpecialKeyInput::MediaTrackNext, KeyCode::MediaTrackNext),
+            (SpecialKeyInput::MediaTrackPrevious, KeyCode::MediaTrackPrevious),
+        ];
+        for (key, code) in table {
+            assert_eq!(convert_key(special(key)), BackendKey::Physical(code), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn release_all_releases_held_input() {
+        let backend = RecordingBackend::default();
+        let mut input = RemoteInput::new(backend, BOUNDS);
+        input.handle_input(InputPacket::KeyDown(special(SpecialKeyInput::Shift)));
+        input.handle_input(InputPacket::MouseDown(MouseButton::Left));
+        input.backend.events.clear();
+        input.release_all();
+        assert_eq!(
+            input.backend.events,
+            [
+                InputEvent::ResetDevices,
+                InputEvent::MouseButton(enigo::MouseButton::Left, false),
+                InputEvent::Key(BackendKey::Enigo(Key::Shift), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn special_keys_are_distinct() {
+        let keys: Vec<_> = SpecialKeyInput::ALL
+            .iter()
+            .map(|&key| convert_key(special(key)))
+            .collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert!(!keys[..i].contains(key), "{:?} is mapped twice", key);
+        }
+    }
+
+    #[test]
+    fn special_keys_are_enigo_keys() {
+        let table = [
+            (SpecialKeyInput::Alt, Key::Alt),
+            (SpecialKeyInput::Backspace, Key::Backspace),
+            (SpecialKeyInput::CapsLock, Key::CapsLock),
+            (SpecialKeyInput::Control, Key::Control),
+            (SpecialKeyInput::Delete, Key::Delete),
+            (SpecialKeyInput::DownArrow, Key::DownArrow),
+            (SpecialKeyInput::End, Key::End),
+            (SpecialKeyInput::Escape, Key::Escape),
+            (SpecialKeyInput::F1, Key::F1),
+            (SpecialKeyInput::F2, Key::F2),
+            (SpecialKeyInput::F3, Key::F3),
+            (SpecialKeyInput::F4, Key::F4),
+            (SpecialKeyInput::F5, Key::F5),
+            (SpecialKeyInput::F6, Key::F6),
+            (SpecialKeyInput::F7, Key::F7),
+            (SpecialKeyInput::F8, Key::F8),
+            (SpecialKeyInput::F9, Key::F9),
+            (SpecialKeyInput::F10, Key::F10),
+            (SpecialKeyInput::F11, Key::F11),
+            (SpecialKeyInput::F12, Key::F12),
+            (SpecialKeyInput::Home, Key::Home),
+            (SpecialKeyInput::LeftArrow, Key::LeftArrow),
+            (SpecialKeyInput::Meta, Key::Meta),
+            (SpecialKeyInput::Option, Key::Option),
+            (SpecialKeyInput::PageDown, Key::PageDown),
+            (SpecialKeyInput::PageUp, Key::PageUp),
+            (SpecialKeyInput::Return, Key::Return),
+            (SpecialKeyInput::RightArrow, Key::RightArrow),
+            (SpecialKeyInput::Shift, Key::Shift),
+            (SpecialKeyInput::Space, Key::Space),
+            (SpecialKeyInput::Tab, Key::Tab),
+            (SpecialKeyInput::UpArrow, Key::UpArrow),
+        ];
+        for (key, enigo_key) in table {
+            assert_eq!(convert_key(special(key)), BackendKey::Enigo(enigo_key), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn other_keys_are_passed_through() {
+        assert_eq!(
+            convert_key(KeyInput::AlphaKey('a')),
+            BackendKey::Enigo(Key::Layout('a'))
+        );
+        assert_eq!(
+            convert_key(KeyInput::AlphaKey('Ä')),
+            BackendKey::Enigo(Key::Layout('Ä'))
+        );
+        assert_eq!(
+            convert_key(KeyInput::RawKey(0xff08)),
+            BackendKey::Enigo(Key::Raw(0xff08))
+        );
+        assert_eq!(
+            convert_key(KeyInput::Code(KeyCode::KeyA)),
+            BackendKey::Physical(KeyCode::KeyA)
+        );
+    }
+
+    #[test]
+    fn mouse_buttons_are_converted() {
+        assert_eq!(convert_button(MouseButton::Left), enigo::MouseButton::Left);
+        assert_eq!(convert_button(MouseButton::Middle), enigo::MouseButton::Middle);
+        assert_eq!(convert_button(MouseButton::Right), enigo::MouseButton::Right);
+    }
+
+    #[test]
+    fn every_packet_produces_its_events() {
+        let table = vec![
+            (
+                InputPacket::KeyDown(special(SpecialKeyInput::Return)),
+                vec![InputEvent::Key(BackendKey::Enigo(Key::Return), true)],
+            ),
+            (
+                InputPacket::KeyUp(KeyInput::AlphaKey('q')),
+                vec![InputEvent::Key(BackendKey::Enigo(Key::Layout('q')), false)],
+            ),
+            (
+                InputPacket::KeyDown(KeyInput::Code(KeyCode::NumpadEnter)),
+                vec![InputEvent::Key(BackendKey::Physical(KeyCode::NumpadEnter), true)],
+            ),
+            (
+                InputPacket::MouseDown(MouseButton::Right),
+                vec![InputEvent::MouseButton(enigo::MouseButton::Right, true)],
+            ),
+            (
+                InputPacket::MouseUp(MouseButton::Middle),
+                vec![InputEvent::MouseButton(enigo::MouseButton::Middle, false)],
+            ),
+            (
+                InputPacket::MouseMove { x: 0.5, y: 0.25 },
+                vec![InputEvent::MouseMoveTo(960, 270)],
+            ),
+            (
+                InputPacket::MouseMoveRelative { dx: -3, dy: 7 },
+                vec![InputEvent::MouseMoveRelative(-3, 7)],
+            ),
+            (
+                InputPacket::MouseScroll { x: 0, y: -2 },
+                vec![InputEvent::MouseScroll(0, -2)],
+            ),
+            (
+                InputPacket::Text("héllo\n".to_owned()),
+                vec![InputEvent::Text("héllo\n".to_owned())],
+            ),
+            (InputPacket::ReleaseAll {}, vec![InputEvent::ResetDevices]),
+            (
+                InputPacket::GamepadButton {
+                    button: GamepadButton::DPadLeft,
+                    pressed: true,
+                },
+                vec![InputEvent::GamepadButton(GamepadButton::DPadLeft, true)],
+            ),
+            (
+                InputPacket::GamepadAxis {
+                    axis: GamepadAxis::LeftTrigger,
+                    value: 0.75,
+                },
+                vec![InputEvent::GamepadAxis(GamepadAxis::LeftTrigger, 0.75)],
+            ),
+            (
+                InputPacket::Touch {
+                    id: 4,
+                    x: 0.1,
+                    y: 0.9,
+                    pressure: 0.5,
+                    state: ContactState::Contact,
+                },
+                vec![InputEvent::Touch {
+                    id: 4,
+                    x: 0.1,
+                    y: 0.9,
+                    pressure: 0.5,
+                    state: ContactState::Contact,
+                }],
+            ),
+            (
+                InputPacket::Pen {
+                    x: 0.3,
+                    y: 0.6,
+                    pressure: 0.0,
+                    tilt_x: -20.0,
+                    tilt_y: 45.0,
+                    state: ContactState::Hover,
+                },
+                vec![InputEvent::Pen {
+                    x: 0.3,
+                    y: 0.6,
+                    pressure: 0.0,
+                    tilt_x: -20.0,
+                    tilt_y: 45.0,
+                    state: ContactState::Hover,
+                }],
+            ),
+        ];
+        for (packet, expected) in table {
+            let description = format!("{:?}", packet);
+            assert_eq!(events(BOUNDS, vec![packet]), expected, "{}", description);
+        }
+    }
+
+    #[test]
+    fn mouse_move_is_scaled_to_the_monitor() {
+        let table = [
+            ((0.0, 0.0), (0, 0)),
+            ((1.0, 1.0), (1920, 1080)),
+            ((0.5, 0.5), (960, 540)),
+            // Rounded to the nearest pixel
+            ((0.1, 0.1), (192, 108)),
+            ((0.0002, 0.9996), (0, 1080)),
+            // Outside of the monitor
+            ((-0.25, 1.5), (-480, 1620)),
+            ((2.0, -1.0), (3840, -1080)),
+        ];
+        for ((x, y), (actual_x, actual_y)) in table {
+            assert_eq!(
+                events(BOUNDS, vec![InputPacket::MouseMove { x, y }]),
+                [InputEvent::MouseMoveTo(actual_x, actual_y)],
+                "({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn mouse_move_ignores_monitor_position() {
+        let packets = || vec![InputPacket::MouseMove { x: 0.5, y: 1.0 }];
+        let moved = MonitorBounds::new(1920, -200, 1280, 720);
+        assert_eq!(events(moved, packets()), [InputEvent::MouseMoveTo(640, 720)]);
+        let origin = MonitorBounds::new(0, 0, 1280, 720);
+        assert_eq!(events(moved, packets()), events(origin, packets()));
+    }
+
+    #[test]
+    fn mouse_move_on_empty_monitor() {
+        let empty = MonitorBounds::new(0, 0, 0, 0);
+        assert_eq!(
+            events(empty, vec![InputPacket::MouseMove { x: 0.5, y: 0.5 }]),
+            [InputEvent::MouseMoveTo(0, 0)]
+        );
+    }
+
+    #[test]
+    fn repeated_presses_are_released_once() {
+        let a = BackendKey::Physical(KeyCode::KeyA);
+        let packets = vec![
+            InputPacket::KeyDown(KeyInput::Code(KeyCode::KeyA)),
+            InputPacket::KeyDown(KeyInput::Code(KeyCode::KeyA)),
+            InputPacket::MouseDown(MouseButton::Right),
+            InputPacket::MouseUp(MouseButton::Right),
+            InputPacket::ReleaseAll {},
+            // Nothing is held anymore
+            InputPacket::ReleaseAll {},
+        ];
+        assert_eq!(
+            events(BOUNDS, packets),
+            [
+                InputEvent::Key(a, true),
+                InputEvent::Key(a, true),
+                InputEvent::MouseButton(enigo::MouseButton::Right, true),
+                InputEvent::MouseButton(enigo::MouseButton::Right, false),
+                InputEvent::ResetDevices,
+                InputEvent::Key(a, false),
+                InputEvent::ResetDevices,
+            ]
+        );
+    }
+
+    #[test]
+    fn releasing_unheld_input_is_passed_through() {
+        let packets = vec![
+            InputPacket::KeyUp(special(SpecialKeyInput::Control)),
+            InputPacket::MouseUp(MouseButton::Left),
+            InputPacket::ReleaseAll {},
+        ];
+        assert_eq!(
+            events(BOUNDS, packets),
+            [
+                InputEvent::Key(BackendKey::Enigo(Key::Control), false),
+                InputEvent::MouseButton(enigo::MouseButton::Left, false),
+                InputEvent::ResetDevices,
+            ]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! A backend that records input instead of sending
+//! it, so tests can check exactly what was sent.
+
+use anyhow::Result;
+use hammeregg_core::{ContactState, GamepadAxis, GamepadButton};
+
+use super::{BackendKey, InputBackend};
+
+/// A call to one of [`InputBackend`]'s methods.
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputEvent {
+    Key(BackendKey, bool),
+    Text(String),
+    MouseButton(enigo::MouseButton, bool),
+    MouseMoveTo(i32, i32),
+    MouseMoveRelative(i32, i32),
+    MouseScroll(i32, i32),
+    GamepadButton(GamepadButton, bool),
+    GamepadAxis(GamepadAxis, f32),
+    Touch {
+        id: u32,
+        x: f32,
+        y: f32,
+        pressure: f32,
+        state: ContactState,
+    },
+    Pen {
+        x: f32,
+        y: f32,
+        pressure: f32,
+        tilt_x: f32,
+        tilt_y: f32,
+        state: ContactState,
+    },
+    ResetDevices,
+}
+
+/// Records every call to it, in order.
+#[derive(Default, Debug)]
+pub struct RecordingBackend {
+    pub events: Vec<InputEvent>,
+}
+
+impl RecordingBackend {
+    fn record(&mut self, event: InputEvent) -> Result<()> {
+        self.events.push(event);
+        Ok(())
+    }
+}
+
+impl InputBackend for RecordingBackend {
+    fn key(&mut self, key: BackendKey, down: bool) -> Result<()> {
+        self.record(InputEvent::Key(key, down))
+    }
+
+    fn text(&mut self, text: &str) -> Result<()> {
+        self.record(InputEvent::Text(text.to_owned()))
+    }
+
+    fn mouse_button(&mut self, button: enigo::MouseButton, down: bool) -> Result<()> {
+        self.record(InputEvent::MouseButton(button, down))
+    }
+
+    fn mouse_move_to(&mut self, x: i32, y: i32) -> Result<()> {
+        self.record(InputEvent::MouseMoveTo(x, y))
+    }
+
+    fn mouse_move_relative(&mut self, dx: i32, dy: i32) -> Result<()> {
+        self.record(InputEvent::MouseMoveRelative(dx, dy))
+    }
+
+    fn mouse_scroll(&mut self, x: i32, y: i32) -> Result<()> {
+        self.record(InputEvent::MouseScroll(x, y))
+    }
+
+    fn gamepad_button(&mut self, button: GamepadButton, pressed: bool) -> Result<()> {
+        self.record(InputEvent::GamepadButton(button, pressed))
+    }
+
+    fn gamepad_axis(&mut self, axis: GamepadAxis, value: f32) -> Result<()> {
+        self.record(InputEvent::GamepadAxis(axis, value))
+    }
+
+    fn touch(&mut self, id: u32, x: f32, y: f32, pressure: f32, state: ContactState) -> Result<()> {
+        self.record(InputEvent::Touch {
+            id,
+            x,
+            y,
+            pressure,
+            state,
+        })
+    }
+
+    fn pen(&mut self, x: f32, y: f32, pressure: f32, tilt_x: f32, tilt_y: f32, state: ContactState) -> Result<()> {
+        self.record(InputEvent::Pen {
+            x,
+            y,
+            pressure,
+            tilt_x,
+            tilt_y,
+            state,
+        })
+    }
+
+    fn reset_devices(&mut self) -> Result<()> {
+        self.record(InputEvent::ResetDevices)
+    }
+}
@@ -0,0 +1,328 @@
+//! Sends input through virtual uinput devices, which
+//! works under Wayland and on the console as well as
+//! under X11, since the kernel treats the devices like
+//! real hardware.
+
+use anyhow::{anyhow, Result};
+use enigo::Key;
+use hammeregg_core::{ContactState, GamepadAxis, GamepadButton, KeyCode};
+use nix::libc::input_id;
+
+use super::devices::VirtualDevices;
+use super::{BackendKey, InputBackend};
+use crate::keyboard::evdev_code;
+use crate::stream::MonitorBounds;
+use crate::uinput::{AbsAxis, Capabilities, UinputDevice, ABS_X, ABS_Y, BUS_VIRTUAL, EV_ABS, EV_KEY, EV_REL};
+
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+
+const BTN_LEFT: u16 = 0x110;
+const BTN_RIGHT: u16 = 0x111;
+const BTN_MIDDLE: u16 = 0x112;
+
+const ID: input_id = input_id {
+    bustype: BUS_VIRTUAL,
+    vendor: 0,
+    product: 0,
+    version: 1,
+};
+
+/// Sends input through a virtual keyboard, mouse
+/// and absolute pointer. Keys are pressed by their
+/// position, so text and layout keys are typed as if
+/// the home computer used a US keyboard layout.
+pub struct UinputBackend {
+    keyboard: UinputDevice,
+    /// Sends relative movement, scrolling and buttons.
+    mouse: UinputDevice,
+    /// Moves the mouse to a position on the screen,
+    /// like a drawing tablet.
+    pointer: UinputDevice,
+    /// Whether the remote is holding the left and right
+    /// shift keys, in which case typing a shifted
+    /// character doesn't press shift itself.
+    shifts_held: [bool; 2],
+    devices: VirtualDevices,
+}
+
+impl UinputBackend {
+    /// Creates the virtual devices. The pointer covers
+    /// `monitor_bounds`'s size in pixels.
+    pub fn create(monitor_bounds: MonitorBounds) -> Result<Self> {
+        let keys: Vec<u16> = KeyCode::ALL.iter().map(|&code| evdev_code(code)).collect();
+        let keyboard = UinputDevice::create(
+            "Hammeregg Virtual Keyboard",
+            ID,
+            Capabilities {
+                keys: &keys,
+                ..Default::default()
+            },
+        )?;
+
+        let mouse = UinputDevice::create(
+            "Hammeregg Virtual Mouse",
+            ID,
+            Capabilities {
+                keys: &[BTN_LEFT, BTN_RIGHT, BTN_MIDDLE],
+                relative: &[REL_X, REL_Y, REL_HWHEEL, REL_WHEEL],
+                ..Default::default()
+            },
+        )?;
+
+        // Absolute pointers need a button to be
+        // recognized as a mouse
+        let axes = [
+            screen_axis(ABS_X, monitor_bounds.w),
+            screen_axis(ABS_Y, monitor_bounds.h),
+        ];
+        let pointer = UinputDevice::create(
+            "Hammeregg Virtual Pointer",
+            ID,
+            Capabilities {
+                keys: &[BTN_LEFT],
+                axes: &axes,
+                ..Default::default()
+            },
+        )?;
+
+        Ok(Self {
+            keyboard,
+            mouse,
+            pointer,
+            shifts_held: [false; 2],
+            devices: VirtualDevices::default(),
+        })
+    }
+
+    fn shift_held(&self) -> bool {
+        self.shifts_held.iter().any(|&held| held)
+    }
+
+    /// Presses or releases a key by its evdev code.
+    fn press(&mut self, code: u16, down: bool) -> Result<()> {
+        if code == evdev_code(KeyCode::ShiftLeft) {
+            self.shifts_held[0] = down;
+        } else if code == evdev_code(KeyCode::ShiftRight) {
+            self.shifts_held[1] = down;
+        }
+        self.keyboard.emit(EV_KEY, code, down as i32)?;
+        self.keyboard.sync()
+    }
+
+    /// Presses or releases a key that types `c`,
+    /// pressing shift along with it if needed.
+    fn press_char(&mut self, c: char, down: bool) -> Result<()> {
+        let (code, shifted) = layout_key(c).ok_or_else(|| anyhow!("Can't type {:?} with uinput", c))?;
+        let shift = evdev_code(KeyCode::ShiftLeft);
+        let synthetic_shift = shifted && !self.shift_held();
+        if synthetic_shift && down {
+            self.keyboard.emit(EV_KEY, shift, 1)?;
+        }
+        self.keyboard.emit(EV_KEY, evdev_code(code), down as i32)?;
+        if synthetic_shift && !down {
+            self.keyboard.emit(EV_KEY, shift, 0)?;
+        }
+        self.keyboard.sync()
+    }
+}
+
+impl InputBackend for UinputBackend {
+    fn key(&mut self, key: BackendKey, down: bool) -> Result<()> {
+        let code = match key {
+            BackendKey::Physical(code) => code,
+            BackendKey::Enigo(Key::Layout(c)) => return self.press_char(c, down),
+            BackendKey::Enigo(key) => {
+                enigo_key_code(key).ok_or_else(|| anyhow!("Can't press {:?} with uinput", key))?
+            }
+        };
+        self.press(evdev_code(code), down)
+    }
+
+    fn text(&mut self, text: &str) -> Result<()> {
+        let mut skipped = 0;
+        for c in text.chars() {
+            if layout_key(c).is_none() {
+                skipped += 1;
+                continue;
+            }
+            self.press_char(c, true)?;
+            self.press_char(c, false)?;
+        }
+        if skipped > 0 {
+            return Err(anyhow!("Couldn't type {} characters with uinput", skipped));
+        }
+        Ok(())
+    }
+
+    fn mouse_button(&mut self, button: enigo::MouseButton, down: bool) -> Result<()> {
+        let code = match button {
+            enigo::MouseButton::Left => BTN_LEFT,
+            enigo::MouseButton::Middle => BTN_MIDDLE,
+            enigo::MouseButton::Right => BTN_RIGHT,
+            button => return Err(anyhow!("Can't press {:?} with uinput", button)),
+        };
+        self.mouse.emit(EV_KEY, code, down as i32)?;
+        self.mouse.sync()
+    }
+
+    fn mouse_move_to(&mut self, x: i32, y: i32) -> Result<()> {
+        self.pointer.emit(EV_ABS, ABS_X, x)?;
+        self.pointer.emit(EV_ABS, ABS_Y, y)?;
+        self.pointer.sync()
+    }
+
+    fn mouse_move_relative(&mut self, dx: i32, dy: i32) -> Result<()> {
+        self.mouse.emit(EV_REL, REL_X, dx)?;
+        self.mouse.emit(EV_REL, REL_Y, dy)?;
+        self.mouse.sync()
+    }
+
+    fn mouse_scroll(&mut self, x: i32, y: i32) -> Result<()> {
+        // The wheel counts up as positive
+        if x != 0 {
+            self.mouse.emit(EV_REL, REL_HWHEEL, x)?;
+        }
+        if y != 0 {
+            self.mouse.emit(EV_REL, REL_WHEEL, -y)?;
+        }
+        self.mouse.sync()
+    }
+
+    fn gamepad_button(&mut self, button: GamepadButton, pressed: bool) -> Result<()> {
+        self.devices.gamepad_button(button, pressed)
+    }
+
+    fn gamepad_axis(&mut self, axis: GamepadAxis, value: f32) -> Result<()> {
+        self.devices.gamepad_axis(axis, value)
+    }
+
+    fn touch(&mut self, id: u32, x: f32, y: f32, pressure: f32, state: ContactState) -> Result<()> {
+        self.devices.touch(id, x, y, pressure, state)
+    }
+
+    fn pen(&mut self, x: f32, y: f32, pressure: f32, tilt_x: f32, tilt_y: f32, state: ContactState) -> Result<()> {
+        self.devices.pen(x, y, pressure, tilt_x, tilt_y, state)
+    }
+
+    fn reset_devices(&mut self) -> Result<()> {
+        self.devices.reset()
+    }
+}
+
+/// An axis that spans the screen, in pixels.
+fn screen_axis(code: u16, size: u32) -> AbsAxis {
+    AbsAxis {
+        code,
+        min: 0,
+        max: size.max(1) as i32,
+        fuzz: 0,
+        flat: 0,
+        resolution: 0,
+    }
+}
+
+/// Gets the physical key for one of the Enigo keys
+/// that remotes can press, other than layout keys.
+fn enigo_key_code(key: Key) -> Option<KeyCode> {
+    Some(match key {
+        Key::Alt | Key::Option => KeyCode::AltLeft,
+        Key::Backspace => KeyCode::Backspace,
+        Key::CapsLock => KeyCode::CapsLock,
+        Key::Meta => KeyCode::MetaLeft,
+        Key::Control => KeyCode::ControlLeft,
+        Key::Delete => KeyCode::Delete,
+        Key::DownArrow => KeyCode::ArrowDown,
+        Key::End => KeyCode::End,
+        Key::Escape => KeyCode::Escape,
+        Key::F1 => KeyCode::F1,
+        Key::F2 => KeyCode::F2,
+        Key::F3 => KeyCode::F3,
+        Key::F4 => KeyCode::F4,
+        Key::F5 => KeyCode::F5,
+        Key::F6 => KeyCode::F6,
+        Key::F7 => KeyCode::F7,
+        Key::F8 => KeyCode::F8,
+        Key::F9 => KeyCode::F9,
+        Key::F10 => KeyCode::F10,
+        Key::F11 => KeyCode::F11,
+        Key::F12 => KeyCode::F12,
+        Key::Home => KeyCode::Home,
+        Key::LeftArrow => KeyCode::ArrowLeft,
+        Key::PageDown => KeyCode::PageDown,
+        Key::PageUp => KeyCode::PageUp,
+        Key::Return => KeyCode::Enter,
+        Key::RightArrow => KeyCode::ArrowRight,
+        Key::Shift => KeyCode::ShiftLeft,
+        Key::Space => KeyCode::Space,
+        Key::Tab => KeyCode::Tab,
+        Key::UpArrow => KeyCode::ArrowUp,
+        // Layout and raw keys, and the deprecated names for meta
+        _ => return None,
+    })
+}
+
+/// Gets the key that types a character on a US keyboard
+/// layout, and whether shift needs to be held for it.
+fn layout_key(c: char) -> Option<(KeyCode, bool)> {
+    use KeyCode::*;
+
+    /// Each row's characters without and with shift.
+    const ROWS: [(&str, &str, &[KeyCode]); 4] = [
+        (
+            "1234567890-=",
+            "!@#$%^&*()_+",
+            &[
+                Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9, Digit0, Minus, Equal,
+            ],
+        ),
+        (
+            "qwertyuiop[]",
+            "QWERTYUIOP{}",
+            &[
+                KeyQ,
+                KeyW,
+                KeyE,
+                KeyR,
+                KeyT,
+                KeyY,
+                KeyU,
+                KeyI,
+                KeyO,
+                KeyP,
+                BracketLeft,
+                BracketRight,
+            ],
+        ),
+        (
+            "asdfghjkl;'`",
+            "ASDFGHJKL:\"~",
+            &[
+                KeyA, KeyS, KeyD, KeyF, KeyG, KeyH, KeyJ, KeyK, KeyL, Semicolon, Quote, Backquote,
+            ],
+        ),
+        (
+            "\\zxcvbnm,./",
+            "|ZXCVBNM<>?",
+            &[
+                Backslash, KeyZ, KeyX, KeyC, KeyV, KeyB, KeyN, KeyM, Comma, Period, Slash,
+            ],
+        ),
+    ];
+
+    match c {
+        ' ' => return Some((Space, false)),
+        '\n' => return Some((Enter, false)),
+        '\t' => return Some((Tab, false)),
+        _ => {}
+    }
+    ROWS.iter().find_map(|(lower, upper, codes)| {
+        if let Some(i) = lower.chars().position(|l| l == c) {
+            Some((codes[i], false))
+        } else {
+            upper.chars().position(|u| u == c).map(|i| (codes[i], true))
+        }
+    })
+}
@@ -1,24 +1,26 @@
-use rand_chacha::rand_core::SeedableRng;
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, Key as AesGcmKey, NewAead, Nonce};
+use anyhow::{anyhow, Context, Result};
+use hammeregg_core::limits::RSA_KEY_BITS;
+use hammeregg_core::{ErrorCode, ErrorMsg};
+use rand_chacha::rand_core::{RngCore, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 use rsa::{PaddingScheme, RsaPrivateKey};
-use serde::Serialize;
 use sha2::Sha256;
-use zeroize::Zeroize;
+use zeroize::Zeroizing;
 
-/// Bit size for RSA public/private keys
-const BIT_SIZE: usize = 4096;
 /// AES key size in bytes
 pub const AES_KEY_SIZE: usize = 32;
 /// AES init vector size in bytes
-pub const AES_IV_SIZE: usize = 12;
+pub use hammeregg_core::limits::AES_IV_SIZE;
 
 /// Generates two unrelated private keys.
 pub fn gen_home_and_remote_keys() -> (RsaPrivateKey, RsaPrivateKey) {
     // init key-pairs
     let mut rng = ChaCha20Rng::from_entropy();
-    let home_private_key = RsaPrivateKey::new(&mut rng, BIT_SIZE).unwrap();
+    let home_private_key = RsaPrivateKey::new(&mut rng, RSA_KEY_BITS).unwrap();
     let mut rng = ChaCha20Rng::from_entropy();
-    let remote_private_key = RsaPrivateKey::new(&mut rng, BIT_SIZE).unwrap();
+    let remote_private_key = RsaPrivateKey::new(&mut rng, RSA_KEY_BITS).unwrap();
     (home_private_key, remote_private_key)
 }
 
@@ -27,16 +29,96 @@ pub fn padding_scheme() -> PaddingScheme {
     PaddingScheme::new_oaep::<Sha256>()
 }
 
-// Maintainers: keep this struct synchronized with
-// `RemotePassword` in egg/src/hammeregg_key.ts
-/// The home public key and remote private key combo
-/// that serves as Hammeregg's password.
+/// Generates a random AES key.
+pub fn gen_aes_key<R: RngCore>(rng: &mut R) -> Result<Zeroizing<Vec<u8>>> {
+    let mut key = Zeroizing::new(vec![0; AES_KEY_SIZE]);
+    rng.try_fill_bytes(key.as_mut_slice())
+        .context("Couldn't generate AES key")?;
+    Ok(key)
+}
+
+/// Encrypts a payload with an AES key and a fresh
+/// init vector. Returns the init vector and the
+/// encrypted payload.
+pub fn encrypt_payload<R: RngCore>(rng: &mut R, aes_key: &[u8], payload: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut iv = vec![0; AES_IV_SIZE];
+    rng.try_fill_bytes(iv.as_mut_slice())
+        .context("Couldn't generate AES init vector")?;
+
+    let aes_cipher = Aes256Gcm::new(AesGcmKey::from_slice(aes_key));
+    let encrypted_payload = aes_cipher
+        .encrypt(Nonce::from_slice(iv.as_slice()), payload)
+        .map_err(|_| anyhow!("Payload couldn't be encrypted"))?;
+    Ok((iv, encrypted_payload))
+}
+
+/// Decrypts a string that was encrypted with an
+/// already exchanged AES key.
 ///
-/// Both keys are stored as PEM strings in this struct.
-// not Deserialize since this is never read by Rust code
-#[derive(Serialize, Zeroize)]
-#[zeroize(drop)]
-pub struct RemotePassword {
-    pub home_public_key: String,
-    pub remote_private_key: String,
+/// Errors are always [`ErrorMsg`]s that can be sent
+/// back to the remote.
+pub fn decrypt_payload(aes_key: &[u8], iv: &[u8], payload: &[u8]) -> Result<String> {
+    check_iv(iv)?;
+
+    let aes_cipher = Aes256Gcm::new(AesGcmKey::from_slice(aes_key));
+    let decrypted_payload = aes_cipher
+        .decrypt(iv.into(), payload)
+        .map_err(|_| ErrorMsg::with_detail(ErrorCode::DecryptFailed, "Couldn't decrypt remote payload"))?;
+
+    Ok(String::from_utf8(decrypted_payload)
+        .map_err(|_| ErrorMsg::with_detail(ErrorCode::OfferInvalid, "Payload was not a valid string"))?)
+}
+
+/// Decrypts the session description in a remote offer.
+/// `key` is the remote's AES key, encrypted with the
+/// home public key, and `payload` is the session
+/// description encrypted with that AES key. Returns
+/// the decrypted AES key alongside the session
+/// description, since the remote keeps using that key
+/// for the ICE candidates it trickles in later.
+///
+/// Errors are always [`ErrorMsg`]s that can be sent
+/// back to the remote.
+pub fn decrypt_offer(
+    home_private_key: &RsaPrivateKey,
+    key: &[u8],
+    iv: &[u8],
+    payload: &[u8],
+) -> Result<(Zeroizing<Vec<u8>>, String)> {
+    // Quick sanity check: does the init vector make sense?
+    check_iv(iv)?;
+
+    // Deserialize key
+    let decrypted_key = Zeroizing::new(
+        home_private_key
+            .decrypt(padding_scheme(), key)
+            .map_err(|_| ErrorMsg::with_detail(ErrorCode::DecryptFailed, "Couldn't decrypt remote key"))?,
+    );
+
+    // Quick sanity check: does the key length make sense?
+    if decrypted_key.len() != AES_KEY_SIZE {
+        return Err(ErrorMsg::with_detail(
+            ErrorCode::DecryptFailed,
+            format!("Invalid AES key length {}", decrypted_key.len()),
+        )
+        .into());
+    }
+
+    // Deserialize payload
+    let offer = decrypt_payload(decrypted_key.as_slice(), iv, payload)?;
+    Ok((decrypted_key, offer))
+}
+
+/// Checks that an init vector is the right size,
+/// since AES-GCM panics otherwise.
+fn check_iv(iv: &[u8]) -> Result<()> {
+    if iv.len() != AES_IV_SIZE {
+        Err(ErrorMsg::with_detail(
+            ErrorCode::OfferInvalid,
+            format!("Invalid AES init vector length {}", iv.len()),
+        )
+        .into())
+    } else {
+        Ok(())
+    }
 }
@@ -0,0 +1,217 @@
+//! Physical key presses, which press keys by their
+//! position on the keyboard so that they don't depend
+//! on the keyboard layouts of either computer.
+
+use hammeregg_core::KeyCode;
+
+pub use self::platform::PhysicalKeyboard;
+
+/// Gets the Linux evdev code of a key. X11 keycodes
+/// are these codes plus [`X11_KEYCODE_OFFSET`].
+pub fn evdev_code(code: KeyCode) -> u16 {
+    match code {
+        KeyCode::Escape => 1,
+        KeyCode::Digit1 => 2,
+        KeyCode::Digit2 => 3,
+        KeyCode::Digit3 => 4,
+        KeyCode::Digit4 => 5,
+        KeyCode::Digit5 => 6,
+        KeyCode::Digit6 => 7,
+        KeyCode::Digit7 => 8,
+        KeyCode::Digit8 => 9,
+        KeyCode::Digit9 => 10,
+        KeyCode::Digit0 => 11,
+        KeyCode::Minus => 12,
+        KeyCode::Equal => 13,
+        KeyCode::Backspace => 14,
+        KeyCode::Tab => 15,
+        KeyCode::KeyQ => 16,
+        KeyCode::KeyW => 17,
+        KeyCode::KeyE => 18,
+        KeyCode::KeyR => 19,
+        KeyCode::KeyT => 20,
+        KeyCode::KeyY => 21,
+        KeyCode::KeyU => 22,
+        KeyCode::KeyI => 23,
+        KeyCode::KeyO => 24,
+        KeyCode::KeyP => 25,
+        KeyCode::BracketLeft => 26,
+        KeyCode::BracketRight => 27,
+        KeyCode::Enter => 28,
+        KeyCode::ControlLeft => 29,
+        KeyCode::KeyA => 30,
+        KeyCode::KeyS => 31,
+        KeyCode::KeyD => 32,
+        KeyCode::KeyF => 33,
+        KeyCode::KeyG => 34,
+        KeyCode::KeyH => 35,
+        KeyCode::KeyJ => 36,
+        KeyCode::KeyK => 37,
+        KeyCode::KeyL => 38,
+        KeyCode::Semicolon => 39,
+        KeyCode::Quote => 40,
+        KeyCode::Backquote => 41,
+        KeyCode::ShiftLeft => 42,
+        KeyCode::Backslash => 43,
+        KeyCode::KeyZ => 44,
+        KeyCode::KeyX => 45,
+        KeyCode::KeyC => 46,
+        KeyCode::KeyV => 47,
+        KeyCode::KeyB => 48,
+        KeyCode::KeyN => 49,
+        KeyCode::KeyM => 50,
+        KeyCode::Comma => 51,
+        KeyCode::Period => 52,
+        KeyCode::Slash => 53,
+        KeyCode::ShiftRight => 54,
+        KeyCode::NumpadMultiply => 55,
+        KeyCode::AltLeft => 56,
+        KeyCode::Space => 57,
+        KeyCode::CapsLock => 58,
+        KeyCode::F1 => 59,
+        KeyCode::F2 => 60,
+        KeyCode::F3 => 61,
+        KeyCode::F4 => 62,
+        KeyCode::F5 => 63,
+        KeyCode::F6 => 64,
+        KeyCode::F7 => 65,
+        KeyCode::F8 => 66,
+        KeyCode::F9 => 67,
+        KeyCode::F10 => 68,
+        KeyCode::NumLock => 69,
+        KeyCode::ScrollLock => 70,
+        KeyCode::Numpad7 => 71,
+        KeyCode::Numpad8 => 72,
+        KeyCode::Numpad9 => 73,
+        KeyCode::NumpadSubtract => 74,
+        KeyCode::Numpad4 => 75,
+        KeyCode::Numpad5 => 76,
+        KeyCode::Numpad6 => 77,
+        KeyCode::NumpadAdd => 78,
+        KeyCode::Numpad1 => 79,
+        KeyCode::Numpad2 => 80,
+        KeyCode::Numpad3 => 81,
+        KeyCode::Numpad0 => 82,
+        KeyCode::NumpadDecimal => 83,
+        KeyCode::IntlBackslash => 86,
+        KeyCode::F11 => 87,
+        KeyCode::F12 => 88,
+        KeyCode::IntlRo => 89,
+        KeyCode::Convert => 92,
+        KeyCode::KanaMode => 93,
+        KeyCode::NonConvert => 94,
+        KeyCode::NumpadEnter => 96,
+        KeyCode::ControlRight => 97,
+        KeyCode::NumpadDivide => 98,
+        KeyCode::PrintScreen => 99,
+        KeyCode::AltRight => 100,
+        KeyCode::Home => 102,
+        KeyCode::ArrowUp => 103,
+        KeyCode::PageUp => 104,
+        KeyCode::ArrowLeft => 105,
+        KeyCode::ArrowRight => 106,
+        KeyCode::End => 107,
+        KeyCode::ArrowDown => 108,
+        KeyCode::PageDown => 109,
+        KeyCode::Insert => 110,
+        KeyCode::Delete => 111,
+        KeyCode::AudioVolumeMute => 113,
+        KeyCode::AudioVolumeDown => 114,
+        KeyCode::AudioVolumeUp => 115,
+        KeyCode::NumpadEqual => 117,
+        KeyCode::Pause => 119,
+        KeyCode::NumpadComma => 121,
+        KeyCode::Lang1 => 122,
+        KeyCode::Lang2 => 123,
+        KeyCode::IntlYen => 124,
+        KeyCode::MetaLeft => 125,
+        KeyCode::MetaRight => 126,
+        KeyCode::ContextMenu => 127,
+        KeyCode::Help => 138,
+        KeyCode::MediaTrackNext => 163,
+        KeyCode::MediaPlayPause => 164,
+        KeyCode::MediaTrackPrevious => 165,
+        KeyCode::MediaStop => 166,
+        KeyCode::F13 => 183,
+        KeyCode::F14 => 184,
+        KeyCode::F15 => 185,
+        KeyCode::F16 => 186,
+        KeyCode::F17 => 187,
+        KeyCode::F18 => 188,
+        KeyCode::F19 => 189,
+        KeyCode::F20 => 190,
+        KeyCode::F21 => 191,
+        KeyCode::F22 => 192,
+        KeyCode::F23 => 193,
+        KeyCode::F24 => 194,
+    }
+}
+
+/// The difference between a key's X11 keycode and
+/// its evdev code.
+pub const X11_KEYCODE_OFFSET: u16 = 8;
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::os::raw::c_int;
+
+    use anyhow::{anyhow, Result};
+    use hammeregg_core::KeyCode;
+    use x11_dl::xlib::{Display, Xlib};
+    use x11_dl::xtest::Xf86vmode as XTest;
+
+    use super::{evdev_code, X11_KEYCODE_OFFSET};
+
+    /// Presses physical keys with the XTest extension.
+    pub struct PhysicalKeyboard {
+        xlib: Xlib,
+        xtest: XTest,
+        display: *mut Display,
+    }
+
+    impl PhysicalKeyboard {
+        pub fn open() -> Result<Self> {
+            let xlib = Xlib::open()?;
+            let xtest = XTest::open()?;
+            let display = unsafe { (xlib.XOpenDisplay)(std::ptr::null()) };
+            if display.is_null() {
+                return Err(anyhow!("Couldn't open the X display"));
+            }
+            Ok(Self { xlib, xtest, display })
+        }
+
+        /// Presses or releases a key.
+        pub fn key(&mut self, code: KeyCode, down: bool) {
+            let keycode = evdev_code(code) + X11_KEYCODE_OFFSET;
+            unsafe {
+                (self.xtest.XTestFakeKeyEvent)(self.display, keycode as u32, down as c_int, 0);
+                (self.xlib.XFlush)(self.display);
+            }
+        }
+    }
+
+    impl Drop for PhysicalKeyboard {
+        fn drop(&mut self) {
+            unsafe {
+                (self.xlib.XCloseDisplay)(self.display);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use anyhow::{anyhow, Result};
+    use hammeregg_core::KeyCode;
+
+    /// Physical keys are only supported on Linux for now.
+    pub struct PhysicalKeyboard;
+
+    impl PhysicalKeyboard {
+        pub fn open() -> Result<Self> {
+            Err(anyhow!("Physical key codes are only supported on Linux"))
+        }
+
+        pub fn key(&mut self, _code: KeyCode, _down: bool) {}
+    }
+}
@@ -5,14 +5,57 @@
 #![feature(try_blocks)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+pub mod clipboard;
+pub mod gamepad;
 pub mod input;
 pub mod key;
+pub mod keyboard;
 pub mod net;
 pub mod pion;
 pub mod stream;
+pub mod terminal;
+pub mod touch;
+pub mod transfer;
+pub mod tunnel;
 pub mod ui;
+#[cfg(target_os = "linux")]
+pub mod uinput;
 pub mod work;
 
-fn main() {
+use std::path::Path;
+
+use anyhow::Result;
+
+fn main() -> Result<()> {
+    let matches = clap::clap_app!("Hammeregg" =>
+        (about: "Lets remotes access this computer through Hammeregg.")
+        (version: clap::crate_version!())
+        (@subcommand replay =>
+            (about: "Replays an input recording on this computer")
+            (@arg FILE: +required "Recording to replay")
+            (@arg SPEED: -s --speed default_value("1") validator(validate_speed)
+                "How many times faster than recorded to replay it")
+        )
+    )
+    .get_matches();
+
+    if let Some(matches) = matches.subcommand_matches("replay") {
+        // These use `.unwrap()` since clap has already ensured that everything is valid.
+        let path = Path::new(matches.value_of_os("FILE").unwrap());
+        let speed = validate_speed(matches.value_of("SPEED").unwrap()).unwrap();
+        return input::replay(path, speed);
+    }
+
     ui::show_ui();
+    Ok(())
+}
+
+fn validate_speed(val: &str) -> Result<f64, String> {
+    match val.parse::<f64>() {
+        Ok(speed) if speed >= input::MIN_REPLAY_SPEED && speed.is_finite() => Ok(speed),
+        _ => Err(format!(
+            "Speed must be a number of at least {}",
+            input::MIN_REPLAY_SPEED
+        )),
+    }
 }
@@ -3,47 +3,102 @@ use std::fs::File;
 use std::io::BufReader;
 use std::net::SocketAddr;
 use std::os::raw::c_char;
+use std::path::PathBuf;
 use std::process::Child;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-use aes_gcm::aead::Aead;
-use aes_gcm::{Aes256Gcm, Key as AesGcmKey, NewAead, Nonce};
 use anyhow::{anyhow, Context, Result};
 use atomic_refcell::{AtomicRefCell, AtomicRefMut};
-use enigo::Enigo;
 use futures::channel::{mpsc, oneshot};
-use futures::future::BoxFuture;
-use futures::{future, FutureExt, SinkExt, StreamExt};
+use futures::future::Either;
+use futures::{SinkExt, StreamExt};
+use hammeregg_core::codec::BsonCodec;
+use hammeregg_core::limits::MAX_PACKET_SIZE;
+use hammeregg_core::websocket::{deserialize_message, serialize_message};
 use hammeregg_core::{
-    deserialize_packet, serialize_packet, HandshakeInitPacket, HandshakePacket, InputPacket, VERSION_1_0,
+    deserialize_and_validate_packet, ClipboardPacket, Codec, ErrorCode, ErrorMsg, FileTransferPacket,
+    HandshakeInitPacket, HandshakePacket, InputPacket, TerminalPacket, TunnelPacket, SUPPORTED_VERSIONS,
 };
-use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaCha20Rng;
 use rsa::{PublicKey, RsaPrivateKey, RsaPublicKey};
 use tokio::net::TcpStream;
 use tokio_rustls::rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::{client_async_tls_with_config, Connector, MaybeTlsStream, WebSocketStream};
 use url::Url;
+use validator::Validate;
 use zeroize::Zeroizing;
 
 use crate::pion::{make_c_closure, PeerConnection};
 use crate::stream::MonitorBounds;
-use crate::{input, key, pion, stream};
+use crate::terminal::Terminal;
+use crate::transfer::FileTransfers;
+use crate::tunnel::{TunnelTarget, Tunnels};
+use crate::{clipboard, input, key, pion, stream};
 
 pub type WSS = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
+/// An initialized connection to the signalling server.
+#[derive(Debug)]
+pub struct SignallingConnection {
+    pub socket: WSS,
+    /// The codec negotiated with the signalling server.
+    pub codec: Codec,
+}
+
+/// Settings that control what a remote can do
+/// once it's connected.
+#[derive(Clone, Debug, Default)]
+pub struct SessionSettings {
+    /// The folders that the remote can browse and
+    /// transfer files to and from.
+    pub shared_folders: Vec<PathBuf>,
+    /// Whether the remote can open a terminal.
+    pub terminal: bool,
+    /// The hosts that the remote can tunnel
+    /// TCP connections to.
+    pub tunnel_allowlist: Vec<TunnelTarget>,
+    /// How long the remote can go without sending
+    /// input before the keys and mouse buttons it's
+    /// holding are released. They're only released
+    /// when the remote disconnects if this is `None`.
+    pub input_idle_timeout: Option<Duration>,
+    /// The file to record each session's input to,
+    /// so that it can be replayed later.
+    pub input_recording: Option<PathBuf>,
+}
+
 /// Stores the components underlying a single remote connection.
 struct RemoteConnection {
+    peer: u32,
     connection: PeerConnection,
-    stop_notifier: Arc<AtomicBool>,
+    /// Set once the connection has stopped, after which
+    /// Pion frees it. This is read-locked for as long as
+    /// the connection is used outside of its own thread,
+    /// so that it can't be freed in the meantime.
+    stop_notifier: Arc<RwLock<bool>>,
     video_process: Child,
+    keys: SessionKeys,
+}
+
+/// The AES keys that encrypt the ICE candidates trickled
+/// over the signalling server after the offer and answer.
+/// Each side keeps using the key that it picked for its
+/// own session description.
+struct SessionKeys {
+    /// The remote's key, taken from its offer.
+    remote: Zeroizing<Vec<u8>>,
+    /// Our key, sent along with the answer.
+    home: Zeroizing<Vec<u8>>,
 }
 
 impl Drop for RemoteConnection {
     fn drop(&mut self) {
-        if !self.connection.is_null() && !self.stop_notifier.load(Ordering::SeqCst) {
+        let stopped = self.stop_notifier.read().unwrap();
+        if !self.connection.is_null() && !*stopped {
             unsafe {
                 pion::hammer_rtp2rtc_stop(self.connection);
             }
@@ -59,7 +114,7 @@ pub async fn init_signalling_connection(
     desktop_name: String,
     addr: SocketAddr,
     extra_ca: Option<String>,
-) -> Result<WSS> {
+) -> Result<SignallingConnection> {
     println!("Connecting to signalling server {} with name {}", addr, desktop_name);
 
     // Setup TLS
@@ -102,80 +157,146 @@ pub async fn init_signalling_connection(
     let stream = TcpStream::connect(addr)
         .await
         .context("Couldn't connect to signalling server")?;
-    let (mut socket, _) = client_async_tls_with_config(url, stream, None, Some(connector))
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(MAX_PACKET_SIZE),
+        max_frame_size: Some(MAX_PACKET_SIZE),
+        ..Default::default()
+    };
+    let (mut socket, _) = client_async_tls_with_config(url, stream, Some(ws_config), Some(connector))
         .await
         .context("Couldn't connect to signalling server: TLS or WebSocket handshake failed")?;
 
     // Hammeregg Signalling Handshake
     // First, send a HomeInit packet to the signalling server.
     socket
-        .send(serialize_packet(&HandshakeInitPacket::new(
-            VERSION_1_0,
-            HandshakePacket::HomeInit {
-                home_name: desktop_name,
-            },
-        )?)?)
+        .send(serialize_message(
+            &BsonCodec,
+            &HandshakeInitPacket::new(
+                SUPPORTED_VERSIONS,
+                HandshakePacket::HomeInit {
+                    home_name: desktop_name,
+                },
+            )?,
+        )?)
         .await?;
 
     // Then wait for a HomeInitResponse packet.
-    match deserialize_packet::<HandshakePacket>(
+    let codec = match deserialize_message::<_, HandshakePacket>(
+        &BsonCodec,
         &socket
             .next()
             .await
             .context("Handshake failed: could not read packet")??,
     )? {
-        HandshakePacket::HomeInitResponse { response } => response?,
+        HandshakePacket::HomeInitResponse {
+            response,
+            version,
+            codec,
+        } => {
+            response?;
+            println!("Negotiated protocol version {:#010x} and codec {:?}", version, codec);
+            codec
+        }
         _ => Err(anyhow!(
             "Handshake failed: server did not respond HomeInitResponse to HomeInit"
         ))?,
-    }
+    };
 
-    Ok(socket)
+    Ok(SignallingConnection { socket, codec })
 }
 
 pub async fn handle_signalling_requests(
-    socket: WSS,
+    connection: SignallingConnection,
     home_private_key: RsaPrivateKey,
     remote_public_key: RsaPublicKey,
     monitor_bounds: MonitorBounds,
+    settings: SessionSettings,
 ) -> Result<()> {
-    let (mut send, mut recv) = socket.split();
+    let SignallingConnection { socket, codec } = connection;
+    let (mut send, recv) = socket.split();
+    let mut recv = recv.fuse();
+    let (candidate_tx, mut candidate_rx) = mpsc::unbounded();
     let remote_ref: AtomicRefCell<Option<RemoteConnection>> = AtomicRefCell::new(None);
 
     println!("Handling signalling requests!");
-    // purely functional version of this loop blocked on https://github.com/rust-lang/rust/issues/90656
-    while let Some(packet) = recv.next().await {
-        let res: Result<BoxFuture<Result<Message>>> = try {
-            match deserialize_packet::<HandshakePacket>(&packet.context("Signalling failed: could not read packet")?)? {
-                HandshakePacket::RemoteOffer { peer, key, iv, payload } => handle_remote_offer(
-                    remote_ref.borrow_mut(),
-                    peer,
-                    key,
-                    iv,
-                    payload,
-                    &home_private_key,
-                    &remote_public_key,
-                    monitor_bounds,
-                )
-                .boxed(),
-                _ => Err(anyhow!("Signalling failed: did not get a RemoteOffer packet"))?,
+    loop {
+        let event = futures::select! {
+            packet = recv.next() => match packet {
+                Some(packet) => Either::Left(packet),
+                None => break,
+            },
+            candidate = candidate_rx.select_next_some() => Either::Right(candidate),
+        };
+        let res: Result<Option<Message>> = match event {
+            Either::Left(packet) => {
+                try {
+                    let packet = deserialize_message::<_, HandshakePacket>(
+                        &codec,
+                        &packet.context("Signalling failed: could not read packet")?,
+                    )?;
+                    // Check sizes before doing any decryption
+                    if let Err(errors) = packet.validate() {
+                        match packet {
+                            HandshakePacket::RemoteOffer { peer, version, .. } => {
+                                eprintln!("Signalling failed: invalid offer from peer {}: {}", peer, errors);
+                                let error = ErrorMsg::with_detail(ErrorCode::OfferInvalid, errors.to_string())
+                                    .for_version(version);
+                                Some(serialize_message(
+                                    &codec,
+                                    &HandshakePacket::HomeAnswerFailure { peer, error },
+                                )?)
+                            }
+                            _ => Err(errors)?,
+                        }
+                    } else {
+                        match packet {
+                            HandshakePacket::RemoteOffer {
+                                peer,
+                                key,
+                                iv,
+                                payload,
+                                codec: remote_codec,
+                                version: remote_version,
+                            } => Some(
+                                handle_remote_offer(
+                                    remote_ref.borrow_mut(),
+                                    codec,
+                                    peer,
+                                    key,
+                                    iv,
+                                    payload,
+                                    remote_codec,
+                                    remote_version,
+                                    &home_private_key,
+                                    &remote_public_key,
+                                    monitor_bounds,
+                                    settings.clone(),
+                                    candidate_tx.clone(),
+                                )
+                                .await?,
+                            ),
+                            HandshakePacket::RemoteIceCandidate { peer, iv, payload } => {
+                                handle_remote_ice_candidate(remote_ref.borrow().as_ref(), peer, &iv, &payload)?;
+                                None
+                            }
+                            _ => Err(anyhow!(
+                                "Signalling failed: did not get a RemoteOffer or RemoteIceCandidate packet"
+                            ))?,
+                        }
+                    }
+                }
+            }
+            Either::Right((peer, candidate)) => {
+                encrypt_home_ice_candidate(remote_ref.borrow().as_ref(), codec, peer, candidate)
             }
         };
         match res {
-            Ok(inner) => inner
-                .then(|inner_res| match inner_res {
-                    Ok(inner) => send.send(inner).boxed(),
-                    Err(err) => {
-                        eprintln!("{:?}", err);
-                        future::ready(Ok(())).boxed()
-                    }
-                })
-                .await
-                .map_err(|x| {
-                    let out = anyhow!(x);
-                    eprintln!("Signalling handler loop crashed: {:?}", out);
-                    out
-                })?,
+            Ok(Some(message)) => send.send(message).await.map_err(|x| {
+                let out = anyhow!(x);
+                eprintln!("Signalling handler loop crashed: {:?}", out);
+                out
+            })?,
+            Ok(None) => {}
             Err(err) => {
                 eprintln!("{:?}", err);
             }
@@ -186,118 +307,169 @@ pub async fn handle_signalling_requests(
 
 async fn handle_remote_offer<'a>(
     mut remote_ref: AtomicRefMut<'a, Option<RemoteConnection>>,
+    codec: Codec,
     peer: u32,
     key: Vec<u8>,
     iv: Vec<u8>,
     payload: Vec<u8>,
+    remote_codec: Codec,
+    remote_version: u32,
     home_private_key: &RsaPrivateKey,
     remote_public_key: &RsaPublicKey,
     monitor_bounds: MonitorBounds,
+    settings: SessionSettings,
+    candidate_tx: mpsc::UnboundedSender<(u32, String)>,
 ) -> Result<Message> {
     println!(
-        "Handling remote offer from peer {} with payload length {}",
+        "Handling remote offer from peer {} with protocol version {:#010x} and payload length {}",
         peer,
+        remote_version,
         payload.len()
     );
 
-    let result = try {
-        // Quick sanity check: does the init vector make sense?
-        if iv.len() != key::AES_IV_SIZE {
-            Err(anyhow!("Invalid AES init vector length {}", iv.len()))?;
-        }
-
-        // Deserialize key
-        let decrypted_key = home_private_key
-            .decrypt(key::padding_scheme(), key.as_slice())
-            .context("Signalling failed: couldn't decrypt remote key")?;
+    let result: Result<Message> = try {
+        let (remote_key, offer) = key::decrypt_offer(home_private_key, &key, &iv, &payload)?;
 
-        // Quick sanity check: does the key length make sense?
-        if decrypted_key.len() != key::AES_KEY_SIZE {
-            Err(anyhow!("Invalid AES key length {}", decrypted_key.len()))?;
-        }
-
-        // Deserialize payload
-        let aes_cipher = Aes256Gcm::new(AesGcmKey::from_slice(decrypted_key.as_slice()));
-        let decrypted_payload = aes_cipher
-            .decrypt(iv.as_slice().into(), payload.as_slice())
-            .map_err(|_| anyhow!("Signalling failed: couldn't decrypt remote payload"))?;
+        let mut rng = ChaCha20Rng::from_entropy();
+        let home_key = key::gen_aes_key(&mut rng).context("Signalling failed")?;
+        let keys = SessionKeys {
+            remote: remote_key,
+            home: home_key,
+        };
 
         // Start the server
-        let (new_remote, answer) = start_pion_server(
-            String::from_utf8(decrypted_payload).context("Signalling failed: offer was not a valid string")?,
-            monitor_bounds,
-        )
-        .await?;
+        let (new_remote, answer) =
+            start_pion_server(offer, peer, keys, candidate_tx, monitor_bounds, settings, remote_codec).await?;
 
         // Encrypt answer payload
-        let mut rng = ChaCha20Rng::from_entropy();
-
-        let mut out_key_data = Zeroizing::new(Vec::with_capacity(key::AES_KEY_SIZE));
-        out_key_data.resize(key::AES_KEY_SIZE, 0);
-        rng.try_fill_bytes(out_key_data.as_mut_slice())
-            .context("Signalling failed: couldn't generate AES key")?;
-        let out_key = AesGcmKey::from_slice(out_key_data.as_slice());
-
-        let mut out_iv_data = Vec::with_capacity(key::AES_IV_SIZE);
-        out_iv_data.resize(key::AES_IV_SIZE, 0);
-        rng.try_fill_bytes(out_iv_data.as_mut_slice())
-            .context("Signalling failed: couldn't generate AES init vector")?;
-        let out_nonce = Nonce::from_slice(out_iv_data.as_slice());
-
-        let out_aes_cipher = Aes256Gcm::new(out_key);
-
         let encrypted_key = remote_public_key
-            .encrypt(&mut rng, key::padding_scheme(), &**out_key_data)
+            .encrypt(&mut rng, key::padding_scheme(), new_remote.keys.home.as_slice())
             .context("Signalling failed: key couldn't be encrypted")?;
-        let encrypted_answer = out_aes_cipher
-            .encrypt(out_nonce, answer.into_bytes().as_slice())
-            .map_err(|_| anyhow!("Signalling failed: answer couldn't be encrypted"))?;
-
-        let message = serialize_packet(&HandshakePacket::HomeAnswerSuccess {
-            peer,
-            key: encrypted_key,
-            iv: out_iv_data,
-            payload: encrypted_answer,
-        })?;
+        let (out_iv, encrypted_answer) = key::encrypt_payload(&mut rng, &new_remote.keys.home, answer.as_bytes())
+            .context("Signalling failed: answer couldn't be encrypted")?;
+
+        let message = serialize_message(
+            &codec,
+            &HandshakePacket::HomeAnswerSuccess {
+                peer,
+                key: encrypted_key,
+                iv: out_iv,
+                payload: encrypted_answer,
+            },
+        )?;
 
         remote_ref.replace(new_remote);
         message
     };
     if let Err(err) = result {
-        eprintln!("{:?}", err);
-        // Notify the remote that signalling failed
-        Ok(serialize_packet(&HandshakePacket::HomeAnswerFailure {
-            peer,
-            error: "Signalling failed".to_string(),
-        })?)
+        eprintln!("Signalling failed: {:?}", err);
+        // Notify the remote that signalling failed, forwarding
+        // the error code if we know why it failed
+        let error = err
+            .downcast_ref::<ErrorMsg>()
+            .cloned()
+            .unwrap_or_else(|| ErrorMsg::with_detail(ErrorCode::Other, "Signalling failed"))
+            .for_version(remote_version);
+        Ok(serialize_message(
+            &codec,
+            &HandshakePacket::HomeAnswerFailure { peer, error },
+        )?)
     } else {
         result
     }
 }
 
+/// Decrypts an ICE candidate trickled in by the remote and
+/// adds it to the remote's connection, unless the
+/// connection has already stopped.
+fn handle_remote_ice_candidate(remote: Option<&RemoteConnection>, peer: u32, iv: &[u8], payload: &[u8]) -> Result<()> {
+    let remote = remote.filter(|remote| remote.peer == peer).ok_or_else(|| {
+        anyhow!(
+            "Signalling failed: got an ICE candidate from peer {} before its offer",
+            peer
+        )
+    })?;
+    // Pion frees the connection once it stops, so candidates
+    // that arrive after that are dropped. Holding the lock
+    // keeps it from being freed until the candidate is added.
+    let stopped = remote.stop_notifier.read().unwrap();
+    if *stopped {
+        eprintln!(
+            "Dropping ICE candidate from peer {}, since its connection has stopped",
+            peer
+        );
+        return Ok(());
+    }
+
+    let candidate = key::decrypt_payload(&remote.keys.remote, iv, payload)
+        .context("Signalling failed: couldn't decrypt ICE candidate")?;
+    let candidate = CString::new(candidate).context("Signalling failed: invalid ICE candidate")?;
+    if unsafe { pion::hammer_rtp2rtc_add_ice_candidate(remote.connection, candidate.as_ptr()) } == 0 {
+        Err(anyhow!("Signalling failed: invalid ICE candidate"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Encrypts one of our own ICE candidates so that it can be
+/// trickled to the remote. Candidates gathered for a remote
+/// that has since been replaced are dropped.
+fn encrypt_home_ice_candidate(
+    remote: Option<&RemoteConnection>,
+    codec: Codec,
+    peer: u32,
+    candidate: String,
+) -> Result<Option<Message>> {
+    let remote = match remote.filter(|remote| remote.peer == peer) {
+        Some(remote) => remote,
+        None => return Ok(None),
+    };
+
+    let mut rng = ChaCha20Rng::from_entropy();
+    let (iv, payload) = key::encrypt_payload(&mut rng, &remote.keys.home, candidate.as_bytes())
+        .context("Signalling failed: ICE candidate couldn't be encrypted")?;
+    Ok(Some(serialize_message(
+        &codec,
+        &HandshakePacket::HomeIceCandidate { peer, iv, payload },
+    )?))
+}
+
 /// Asynchronously starts a Pion RTP -> RTC server, blocking until
 /// the server returns an answer to the given WebRTC offer.
 /// Returns a pointer to the server's PeerConnection, the server's
-/// answer, and an atomic boolean that will be set to true when
-/// the server stops.
-async fn start_pion_server(offer: String, monitor_bounds: MonitorBounds) -> Result<(RemoteConnection, String)> {
+/// answer, and a lock around a boolean that will be set to true
+/// when the server stops. Packets sent over the server's data
+/// channels are decoded with the given codec, and what the
+/// remote can do is limited by `settings`. Local ICE
+/// candidates are sent to `candidate_tx` along with `peer` as
+/// they're gathered, which may be before this returns.
+async fn start_pion_server(
+    offer: String,
+    peer: u32,
+    keys: SessionKeys,
+    candidate_tx: mpsc::UnboundedSender<(u32, String)>,
+    monitor_bounds: MonitorBounds,
+    settings: SessionSettings,
+    codec: Codec,
+) -> Result<(RemoteConnection, String)> {
     let (connection_tx, connection_rx) = oneshot::channel();
     let (answer_tx, answer_rx) = oneshot::channel();
     let (ports_tx, mut ports_rx) = mpsc::unbounded();
-    let stop_notifier = Arc::new(AtomicBool::new(false));
+    let stop_notifier = Arc::new(RwLock::new(false));
     let stop_notifier_out = stop_notifier.clone();
     std::thread::spawn(move || {
         /// A wrapper around a [`PeerConnection`] that ensures
         /// that the rtp2rtc server is cleaned up and that the
-        /// wait-for-stop future is notified.
+        /// stop notifier is set before the connection is freed.
         struct PionServer {
             connection: PeerConnection,
-            stop_notifier: Arc<AtomicBool>,
+            stop_notifier: Arc<RwLock<bool>>,
         }
 
         impl Drop for PionServer {
             fn drop(&mut self) {
-                self.stop_notifier.store(true, Ordering::SeqCst);
+                // Waits for anything that's still using the connection
+                *self.stop_notifier.write().unwrap() = true;
                 unsafe {
                     if !self.connection.is_null() {
                         pion::hammer_rtp2rtc_stop(self.connection);
@@ -325,6 +497,16 @@ async fn start_pion_server(offer: String, monitor_bounds: MonitorBounds) -> Resu
             }
         }
 
+        // Declared before the server so that it outlives the connection
+        let (_candidate_closure, candidate_callback, candidate_callback_user_data) =
+            make_c_closure!(move |candidate: *const c_char| {
+                match unsafe { CStr::from_ptr(candidate) }.to_str() {
+                    // The receiver only hangs up when signalling stops
+                    Ok(candidate) => drop(candidate_tx.unbounded_send((peer, candidate.to_string()))),
+                    Err(err) => eprintln!("Invalid ICE candidate: {}", err),
+                }
+            });
+
         // Init connection
         let connection = unsafe { pion::hammer_rtp2rtc_init() };
         if connection.is_null() {
@@ -341,11 +523,16 @@ async fn start_pion_server(offer: String, monitor_bounds: MonitorBounds) -> Resu
         };
 
         // Signal offer
-        const OFFER_ERR: &str = "RTP2RTC init failed: invalid offer!";
+        let offer_err = || {
+            anyhow!(ErrorMsg::with_detail(
+                ErrorCode::OfferInvalid,
+                "RTP2RTC init failed: invalid offer!",
+            ))
+        };
         let offer = match CString::new(offer) {
             Ok(string) => string,
             Err(_) => {
-                answer_tx.send(Err(anyhow!(OFFER_ERR))).unwrap();
+                answer_tx.send(Err(offer_err())).unwrap();
                 return;
             }
         };
@@ -354,36 +541,41 @@ async fn start_pion_server(offer: String, monitor_bounds: MonitorBounds) -> Resu
             pion::hammer_rtp2rtc_build_offer(offer.as_ptr() as *const c_char, offer.len() as i32)
         };
         if desc.is_null() {
-            answer_tx.send(Err(anyhow!(OFFER_ERR))).unwrap();
+            answer_tx.send(Err(offer_err())).unwrap();
             return;
         }
+        unsafe {
+            pion::hammer_rtp2rtc_on_ice_candidate(server.connection, candidate_callback, candidate_callback_user_data);
+        }
         let answer_c = PionCString {
             inner: unsafe { pion::hammer_rtp2rtc_signal_offer(server.connection, desc) },
         };
         if answer_c.inner.is_null() {
-            answer_tx.send(Err(anyhow!(OFFER_ERR))).unwrap();
+            answer_tx.send(Err(offer_err())).unwrap();
             return;
         }
         let answer = match unsafe { CStr::from_ptr(answer_c.inner) }.to_str() {
             Ok(string) => string,
             Err(_) => {
-                answer_tx.send(Err(anyhow!(OFFER_ERR))).unwrap();
+                answer_tx.send(Err(offer_err())).unwrap();
                 return;
             }
         };
         answer_tx.send(Ok(answer.to_string())).unwrap();
 
         // Start streaming!
-        start_pion_server_inner(connection, ports_tx, monitor_bounds);
+        start_pion_server_inner(connection, ports_tx, monitor_bounds, settings, codec);
     });
     let connection = connection_rx.await??;
     let answer = answer_rx.await??;
     let ports = ports_rx.next().await.ok_or_else(|| anyhow!("Couldn't bind ports"))?;
     let video_process = stream::stream_video(monitor_bounds, ports.0)?;
     let remote = RemoteConnection {
+        peer,
         connection,
         stop_notifier: stop_notifier_out,
         video_process,
+        keys,
     };
     Ok((remote, answer))
 }
@@ -392,17 +584,70 @@ fn start_pion_server_inner(
     connection: PeerConnection,
     ports_tx: mpsc::UnboundedSender<(u16, u16)>,
     monitor_bounds: MonitorBounds,
+    settings: SessionSettings,
+    codec: Codec,
 ) {
     let (_ports_closure, ports_callback, ports_callback_user_data) = make_c_closure!(move |video: u16, audio: u16| {
         ports_tx.unbounded_send((video, audio)).unwrap();
     });
 
-    let mut enigo = Enigo::new();
-    let (_input_closure, input_callback, input_callback_user_data) =
+    let (input_tx, input_rx) = std::sync::mpsc::channel();
+    let input_idle_timeout = settings.input_idle_timeout;
+    let input_recording = settings.input_recording;
+    let input_thread =
+        std::thread::spawn(move || input::handle_inputs(monitor_bounds, input_idle_timeout, input_recording, input_rx));
+    let (input_closure, input_callback, input_callback_user_data) =
         make_c_closure!(move |input_packet: *mut c_void, input_packet_len: usize| {
             let input_packet_raw = unsafe { std::slice::from_raw_parts(input_packet as *const u8, input_packet_len) };
-            match bson::from_slice::<InputPacket>(input_packet_raw).context("Failed to deserialize packet") {
-                Ok(packet) => input::handle_input(&mut enigo, monitor_bounds, packet),
+            match deserialize_and_validate_packet::<_, InputPacket>(&codec, input_packet_raw) {
+                // The input thread only hangs up if it panicked
+                Ok(packet) => drop(input_tx.send(packet)),
+                Err(err) => eprintln!("{:?}", err),
+            }
+        });
+
+    let (clipboard_tx, clipboard_rx) = std::sync::mpsc::channel();
+    let clipboard_thread = std::thread::spawn(move || clipboard::sync_clipboard(connection, codec, clipboard_rx));
+    let (clipboard_closure, clipboard_callback, clipboard_callback_user_data) =
+        make_c_closure!(move |clipboard_packet: *mut c_void, clipboard_packet_len: usize| {
+            let clipboard_packet_raw =
+                unsafe { std::slice::from_raw_parts(clipboard_packet as *const u8, clipboard_packet_len) };
+            match deserialize_and_validate_packet::<_, ClipboardPacket>(&codec, clipboard_packet_raw) {
+                // The clipboard thread only hangs up if the clipboard couldn't be opened
+                Ok(packet) => drop(clipboard_tx.send(packet)),
+                Err(err) => eprintln!("{:?}", err),
+            }
+        });
+
+    // Unfinished downloads are cancelled, the shell is killed and
+    // tunnels are closed when these closures are dropped at the
+    // end of this function, before the connection is freed
+    let mut transfers = FileTransfers::new(connection, codec, settings.shared_folders);
+    let (_files_closure, files_callback, files_callback_user_data) =
+        make_c_closure!(move |files_packet: *mut c_void, files_packet_len: usize| {
+            let files_packet_raw = unsafe { std::slice::from_raw_parts(files_packet as *const u8, files_packet_len) };
+            match deserialize_and_validate_packet::<_, FileTransferPacket>(&codec, files_packet_raw) {
+                Ok(packet) => transfers.handle_packet(packet),
+                Err(err) => eprintln!("{:?}", err),
+            }
+        });
+    let mut terminal = Terminal::new(connection, codec, settings.terminal);
+    let (_terminal_closure, terminal_callback, terminal_callback_user_data) =
+        make_c_closure!(move |terminal_packet: *mut c_void, terminal_packet_len: usize| {
+            let terminal_packet_raw =
+                unsafe { std::slice::from_raw_parts(terminal_packet as *const u8, terminal_packet_len) };
+            match deserialize_and_validate_packet::<_, TerminalPacket>(&codec, terminal_packet_raw) {
+                Ok(packet) => terminal.handle_packet(packet),
+                Err(err) => eprintln!("{:?}", err),
+            }
+        });
+    let mut tunnels = Tunnels::new(connection, codec, settings.tunnel_allowlist);
+    let (_tunnel_closure, tunnel_callback, tunnel_callback_user_data) =
+        make_c_closure!(move |tunnel_packet: *mut c_void, tunnel_packet_len: usize| {
+            let tunnel_packet_raw =
+                unsafe { std::slice::from_raw_parts(tunnel_packet as *const u8, tunnel_packet_len) };
+            match deserialize_and_validate_packet::<_, TunnelPacket>(&codec, tunnel_packet_raw) {
+                Ok(packet) => tunnels.handle_packet(packet),
                 Err(err) => eprintln!("{:?}", err),
             }
         });
@@ -413,6 +658,26 @@ fn start_pion_server_inner(
             ports_callback_user_data,
             input_callback,
             input_callback_user_data,
+            clipboard_callback,
+            clipboard_callback_user_data,
+            files_callback,
+            files_callback_user_data,
+            terminal_callback,
+            terminal_callback_user_data,
+            tunnel_callback,
+            tunnel_callback_user_data,
         );
     }
+
+    // Release anything that the remote was holding down
+    drop(input_closure);
+    if input_thread.join().is_err() {
+        eprintln!("Input thread panicked!");
+    }
+
+    // Stop syncing the clipboard before the connection is freed
+    drop(clipboard_closure);
+    if clipboard_thread.join().is_err() {
+        eprintln!("Clipboard thread panicked!");
+    }
 }
@@ -14,6 +14,12 @@ macro_rules! define_handle_structs {
             }
 
             impl $name {
+                /// A null handle, for tests that never pass it to Pion.
+                #[cfg(test)]
+                pub fn null() -> Self {
+                    Self { ptr: 0 }
+                }
+
                 #[inline]
                 pub fn is_null(&self) -> bool {
                     self.ptr == 0
@@ -56,27 +62,112 @@ extern "C" {
 
     /// Signals a remote offer to the RTP -> WebRTC connection.
     /// Returns an answer that should be forwarded to the remote peer.
+    /// This doesn't wait for ICE gathering to finish, local ICE
+    /// candidates are passed to the callback registered with
+    /// [`hammer_rtp2rtc_on_ice_candidate`] instead.
     ///
     /// **If signalling fails, this will return a null pointer.**
     ///
     /// The returned CString should be freed using [`hammer_free_cstring`].
     pub fn hammer_rtp2rtc_signal_offer(connection: PeerConnection, desc: SessionDescription) -> *mut c_char;
 
+    /// Registers a callback that is passed every local ICE candidate
+    /// as it's gathered, serialized as a JSON `RTCIceCandidateInit`.
+    /// This should be called before [`hammer_rtp2rtc_signal_offer`]
+    /// so that no candidates are missed.
+    ///
+    /// The candidate passed to `candidate_callback` is freed as soon
+    /// as the callback returns.
+    pub fn hammer_rtp2rtc_on_ice_candidate(
+        connection: PeerConnection,
+        candidate_callback: extern "C" fn(candidate: *const c_char, user_data: *mut c_void),
+        candidate_callback_user_data: *mut c_void,
+    );
+
+    /// Adds an ICE candidate from the remote peer, serialized as a
+    /// JSON `RTCIceCandidateInit`, to the RTP -> WebRTC connection.
+    ///
+    /// **If the candidate is invalid, this will return 0.**
+    pub fn hammer_rtp2rtc_add_ice_candidate(connection: PeerConnection, candidate: *const c_char) -> c_int;
+
     /// Synchronously starts the RTP -> WebRTC connection. RTP packets
     /// will be read from local ports and forwarded to the remote peer.
     ///
     /// The ports this method binds to are passed to `ports_callback`.
     ///
     /// Key and mouse inputs from the remote peer are passed to
-    /// `input_callback`.
+    /// `input_callback`, clipboard packets from the remote peer
+    /// are passed to `clipboard_callback`, file transfer packets
+    /// from the remote peer are passed to `files_callback`,
+    /// terminal packets from the remote peer are passed to
+    /// `terminal_callback`, and tunnel packets from the remote
+    /// peer are passed to `tunnel_callback`.
     pub fn hammer_rtp2rtc_start(
         connection: PeerConnection,
         ports_callback: extern "C" fn(video: u16, audio: u16, user_data: *mut c_void),
         ports_callback_user_data: *mut c_void,
         input_callback: extern "C" fn(input_packet: *mut c_void, input_packet_len: usize, user_data: *mut c_void),
         input_callback_user_data: *mut c_void,
+        clipboard_callback: extern "C" fn(
+            clipboard_packet: *mut c_void,
+            clipboard_packet_len: usize,
+            user_data: *mut c_void,
+        ),
+        clipboard_callback_user_data: *mut c_void,
+        files_callback: extern "C" fn(files_packet: *mut c_void, files_packet_len: usize, user_data: *mut c_void),
+        files_callback_user_data: *mut c_void,
+        terminal_callback: extern "C" fn(
+            terminal_packet: *mut c_void,
+            terminal_packet_len: usize,
+            user_data: *mut c_void,
+        ),
+        terminal_callback_user_data: *mut c_void,
+        tunnel_callback: extern "C" fn(tunnel_packet: *mut c_void, tunnel_packet_len: usize, user_data: *mut c_void),
+        tunnel_callback_user_data: *mut c_void,
     );
 
+    /// Sends an encoded clipboard packet to the remote peer.
+    ///
+    /// **If the clipboard channel isn't open, this will return 0.**
+    pub fn hammer_rtp2rtc_send_clipboard(
+        connection: PeerConnection,
+        clipboard_packet: *const c_void,
+        clipboard_packet_len: usize,
+    ) -> c_int;
+
+    /// Sends an encoded file transfer packet to the remote peer.
+    /// This blocks while too much data is already queued on the
+    /// files channel.
+    ///
+    /// **If the files channel isn't open, this will return 0.**
+    pub fn hammer_rtp2rtc_send_files(
+        connection: PeerConnection,
+        files_packet: *const c_void,
+        files_packet_len: usize,
+    ) -> c_int;
+
+    /// Sends an encoded terminal packet to the remote peer.
+    /// This blocks while too much data is already queued on
+    /// the terminal channel.
+    ///
+    /// **If the terminal channel isn't open, this will return 0.**
+    pub fn hammer_rtp2rtc_send_terminal(
+        connection: PeerConnection,
+        terminal_packet: *const c_void,
+        terminal_packet_len: usize,
+    ) -> c_int;
+
+    /// Sends an encoded tunnel packet to the remote peer.
+    /// This blocks while too much data is already queued on
+    /// the tunnel channel.
+    ///
+    /// **If the tunnel channel isn't open, this will return 0.**
+    pub fn hammer_rtp2rtc_send_tunnel(
+        connection: PeerConnection,
+        tunnel_packet: *const c_void,
+        tunnel_packet_len: usize,
+    ) -> c_int;
+
     /// Asynchronously requests the RTP -> WebRTC connection to stop.
     /// If this is called more than once, any subsequent calls will have
     /// no effect.
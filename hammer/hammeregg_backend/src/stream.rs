@@ -1,10 +1,11 @@
 use std::process::{Child, Command};
 
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 
 /// The logical pixel boundaries of
 /// the monitor that Hammeregg is sharing.
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct MonitorBounds {
     pub x: i32,
     pub y: i32,
@@ -0,0 +1,204 @@
+//! Remote terminals, which run the user's shell
+//! in a PTY on the home computer.
+
+use std::io::{Read, Write};
+use std::os::raw::c_void;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use hammeregg_core::limits::MAX_TERMINAL_DATA_SIZE;
+use hammeregg_core::{serialize_packet, Codec, ErrorCode, ErrorMsg, TerminalPacket};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+use crate::pion;
+use crate::pion::PeerConnection;
+
+/// How often a shell whose output has ended
+/// is checked to see if it has exited.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A shell running in a PTY.
+struct Shell {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Arc<Mutex<Box<dyn Child + Send + Sync>>>,
+}
+
+/// Handles terminal packets from a remote. The shell
+/// is killed when this is dropped, and nothing is sent
+/// to the remote after that.
+pub struct Terminal {
+    connection: PeerConnection,
+    codec: Codec,
+    /// Whether the remote is allowed to open a terminal.
+    enabled: bool,
+    shell: Option<Shell>,
+    /// The connection used by the thread that forwards
+    /// the shell's output, which is taken once the
+    /// connection can't be used anymore. That thread
+    /// holds this lock while sending, so that it never
+    /// sends after the connection is freed.
+    output_connection: Arc<Mutex<Option<PeerConnection>>>,
+}
+
+impl Terminal {
+    pub fn new(connection: PeerConnection, codec: Codec, enabled: bool) -> Self {
+        Self {
+            connection,
+            codec,
+            enabled,
+            shell: None,
+            output_connection: Arc::new(Mutex::new(Some(connection))),
+        }
+    }
+
+    /// Handles a terminal packet from the remote.
+    pub fn handle_packet(&mut self, packet: TerminalPacket) {
+        match packet {
+            TerminalPacket::Open { cols, rows } => {
+                if let Err(error) = self.open(cols, rows) {
+                    eprintln!("Couldn't open terminal: {}", error);
+                    let packet = TerminalPacket::Exit {
+                        success: false,
+                        error: Some(error),
+                    };
+                    if let Err(err) = send_terminal(self.connection, self.codec, &packet) {
+                        eprintln!("Couldn't send terminal exit: {:?}", err);
+                    }
+                }
+            }
+            TerminalPacket::Data(data) => {
+                if let Some(shell) = &mut self.shell {
+                    if let Err(err) = shell.writer.write_all(&data) {
+                        eprintln!("Couldn't write to terminal: {}", err);
+                    }
+                }
+            }
+            TerminalPacket::Resize { cols, rows } => {
+                if let Some(shell) = &self.shell {
+                    if let Err(err) = shell.master.resize(pty_size(cols, rows)) {
+                        eprintln!("Couldn't resize terminal: {}", err);
+                    }
+                }
+            }
+            TerminalPacket::Exit { .. } => eprintln!("Only the home computer can exit terminals"),
+        }
+    }
+
+    /// Starts the user's shell. Only one shell
+    /// can run at a time.
+    fn open(&mut self, cols: u16, rows: u16) -> Result<(), ErrorMsg> {
+        if !self.enabled {
+            return Err(ErrorMsg::with_detail(
+                ErrorCode::Unauthorized,
+                "Remote terminals are disabled on this computer",
+            ));
+        }
+        if let Some(shell) = &self.shell {
+            if let Ok(None) = shell.child.lock().unwrap().try_wait() {
+                return Err(ErrorMsg::with_detail(ErrorCode::Other, "A terminal is already open"));
+            }
+        }
+
+        let pty_error = |err: anyhow::Error| ErrorMsg::with_detail(ErrorCode::Other, err.to_string());
+        let pair = native_pty_system().openpty(pty_size(cols, rows)).map_err(pty_error)?;
+        let mut command = CommandBuilder::new_default_prog();
+        command.env("TERM", "xterm-256color");
+        let child = pair.slave.spawn_command(command).map_err(pty_error)?;
+        // The shell's output only ends once every
+        // handle to the slave side has been closed
+        drop(pair.slave);
+
+        let reader = pair.master.try_clone_reader().map_err(pty_error)?;
+        let writer = pair.master.try_clone_writer().map_err(pty_error)?;
+        let child = Arc::new(Mutex::new(child));
+        {
+            let codec = self.codec;
+            let connection = self.output_connection.clone();
+            let child = child.clone();
+            std::thread::spawn(move || forward_output(connection, codec, reader, child));
+        }
+
+        self.shell = Some(Shell {
+            master: pair.master,
+            writer,
+            child,
+        });
+        Ok(())
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        self.output_connection.lock().unwrap().take();
+        if let Some(shell) = &self.shell {
+            // Killing a shell that already exited fails, which is fine
+            let _ = shell.child.lock().unwrap().kill();
+        }
+    }
+}
+
+/// Sends the shell's output to the remote until the
+/// shell exits, and then tells the remote that it exited.
+fn forward_output(
+    connection: Arc<Mutex<Option<PeerConnection>>>,
+    codec: Codec,
+    mut reader: Box<dyn Read + Send>,
+    child: Arc<Mutex<Box<dyn Child + Send + Sync>>>,
+) {
+    let send = |packet: &TerminalPacket| match *connection.lock().unwrap() {
+        Some(connection) => send_terminal(connection, codec, packet).is_ok(),
+        None => false,
+    };
+
+    let mut buffer = vec![0; MAX_TERMINAL_DATA_SIZE];
+    loop {
+        // Reading fails instead of returning 0 on some
+        // platforms once the shell exits
+        match reader.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => {
+                if !send(&TerminalPacket::Data(buffer[..read].to_vec())) {
+                    return;
+                }
+            }
+        }
+    }
+
+    // The child is polled rather than waited on so that
+    // it can still be killed if it outlives its output
+    let success = loop {
+        match child.lock().unwrap().try_wait() {
+            Ok(Some(status)) => break status.success(),
+            Ok(None) => {}
+            Err(_) => break false,
+        }
+        if connection.lock().unwrap().is_none() {
+            return;
+        }
+        std::thread::sleep(EXIT_POLL_INTERVAL);
+    };
+    send(&TerminalPacket::Exit { success, error: None });
+}
+
+fn pty_size(cols: u16, rows: u16) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+/// Sends a terminal packet to the remote, blocking
+/// while the terminal channel is backed up.
+fn send_terminal(connection: PeerConnection, codec: Codec, packet: &TerminalPacket) -> Result<()> {
+    let bytes = serialize_packet(&codec, packet)?;
+    let sent = unsafe { pion::hammer_rtp2rtc_send_terminal(connection, bytes.as_ptr() as *const c_void, bytes.len()) };
+    if sent == 0 {
+        Err(anyhow!("Terminal channel isn't open"))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+//! Virtual touchscreens and pen tablets, which let
+//! remotes use multi-touch gestures and pen pressure
+//! instead of having them turned into mouse clicks.
+//!
+//! Like the mouse, these cover the whole screen rather
+//! than just the monitor being streamed.
+
+pub use self::platform::{PenTablet, TouchScreen};
+
+/// The most fingers that can touch the screen at
+/// once. Fingers past this are ignored.
+pub const MAX_TOUCH_POINTS: usize = 10;
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use anyhow::Result;
+    use hammeregg_core::ContactState;
+    use nix::libc::input_id;
+
+    use super::MAX_TOUCH_POINTS;
+    use crate::uinput::{
+        AbsAxis, Capabilities, UinputDevice, ABS_X, ABS_Y, BTN_TOUCH, BUS_VIRTUAL, EV_ABS, EV_KEY, INPUT_PROP_DIRECT,
+    };
+
+    const BTN_TOOL_PEN: u16 = 0x140;
+
+    const ABS_PRESSURE: u16 = 0x18;
+    const ABS_TILT_X: u16 = 0x1a;
+    const ABS_TILT_Y: u16 = 0x1b;
+    const ABS_MT_SLOT: u16 = 0x2f;
+    const ABS_MT_POSITION_X: u16 = 0x35;
+    const ABS_MT_POSITION_Y: u16 = 0x36;
+    const ABS_MT_TRACKING_ID: u16 = 0x39;
+    const ABS_MT_PRESSURE: u16 = 0x3a;
+
+    const POSITION_MAX: i32 = 32767;
+    const PRESSURE_MAX: i32 = 4095;
+    /// Makes the screen about 33cm wide, which
+    /// is only used to scale gestures.
+    const POSITION_RESOLUTION: i32 = 100;
+    /// Tilt is in degrees, and its
+    /// resolution is in units per radian.
+    const TILT_RESOLUTION: i32 = 57;
+
+    const fn axis(code: u16, min: i32, max: i32, resolution: i32) -> AbsAxis {
+        AbsAxis {
+            code,
+            min,
+            max,
+            fuzz: 0,
+            flat: 0,
+            resolution,
+        }
+    }
+
+    /// Converts a normalized coordinate to
+    /// a position on a virtual device.
+    fn to_position(value: f32) -> i32 {
+        ((value * POSITION_MAX as f32).round() as i32).clamp(0, POSITION_MAX)
+    }
+
+    fn to_pressure(value: f32) -> i32 {
+        ((value * PRESSURE_MAX as f32).round() as i32).clamp(0, PRESSURE_MAX)
+    }
+
+    /// A virtual multi-touch screen.
+    pub struct TouchScreen {
+        device: UinputDevice,
+        /// The remote's id for the finger in each slot.
+        slots: [Option<u32>; MAX_TOUCH_POINTS],
+        /// The tracking id to give the next finger.
+        /// Every touch needs a new one, even if it
+        /// reuses a slot.
+        next_tracking_id: i32,
+    }
+
+    impl TouchScreen {
+        pub fn create() -> Result<Self> {
+            let id = input_id {
+                bustype: BUS_VIRTUAL,
+                vendor: 0,
+                product: 0,
+                version: 1,
+            };
+            let axes = [
+                axis(ABS_X, 0, POSITION_MAX, POSITION_RESOLUTION),
+                axis(ABS_Y, 0, POSITION_MAX, POSITION_RESOLUTION),
+                axis(ABS_MT_SLOT, 0, MAX_TOUCH_POINTS as i32 - 1, 0),
+                axis(ABS_MT_TRACKING_ID, 0, u16::MAX as i32, 0),
+                axis(ABS_MT_POSITION_X, 0, POSITION_MAX, POSITION_RESOLUTION),
+                axis(ABS_MT_POSITION_Y, 0, POSITION_MAX, POSITION_RESOLUTION),
+                axis(ABS_MT_PRESSURE, 0, PRESSURE_MAX, 0),
+            ];
+            let capabilities = Capabilities {
+                props: &[INPUT_PROP_DIRECT],
+                keys: &[BTN_TOUCH],
+                axes: &axes,
+                ..Default::default()
+            };
+            let device = UinputDevice::create("Hammeregg Virtual Touchscreen", id, capabilities)?;
+            Ok(Self {
+                device,
+                slots: [None; MAX_TOUCH_POINTS],
+                next_tracking_id: 0,
+            })
+        }
+
+        /// Puts down, moves or lifts a finger.
+        pub fn touch(&mut self, id: u32, x: f32, y: f32, pressure: f32, state: ContactState) -> Result<()> {
+            let slot = match self.slots.iter().position(|&finger| finger == Some(id)) {
+                Some(slot) => slot,
+                None if state == ContactState::Contact => match self.slots.iter().position(Option::is_none) {
+                    Some(slot) => slot,
+                    None => return Ok(()),
+                },
+                // Lifting a finger that isn't down
+                None => return Ok(()),
+            };
+
+            self.device.emit(EV_ABS, ABS_MT_SLOT, slot as i32)?;
+            if state == ContactState::Contact {
+                if self.slots[slot].is_none() {
+                    self.slots[slot] = Some(id);
+                    self.device.emit(EV_ABS, ABS_MT_TRACKING_ID, self.next_tracking_id)?;
+                    self.next_tracking_id = (self.next_tracking_id + 1) % (u16::MAX as i32 + 1);
+                }
+                let (x, y) = (to_position(x), to_position(y));
+                self.device.emit(EV_ABS, ABS_MT_POSITION_X, x)?;
+                self.device.emit(EV_ABS, ABS_MT_POSITION_Y, y)?;
+                self.device.emit(EV_ABS, ABS_MT_PRESSURE, to_pressure(pressure))?;
+                // Single touch events follow the first finger
+                if self.slots[..slot].iter().all(Option::is_none) {
+                    self.device.emit(EV_ABS, ABS_X, x)?;
+                    self.device.emit(EV_ABS, ABS_Y, y)?;
+                }
+            } else {
+                self.slots[slot] = None;
+                self.device.emit(EV_ABS, ABS_MT_TRACKING_ID, -1)?;
+            }
+            let touching = self.slots.iter().any(Option::is_some);
+            self.device.emit(EV_KEY, BTN_TOUCH, touching as i32)?;
+            self.device.sync()
+        }
+
+        /// Lifts every finger.
+        pub fn lift_all(&mut self) -> Result<()> {
+            for slot in 0..MAX_TOUCH_POINTS {
+                if self.slots[slot].take().is_some() {
+                    self.device.emit(EV_ABS, ABS_MT_SLOT, slot as i32)?;
+                    self.device.emit(EV_ABS, ABS_MT_TRACKING_ID, -1)?;
+                }
+            }
+            self.device.emit(EV_KEY, BTN_TOUCH, 0)?;
+            self.device.sync()
+        }
+    }
+
+    /// A virtual pen tablet that covers the screen.
+    pub struct PenTablet {
+        device: UinputDevice,
+    }
+
+    impl PenTablet {
+        pub fn create() -> Result<Self> {
+            let id = input_id {
+                bustype: BUS_VIRTUAL,
+                vendor: 0,
+                product: 0,
+                version: 1,
+            };
+            let axes = [
+                axis(ABS_X, 0, POSITION_MAX, POSITION_RESOLUTION),
+                axis(ABS_Y, 0, POSITION_MAX, POSITION_RESOLUTION),
+                axis(ABS_PRESSURE, 0, PRESSURE_MAX, 0),
+                axis(ABS_TILT_X, -90, 90, TILT_RESOLUTION),
+                axis(ABS_TILT_Y, -90, 90, TILT_RESOLUTION),
+            ];
+            let capabilities = Capabilities {
+                props: &[INPUT_PROP_DIRECT],
+                keys: &[BTN_TOOL_PEN, BTN_TOUCH],
+                axes: &axes,
+                ..Default::default()
+            };
+            let device = UinputDevice::create("Hammeregg Virtual Pen", id, capabilities)?;
+            Ok(Self { device })
+        }
+
+        /// Moves the pen, and puts it down or lifts it.
+        pub fn pen(
+            &mut self,
+            x: f32,
+            y: f32,
+            pressure: f32,
+            tilt_x: f32,
+            tilt_y: f32,
+            state: ContactState,
+        ) -> Result<()> {
+            if state == ContactState::Lifted {
+                return self.lift();
+            }
+            let touching = state == ContactState::Contact;
+            self.device.emit(EV_KEY, BTN_TOOL_PEN, 1)?;
+            self.device.emit(EV_KEY, BTN_TOUCH, touching as i32)?;
+            self.device.emit(EV_ABS, ABS_X, to_position(x))?;
+            self.device.emit(EV_ABS, ABS_Y, to_position(y))?;
+            let pressure = if touching { to_pressure(pressure) } else { 0 };
+            self.device.emit(EV_ABS, ABS_PRESSURE, pressure)?;
+            self.device.emit(EV_ABS, ABS_TILT_X, tilt_x.round() as i32)?;
+            self.device.emit(EV_ABS, ABS_TILT_Y, tilt_y.round() as i32)?;
+            self.device.sync()
+        }
+
+        /// Moves the pen away from the screen.
+        pub fn lift(&mut self) -> Result<()> {
+            self.device.emit(EV_ABS, ABS_PRESSURE, 0)?;
+            self.device.emit(EV_KEY, BTN_TOUCH, 0)?;
+            self.device.emit(EV_KEY, BTN_TOOL_PEN, 0)?;
+            self.device.sync()
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use anyhow::{anyhow, Result};
+    use hammeregg_core::ContactState;
+
+    /// Touchscreens are only supported on Linux for now.
+    pub struct TouchScreen;
+
+    impl TouchScreen {
+        pub fn create() -> Result<Self> {
+            Err(anyhow!("Touch input is only supported on Linux"))
+        }
+
+        pub fn touch(&mut self, _id: u32, _x: f32, _y: f32, _pressure: f32, _state: ContactState) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn lift_all(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Pen tablets are only supported on Linux for now.
+    pub struct PenTablet;
+
+    impl PenTablet {
+        pub fn create() -> Result<Self> {
+            Err(anyhow!("Pen input is only supported on Linux"))
+        }
+
+        pub fn pen(
+            &mut self,
+            _x: f32,
+            _y: f32,
+            _pressure: f32,
+            _tilt_x: f32,
+            _tilt_y: f32,
+            _state: ContactState,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn lift(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+}
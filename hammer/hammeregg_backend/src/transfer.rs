@@ -0,0 +1,551 @@
+//! File transfers between the home computer's
+//! shared folders and the remote.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{File, Metadata, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, Result};
+use hammeregg_core::limits::{validate_file_name, MAX_FILE_CHUNK_SIZE, MAX_LISTING_ENTRIES};
+use hammeregg_core::transfer::verify_chunk;
+use hammeregg_core::{serialize_packet, Codec, ErrorCode, ErrorMsg, FileEntry, FileTransferPacket};
+
+use crate::pion;
+use crate::pion::PeerConnection;
+
+/// Extension added to files that are still being
+/// uploaded, so that half-uploaded files are never
+/// mistaken for complete ones.
+const PART_EXTENSION: &str = "hammeregg-part";
+
+/// An upload from the remote that is being
+/// written to a shared folder.
+struct Upload {
+    file: File,
+    part_path: PathBuf,
+    path: PathBuf,
+    size: u64,
+    written: u64,
+}
+
+/// A download to the remote that is being
+/// streamed on its own thread.
+struct Download {
+    cancelled: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// Gets the name that the remote uses to refer
+/// to a shared folder, which is the last component
+/// of its path. Returns `None` if the folder has
+/// no name that can be sent to the remote.
+pub fn shared_folder_name(folder: &Path) -> Option<&str> {
+    folder
+        .file_name()?
+        .to_str()
+        .filter(|name| validate_file_name(name).is_ok())
+}
+
+/// Handles file transfer packets from a remote,
+/// reading and writing files in the shared folders.
+/// Unfinished downloads are cancelled when this
+/// is dropped.
+pub struct FileTransfers {
+    connection: PeerConnection,
+    codec: Codec,
+    /// The folders that the remote can browse. File
+    /// transfers are disabled if there aren't any.
+    shared_folders: Vec<PathBuf>,
+    uploads: HashMap<u32, Upload>,
+    downloads: HashMap<u32, Download>,
+    /// The threads of downloads that were stopped early.
+    /// They aren't joined until this is dropped, since
+    /// they can be waiting for the files channel to drain.
+    stopped_downloads: Vec<JoinHandle<()>>,
+}
+
+impl FileTransfers {
+    pub fn new(connection: PeerConnection, codec: Codec, shared_folders: Vec<PathBuf>) -> Self {
+        Self {
+            connection,
+            codec,
+            shared_folders,
+            uploads: HashMap::new(),
+            downloads: HashMap::new(),
+            stopped_downloads: Vec::new(),
+        }
+    }
+
+    /// Handles a file transfer packet from the remote.
+    /// If the packet's transfer fails, the transfer is
+    /// stopped and the remote is sent a `Cancel` packet.
+    pub fn handle_packet(&mut self, packet: FileTransferPacket) {
+        let id = packet.id();
+        let result = match packet {
+            FileTransferPacket::Download { id, path, offset } => self.start_download(id, &path, offset),
+            FileTransferPacket::Upload { id, path, size } => self.start_upload(id, &path, size),
+            FileTransferPacket::Chunk {
+                id,
+                offset,
+                data,
+                sha256,
+            } => self.write_chunk(id, offset, &data, &sha256),
+            FileTransferPacket::Complete { id } => {
+                self.stop_download(id);
+                Ok(())
+            }
+            FileTransferPacket::Cancel { id, error } => {
+                if let Some(error) = error {
+                    eprintln!("Remote cancelled file transfer {}: {}", id, error);
+                }
+                self.stop_download(id);
+                // Partial uploads are kept so that they can be resumed
+                self.uploads.remove(&id);
+                Ok(())
+            }
+            FileTransferPacket::List { id, path } => self.list(id, &path),
+            FileTransferPacket::Stat { id, path } => self.stat(id, &path),
+            FileTransferPacket::Accepted { .. }
+            | FileTransferPacket::Listing { .. }
+            | FileTransferPacket::Entry { .. } => Err(ErrorMsg::with_detail(
+                ErrorCode::Other,
+                "Only the home computer can answer file transfer requests",
+            )),
+        };
+
+        if let Err(error) = result {
+            eprintln!("File transfer {} failed: {}", id, error);
+            self.stop_download(id);
+            self.uploads.remove(&id);
+            let packet = FileTransferPacket::Cancel { id, error: Some(error) };
+            if let Err(err) = send_files(self.connection, self.codec, &packet) {
+                eprintln!("Couldn't cancel file transfer: {:?}", err);
+            }
+        }
+    }
+
+    /// Resolves a transfer path against the shared folders.
+    /// Transfer paths are validated before they get here,
+    /// so they can't contain `..` or absolute components,
+    /// but they can still lead out of a shared folder
+    /// through a symlink.
+    fn resolve(&self, path: &str) -> Result<PathBuf, ErrorMsg> {
+        self.resolve_in_folder(path).map(|(_, path)| path)
+    }
+
+    /// Resolves a transfer path that must lead to a file or
+    /// folder inside a shared folder, rather than to the
+    /// shared folder itself. Returns the canonical path of
+    /// the shared folder along with the resolved path.
+    fn resolve_inside(&self, path: &str) -> Result<(PathBuf, PathBuf), ErrorMsg> {
+        if !path.contains('/') {
+            return Err(ErrorMsg::with_detail(
+                ErrorCode::Unauthorized,
+                "Path must be inside a shared folder",
+            ));
+        }
+        self.resolve_in_folder(path)
+    }
+
+    fn resolve_in_folder(&self, path: &str) -> Result<(PathBuf, PathBuf), ErrorMsg> {
+        if self.shared_folders.is_empty() {
+            return Err(ErrorMsg::with_detail(
+                ErrorCode::Unauthorized,
+                "File transfers are disabled on this computer",
+            ));
+        }
+
+        let mut components = path.split('/');
+        let name = components.next().unwrap_or_default();
+        let folder = self
+            .shared_folders
+            .iter()
+            .find(|folder| shared_folder_name(folder) == Some(name))
+            .ok_or_else(|| ErrorMsg::new(ErrorCode::FileNotFound))?;
+        // These are rejected by validation too, but an empty
+        // component would resolve to the shared folder itself
+        let components = components.collect::<Vec<_>>();
+        if components.iter().any(|component| matches!(*component, "" | "." | "..")) {
+            return Err(ErrorMsg::with_detail(
+                ErrorCode::Unauthorized,
+                "Path cannot contain empty, . or .. components",
+            ));
+        }
+        let path = components
+            .into_iter()
+            .fold(folder.clone(), |path, component| path.join(component));
+
+        // Paths that don't exist yet are checked
+        // against their closest existing ancestor
+        let folder = folder.canonicalize().map_err(io_error)?;
+        let existing = path
+            .ancestors()
+            .find_map(|ancestor| ancestor.canonicalize().ok())
+            .ok_or_else(|| ErrorMsg::new(ErrorCode::FileNotFound))?;
+        if existing.starts_with(&folder) {
+            Ok((folder, path))
+        } else {
+            Err(ErrorMsg::with_detail(
+                ErrorCode::Unauthorized,
+                "Path leads out of the shared folder",
+            ))
+        }
+    }
+
+    /// Sends a listing of a folder, or of the shared
+    /// folders themselves if `path` is empty.
+    fn list(&mut self, id: u32, path: &str) -> Result<(), ErrorMsg> {
+        let mut entries = if path.is_empty() {
+            self.shared_folders
+                .iter()
+                .filter_map(|folder| {
+                    let metadata = folder.metadata().ok()?;
+                    Some(file_entry(shared_folder_name(folder)?, &metadata))
+                })
+                .collect::<Vec<_>>()
+        } else {
+            let path = self.resolve(path)?;
+            std::fs::read_dir(&path)
+                .map_err(io_error)?
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    // Skip files that the remote couldn't ask for
+                    // by name and uploads that haven't finished yet
+                    let name = entry.file_name().into_string().ok()?;
+                    if validate_file_name(&name).is_err()
+                        || Path::new(&name).extension() == Some(OsStr::new(PART_EXTENSION))
+                    {
+                        return None;
+                    }
+                    // Follows symlinks, unlike DirEntry::metadata
+                    let metadata = entry.path().metadata().ok()?;
+                    Some(file_entry(&name, &metadata))
+                })
+                .collect::<Vec<_>>()
+        };
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+        let mut pages = entries
+            .chunks(MAX_LISTING_ENTRIES)
+            .map(<[_]>::to_vec)
+            .collect::<Vec<_>>();
+        // Empty folders still get a single listing packet
+        if pages.is_empty() {
+            pages.push(Vec::new());
+        }
+        let last = pages.len() - 1;
+        for (i, entries) in pages.into_iter().enumerate() {
+            let packet = FileTransferPacket::Listing {
+                id,
+                entries,
+                done: i == last,
+            };
+            send_files(self.connection, self.codec, &packet)
+                .map_err(|err| ErrorMsg::with_detail(ErrorCode::Other, err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Sends the metadata of a single file or folder.
+    fn stat(&mut self, id: u32, path: &str) -> Result<(), ErrorMsg> {
+        let name = path.rsplit('/').next().unwrap_or_default();
+        let (_, path) = self.resolve_inside(path)?;
+        let metadata = path.metadata().map_err(io_error)?;
+        let packet = FileTransferPacket::Entry {
+            id,
+            entry: file_entry(name, &metadata),
+        };
+        send_files(self.connection, self.codec, &packet)
+            .map_err(|err| ErrorMsg::with_detail(ErrorCode::Other, err.to_string()))
+    }
+
+    fn start_download(&mut self, id: u32, path: &str, offset: u64) -> Result<(), ErrorMsg> {
+        self.check_unused(id)?;
+        let (_, path) = self.resolve_inside(path)?;
+        let mut file = File::open(&path).map_err(io_error)?;
+        let metadata = file.metadata().map_err(io_error)?;
+        if !metadata.is_file() {
+            return Err(ErrorMsg::new(ErrorCode::FileNotFound));
+        }
+        let size = metadata.len();
+        if offset > size {
+            return Err(ErrorMsg::with_detail(
+                ErrorCode::Other,
+                "Download can't start past the end of the file",
+            ));
+        }
+        file.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+
+        send_files(
+            self.connection,
+            self.codec,
+            &FileTransferPacket::Accepted { id, size, offset },
+        )
+        .map_err(|err| ErrorMsg::with_detail(ErrorCode::Other, err.to_string()))?;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let connection = self.connection;
+            let codec = self.codec;
+            let cancelled = cancelled.clone();
+            std::thread::spawn(move || stream_download(connection, codec, id, file, offset, cancelled))
+        };
+        self.downloads.insert(id, Download { cancelled, thread });
+        Ok(())
+    }
+
+    fn start_upload(&mut self, id: u32, path: &str, size: u64) -> Result<(), ErrorMsg> {
+        self.check_unused(id)?;
+        let (folder, path) = self.resolve_inside(path)?;
+        let name = path
+            .file_name()
+            .ok_or_else(|| ErrorMsg::with_detail(ErrorCode::Other, "Upload path has no file name"))?;
+        let mut part_name = name.to_os_string();
+        part_name.push(".");
+        part_name.push(PART_EXTENSION);
+        let part_path = path.with_file_name(part_name);
+
+        let parent = part_path
+            .parent()
+            .ok_or_else(|| ErrorMsg::with_detail(ErrorCode::Other, "Upload path has no folder"))?;
+        std::fs::create_dir_all(parent).map_err(io_error)?;
+        // Make sure the part file really ends up in the shared folder
+        if !parent.canonicalize().map_err(io_error)?.starts_with(&folder) {
+            return Err(ErrorMsg::with_detail(
+                ErrorCode::Unauthorized,
+                "Path leads out of the shared folder",
+            ));
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&part_path)
+            .map_err(io_error)?;
+
+        // Resume from whatever an earlier attempt left behind,
+        // unless it's somehow bigger than the whole file
+        let mut written = file.metadata().map_err(io_error)?.len();
+        if written > size {
+            file.set_len(0).map_err(io_error)?;
+            written = 0;
+        }
+
+        send_files(
+            self.connection,
+            self.codec,
+            &FileTransferPacket::Accepted {
+                id,
+                size,
+                offset: written,
+            },
+        )
+        .map_err(|err| ErrorMsg::with_detail(ErrorCode::Other, err.to_string()))?;
+
+        self.uploads.insert(
+            id,
+            Upload {
+                file,
+                part_path,
+                path,
+                size,
+                written,
+            },
+        );
+        // Empty files don't get any chunks
+        if written == size {
+            self.finish_upload(id)?;
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, id: u32, offset: u64, data: &[u8], sha256: &[u8]) -> Result<(), ErrorMsg> {
+        let upload = self.uploads.get_mut(&id).ok_or_else(|| {
+            ErrorMsg::with_detail(ErrorCode::Other, "Got a chunk for a transfer that isn't an upload")
+        })?;
+        if !verify_chunk(data, sha256) {
+            return Err(ErrorMsg::new(ErrorCode::ChecksumMismatch));
+        }
+        if offset != upload.written || upload.written + data.len() as u64 > upload.size {
+            return Err(ErrorMsg::with_detail(ErrorCode::Other, "Got a chunk out of order"));
+        }
+
+        upload.file.write_all(data).map_err(io_error)?;
+        upload.written += data.len() as u64;
+        if upload.written == upload.size {
+            self.finish_upload(id)?;
+        }
+        Ok(())
+    }
+
+    /// Moves a fully written upload into place
+    /// and tells the remote that it's done.
+    fn finish_upload(&mut self, id: u32) -> Result<(), ErrorMsg> {
+        let upload = self.uploads.remove(&id).unwrap();
+        upload.file.sync_all().map_err(io_error)?;
+        std::fs::rename(&upload.part_path, &upload.path).map_err(io_error)?;
+        send_files(self.connection, self.codec, &FileTransferPacket::Complete { id })
+            .map_err(|err| ErrorMsg::with_detail(ErrorCode::Other, err.to_string()))?;
+        Ok(())
+    }
+
+    fn check_unused(&self, id: u32) -> Result<(), ErrorMsg> {
+        if self.uploads.contains_key(&id) || self.downloads.contains_key(&id) {
+            Err(ErrorMsg::with_detail(ErrorCode::Other, "Transfer id is already in use"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Tells a download's thread to stop, if it's still
+    /// running, without waiting for it.
+    fn stop_download(&mut self, id: u32) {
+        if let Some(download) = self.downloads.remove(&id) {
+            download.cancelled.store(true, Ordering::SeqCst);
+            self.stopped_downloads.push(download.thread);
+        }
+    }
+}
+
+impl Drop for FileTransfers {
+    fn drop(&mut self) {
+        let ids = self.downloads.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            self.stop_download(id);
+        }
+        // The files channel is closed by now, so
+        // none of these are waiting to send
+        for thread in self.stopped_downloads.drain(..) {
+            if thread.join().is_err() {
+                eprintln!("Download thread panicked!");
+            }
+        }
+    }
+}
+
+/// Sends every chunk of a file after `offset` to the
+/// remote, stopping early if `cancelled` is set or the
+/// files channel closes.
+fn stream_download(
+    connection: PeerConnection,
+    codec: Codec,
+    id: u32,
+    mut file: File,
+    mut offset: u64,
+    cancelled: Arc<AtomicBool>,
+) {
+    let mut buffer = vec![0; MAX_FILE_CHUNK_SIZE];
+    while !cancelled.load(Ordering::SeqCst) {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => return,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => {
+                eprintln!("Couldn't read file for download {}: {}", id, err);
+                let error = Some(io_error(err));
+                if let Err(err) = send_files(connection, codec, &FileTransferPacket::Cancel { id, error }) {
+                    eprintln!("Couldn't cancel file transfer: {:?}", err);
+                }
+                return;
+            }
+        };
+
+        let packet = FileTransferPacket::chunk(id, offset, buffer[..read].to_vec());
+        match send_files(connection, codec, &packet) {
+            Ok(()) => offset += read as u64,
+            Err(err) => {
+                eprintln!("Couldn't send file chunk: {:?}", err);
+                return;
+            }
+        }
+    }
+}
+
+/// Describes a file or folder to the remote.
+fn file_entry(name: &str, metadata: &Metadata) -> FileEntry {
+    FileEntry {
+        name: name.to_string(),
+        is_dir: metadata.is_dir(),
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        modified: metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_millis() as u64),
+    }
+}
+
+/// Converts an IO error into an error that
+/// can be sent to the remote.
+fn io_error(err: std::io::Error) -> ErrorMsg {
+    match err.kind() {
+        ErrorKind::NotFound => ErrorMsg::new(ErrorCode::FileNotFound),
+        ErrorKind::PermissionDenied => ErrorMsg::new(ErrorCode::Unauthorized),
+        _ => ErrorMsg::with_detail(ErrorCode::Other, err.to_string()),
+    }
+}
+
+/// Sends a file transfer packet to the remote, blocking
+/// while the files channel is backed up.
+fn send_files(connection: PeerConnection, codec: Codec, packet: &FileTransferPacket) -> Result<()> {
+    let bytes = serialize_packet(&codec, packet)?;
+    let sent = unsafe { pion::hammer_rtp2rtc_send_files(connection, bytes.as_ptr() as *const c_void, bytes.len()) };
+    if sent == 0 {
+        Err(anyhow!("Files channel isn't open"))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes a shared folder called `eggs` in a
+    /// fresh temporary folder.
+    fn shared_folder(test: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("hammeregg-transfer-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let folder = root.join("eggs");
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn transfers(folder: &Path) -> FileTransfers {
+        // These tests fail before anything is sent, so the
+        // connection is never passed to Pion
+        FileTransfers::new(PeerConnection::null(), Codec::default(), vec![folder.to_path_buf()])
+    }
+
+    #[test]
+    fn uploads_to_the_shared_folder_itself_are_rejected() {
+        let folder = shared_folder("upload-root");
+        let mut transfers = transfers(&folder);
+        for path in ["eggs", "eggs/"] {
+            let err = transfers.start_upload(1, path, 4).unwrap_err();
+            assert_eq!(err.code, ErrorCode::Unauthorized, "{}", path);
+        }
+        let parent = folder.parent().unwrap();
+        assert!(!parent.join("eggs.hammeregg-part").exists());
+        assert_eq!(std::fs::read_dir(parent).unwrap().count(), 1);
+        std::fs::remove_dir_all(parent).unwrap();
+    }
+
+    #[test]
+    fn only_listing_can_reach_the_shared_folder_itself() {
+        let folder = shared_folder("resolve-root");
+        let transfers = transfers(&folder);
+        assert_eq!(transfers.resolve("eggs").unwrap(), folder);
+        assert!(transfers.resolve_inside("eggs").is_err());
+        assert!(transfers.resolve_inside("eggs/").is_err());
+        assert!(transfers.resolve("eggs/").is_err());
+        assert!(transfers.resolve_inside("eggs/egg.txt").is_ok());
+        std::fs::remove_dir_all(folder.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,418 @@
+//! TCP tunnels, which forward connections from the
+//! remote to hosts that the home computer can reach.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::os::raw::c_void;
+use std::str::FromStr;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Error, Result};
+use hammeregg_core::limits::{MAX_TUNNEL_DATA_SIZE, MAX_TUNNEL_HOST_LEN};
+use hammeregg_core::{serialize_packet, Codec, ErrorCode, ErrorMsg, TunnelPacket};
+
+use crate::pion;
+use crate::pion::PeerConnection;
+
+/// How long to wait for a host to accept
+/// a connection before giving up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a write to a host can block
+/// before giving up on the connection.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many connections a remote can have open,
+/// or be connecting, at once. Every connection
+/// has two threads of its own.
+const MAX_TUNNELS: usize = 16;
+
+/// How many packets of data from the remote can be
+/// waiting to be written to a host. Hosts that fall
+/// this far behind are disconnected, since waiting
+/// for them would hold up every other tunnel.
+const WRITE_QUEUE_SIZE: usize = 64;
+
+/// A host and port that remotes are allowed to tunnel
+/// to. Hosts are matched as they're written, ignoring
+/// case, so allowing `localhost` allows `LocalHost` but
+/// not `127.0.0.1`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TunnelTarget {
+    pub host: String,
+    pub port: u16,
+}
+
+impl TunnelTarget {
+    fn allows(&self, host: &str, port: u16) -> bool {
+        self.port == port && self.host.eq_ignore_ascii_case(host)
+    }
+}
+
+impl FromStr for TunnelTarget {
+    type Err = Error;
+
+    /// Parses a `host:port` pair. IPv6 addresses
+    /// must be wrapped in square brackets.
+    fn from_str(target: &str) -> Result<Self> {
+        let (host, port) = target
+            .rsplit_once(':')
+            .ok_or_else(|| anyhow!("{} is missing a port", target))?;
+        let host = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+        if host.is_empty() || host.len() > MAX_TUNNEL_HOST_LEN {
+            return Err(anyhow!("{} doesn't have a valid host", target));
+        }
+        match port.parse::<u16>() {
+            Ok(port) if port != 0 => Ok(Self {
+                host: host.to_string(),
+                port,
+            }),
+            _ => Err(anyhow!("{} doesn't have a valid port", target)),
+        }
+    }
+}
+
+impl Display for TunnelTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Parses a comma separated list of `host:port`
+/// pairs, ignoring blank entries.
+pub fn parse_allowlist(allowlist: &str) -> Result<Vec<TunnelTarget>> {
+    allowlist
+        .split(',')
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+        .map(TunnelTarget::from_str)
+        .collect()
+}
+
+/// A connection to a host.
+struct Tunnel {
+    /// Queues data for the thread that writes to the
+    /// host. Once this is dropped, that thread writes
+    /// what's left in the queue and then disconnects.
+    writes: SyncSender<Vec<u8>>,
+    /// `None` while the home computer is still connecting.
+    stream: Option<Arc<TcpStream>>,
+}
+
+/// Open connections by id.
+type Streams = Arc<Mutex<HashMap<u32, Tunnel>>>;
+
+/// Handles tunnel packets from a remote. Every open
+/// connection is closed when this is dropped, and
+/// nothing is sent to the remote after that.
+pub struct Tunnels {
+    connection: PeerConnection,
+    codec: Codec,
+    /// The hosts that the remote can connect to.
+    /// Tunnels are disabled if there aren't any.
+    allowlist: Vec<TunnelTarget>,
+    streams: Streams,
+    /// The connection used by the threads that forward
+    /// data from each host, which is taken once the
+    /// connection can't be used anymore. Those threads
+    /// hold this lock while sending, so that they never
+    /// send after the connection is freed.
+    output_connection: Arc<Mutex<Option<PeerConnection>>>,
+}
+
+impl Tunnels {
+    pub fn new(connection: PeerConnection, codec: Codec, allowlist: Vec<TunnelTarget>) -> Self {
+        Self {
+            connection,
+            codec,
+            allowlist,
+            streams: Arc::new(Mutex::new(HashMap::new())),
+            output_connection: Arc::new(Mutex::new(Some(connection))),
+        }
+    }
+
+    /// Handles a tunnel packet from the remote.
+    pub fn handle_packet(&mut self, packet: TunnelPacket) {
+        match packet {
+            TunnelPacket::Connect { id, host, port } => {
+                if let Err(error) = self.connect(id, host, port) {
+                    self.close(id, error);
+                }
+            }
+            TunnelPacket::Data { id, data } => {
+                // This runs on the same thread as every other
+                // tunnel, so it can't wait for a slow host
+                let backed_up = {
+                    let mut streams = self.streams.lock().unwrap();
+                    match streams.get(&id).map(|tunnel| tunnel.writes.try_send(data)) {
+                        Some(Err(TrySendError::Full(_))) => streams.remove(&id),
+                        // The writing thread has already closed the connection
+                        // if it's disconnected
+                        _ => None,
+                    }
+                };
+                if let Some(tunnel) = backed_up {
+                    if let Some(stream) = tunnel.stream {
+                        let _ = stream.shutdown(Shutdown::Both);
+                    }
+                    self.close(
+                        id,
+                        ErrorMsg::with_detail(ErrorCode::Other, "Host isn't keeping up with the tunnel"),
+                    );
+                }
+            }
+            TunnelPacket::Close { id, .. } => {
+                // The writing thread disconnects once it has
+                // written everything the remote sent before this
+                self.streams.lock().unwrap().remove(&id);
+            }
+            TunnelPacket::Connected { .. } => eprintln!("Only the home computer can accept tunnels"),
+        }
+    }
+
+    /// Starts connecting to `host:port` on its own thread,
+    /// if that host is in the allowlist.
+    fn connect(&mut self, id: u32, host: String, port: u16) -> Result<(), ErrorMsg> {
+        if !self.allowlist.iter().any(|target| target.allows(&host, port)) {
+            let target = TunnelTarget { host, port };
+            return Err(ErrorMsg::with_detail(
+                ErrorCode::Unauthorized,
+                format!("{} isn't allowed by this computer", target),
+            ));
+        }
+        let (writes, writes_rx) = sync_channel(WRITE_QUEUE_SIZE);
+        {
+            let mut streams = self.streams.lock().unwrap();
+            if streams.contains_key(&id) {
+                return Err(ErrorMsg::with_detail(ErrorCode::Other, "Tunnel id is already in use"));
+            }
+            if streams.len() >= MAX_TUNNELS {
+                return Err(ErrorMsg::with_detail(
+                    ErrorCode::RateLimited,
+                    format!("Only {} tunnels can be open at once", MAX_TUNNELS),
+                ));
+            }
+            streams.insert(id, Tunnel { writes, stream: None });
+        }
+
+        let forwarder = Forwarder {
+            connection: self.output_connection.clone(),
+            codec: self.codec,
+            streams: self.streams.clone(),
+            id,
+        };
+        std::thread::spawn(move || forward_stream(forwarder, host, port, writes_rx));
+        Ok(())
+    }
+
+    /// Tells the remote that a connection failed.
+    fn close(&self, id: u32, error: ErrorMsg) {
+        let packet = TunnelPacket::Close { id, error: Some(error) };
+        if let Err(err) = send_tunnel(self.connection, self.codec, &packet) {
+            eprintln!("Couldn't send tunnel close: {:?}", err);
+        }
+    }
+}
+
+impl Drop for Tunnels {
+    fn drop(&mut self) {
+        self.output_connection.lock().unwrap().take();
+        for stream in self
+            .streams
+            .lock()
+            .unwrap()
+            .drain()
+            .filter_map(|(_, tunnel)| tunnel.stream)
+        {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// What the threads that forward a connection share.
+#[derive(Clone)]
+struct Forwarder {
+    connection: Arc<Mutex<Option<PeerConnection>>>,
+    codec: Codec,
+    streams: Streams,
+    id: u32,
+}
+
+impl Forwarder {
+    /// Sends a packet to the remote, returning
+    /// whether it could be sent.
+    fn send(&self, packet: &TunnelPacket) -> bool {
+        match *self.connection.lock().unwrap() {
+            Some(connection) => send_tunnel(connection, self.codec, packet).is_ok(),
+            None => false,
+        }
+    }
+
+    fn close(&self, error: Option<ErrorMsg>) {
+        // Only tell the remote if it didn't close the connection itself
+        if self.streams.lock().unwrap().remove(&self.id).is_some() {
+            self.send(&TunnelPacket::Close { id: self.id, error });
+        }
+    }
+}
+
+/// Connects to a host and sends everything it sends
+/// to the remote, until either side closes the
+/// connection. Data from the remote is written to
+/// the host by a thread of its own.
+fn forward_stream(forwarder: Forwarder, host: String, port: u16, writes: Receiver<Vec<u8>>) {
+    let id = forwarder.id;
+    let stream = match connect(&host, port).and_then(|stream| {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        Ok(stream)
+    }) {
+        Ok(stream) => Arc::new(stream),
+        Err(err) => {
+            eprintln!("Couldn't connect tunnel to {}:{}: {}", host, port, err);
+            forwarder.close(Some(ErrorMsg::with_detail(ErrorCode::Other, err.to_string())));
+            return;
+        }
+    };
+    match forwarder.streams.lock().unwrap().get_mut(&id) {
+        Some(tunnel) => tunnel.stream = Some(stream.clone()),
+        // Closed by the remote while connecting
+        None => return,
+    }
+    if !forwarder.send(&TunnelPacket::Connected { id }) {
+        return;
+    }
+    {
+        let forwarder = forwarder.clone();
+        let stream = stream.clone();
+        std::thread::spawn(move || write_stream(forwarder, stream, writes));
+    }
+
+    let mut buffer = vec![0; MAX_TUNNEL_DATA_SIZE];
+    loop {
+        match (&*stream).read(&mut buffer) {
+            Ok(0) => {
+                forwarder.close(None);
+                break;
+            }
+            Ok(read) => {
+                let data = buffer[..read].to_vec();
+                if !forwarder.send(&TunnelPacket::Data { id, data }) {
+                    break;
+                }
+            }
+            Err(err) => {
+                forwarder.close(Some(ErrorMsg::with_detail(ErrorCode::Other, err.to_string())));
+                break;
+            }
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+/// Writes data from the remote to a host, until the
+/// connection is closed or a write fails.
+fn write_stream(forwarder: Forwarder, stream: Arc<TcpStream>, writes: Receiver<Vec<u8>>) {
+    for data in writes {
+        if let Err(err) = (&*stream).write_all(&data) {
+            forwarder.close(Some(ErrorMsg::with_detail(ErrorCode::Other, err.to_string())));
+            break;
+        }
+    }
+    // The reading thread stops once its reads fail
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+/// Connects to the first address of a host that accepts.
+fn connect(host: &str, port: u16) -> std::io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Host has no addresses")))
+}
+
+/// Sends a tunnel packet to the remote, blocking
+/// while the tunnel channel is backed up.
+fn send_tunnel(connection: PeerConnection, codec: Codec, packet: &TunnelPacket) -> Result<()> {
+    let bytes = serialize_packet(&codec, packet)?;
+    let sent = unsafe { pion::hammer_rtp2rtc_send_tunnel(connection, bytes.as_ptr() as *const c_void, bytes.len()) };
+    if sent == 0 {
+        Err(anyhow!("Tunnel channel isn't open"))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(host: &str, port: u16) -> TunnelTarget {
+        TunnelTarget {
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    #[test]
+    fn targets_are_parsed() {
+        assert_eq!("localhost:22".parse::<TunnelTarget>().unwrap(), target("localhost", 22));
+        assert_eq!(
+            "nas.local:443".parse::<TunnelTarget>().unwrap(),
+            target("nas.local", 443)
+        );
+        assert_eq!("[::1]:8080".parse::<TunnelTarget>().unwrap(), target("::1", 8080));
+        assert_eq!(target("::1", 8080).to_string(), "[::1]:8080");
+    }
+
+    #[test]
+    fn invalid_targets_are_rejected() {
+        for invalid in [
+            "",
+            "localhost",
+            ":22",
+            "[]:22",
+            "localhost:",
+            "localhost:0",
+            "localhost:ssh",
+            "localhost:65536",
+        ] {
+            assert!(invalid.parse::<TunnelTarget>().is_err(), "{:?} was accepted", invalid);
+        }
+        let long_host = format!("{}:22", "a".repeat(MAX_TUNNEL_HOST_LEN + 1));
+        assert!(long_host.parse::<TunnelTarget>().is_err());
+    }
+
+    #[test]
+    fn allowlists_skip_blank_entries() {
+        assert_eq!(parse_allowlist("").unwrap(), vec![]);
+        assert_eq!(parse_allowlist(" , ").unwrap(), vec![]);
+        assert_eq!(
+            parse_allowlist(" localhost:22, ,[::1]:80,").unwrap(),
+            vec![target("localhost", 22), target("::1", 80)]
+        );
+        assert!(parse_allowlist("localhost:22,localhost").is_err());
+    }
+
+    #[test]
+    fn hosts_are_matched_ignoring_case() {
+        let target = target("NAS.local", 443);
+        assert!(target.allows("nas.LOCAL", 443));
+        assert!(!target.allows("nas.local", 80));
+        assert!(!target.allows("192.168.1.2", 443));
+    }
+}
@@ -4,10 +4,10 @@ use futures::channel::oneshot::Receiver;
 use rsa::RsaPrivateKey;
 
 use crate::key;
-use crate::net::WSS;
+use crate::net::SignallingConnection;
 use crate::ui::running::RunningScreen;
 use crate::ui::screen::Screen;
-use crate::ui::setup::SetupScreen;
+use crate::ui::setup::{SessionFields, SetupScreen};
 use crate::work::WorkThread;
 
 pub struct KeygenScreen {
@@ -17,7 +17,8 @@ pub struct KeygenScreen {
     desktop_name: String,
     signalling_server_addr: String,
     extra_ca: Option<String>,
-    wss: WSS,
+    session: SessionFields,
+    connection: SignallingConnection,
 
     error_msg: Option<String>,
     password_rx: Receiver<(RsaPrivateKey, RsaPrivateKey)>,
@@ -29,7 +30,8 @@ impl KeygenScreen {
         desktop_name: String,
         signalling_server_addr: String,
         extra_ca: Option<String>,
-        wss: WSS,
+        session: SessionFields,
+        connection: SignallingConnection,
     ) -> Self {
         let (tx, rx) = oneshot::channel();
         std::thread::spawn(move || {
@@ -41,7 +43,8 @@ impl KeygenScreen {
             desktop_name,
             signalling_server_addr,
             extra_ca,
-            wss,
+            session,
+            connection,
             error_msg: None,
             password_rx: rx,
         }
@@ -62,6 +65,7 @@ impl Screen for KeygenScreen {
                             self.desktop_name,
                             self.signalling_server_addr,
                             self.extra_ca,
+                            self.session,
                         )),
                         true,
                     )
@@ -86,7 +90,8 @@ impl Screen for KeygenScreen {
                         Box::new(RunningScreen::new(
                             self.work_thread,
                             self.desktop_name,
-                            self.wss,
+                            self.session.into_settings(),
+                            self.connection,
                             password,
                         )),
                         true,
@@ -2,14 +2,14 @@ use std::rc::Rc;
 
 use anyhow::{Context, Result};
 use eframe::egui::Ui;
+use hammeregg_core::RemotePassword;
 use rfd::FileDialog;
 use rsa::pkcs8::{ToPrivateKey, ToPublicKey};
 use rsa::RsaPrivateKey;
 use winit::event_loop::EventLoop;
 
-use crate::key::RemotePassword;
 use crate::net;
-use crate::net::WSS;
+use crate::net::{SessionSettings, SignallingConnection};
 use crate::stream::MonitorBounds;
 use crate::ui::screen::Screen;
 use crate::work::WorkThread;
@@ -25,7 +25,8 @@ impl RunningScreen {
     pub fn new(
         work_thread: WorkThread,
         desktop_name: String,
-        wss: WSS,
+        settings: SessionSettings,
+        connection: SignallingConnection,
         password: (RsaPrivateKey, RsaPrivateKey),
     ) -> Self {
         let (home_private_key, remote_private_key) = password;
@@ -49,10 +50,11 @@ impl RunningScreen {
         let home_public_key = home_private_key.to_public_key();
         let remote_public_key = remote_private_key.to_public_key();
         let join_handle = work_thread.handle().spawn(net::handle_signalling_requests(
-            wss,
+            connection,
             home_private_key,
             remote_public_key,
             bounds,
+            settings,
         ));
         work_thread.handle().spawn(async move {
             if let Err(err) = join_handle.await {
@@ -1,24 +1,80 @@
-use std::ffi::CString;
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
 
 use anyhow::Result;
-use eframe::egui::{Button, Label, TextEdit, Ui};
+use eframe::egui::{Button, Checkbox, Label, TextEdit, Ui};
 use futures::channel::oneshot::Receiver;
+use hammeregg_core::limits::validate_home_name;
 use hammeregg_core::DEFAULT_HAMMEREGG_PORT;
 
 use crate::net;
-use crate::net::WSS;
+use crate::net::{SessionSettings, SignallingConnection};
+use crate::transfer::shared_folder_name;
+use crate::tunnel::parse_allowlist;
 use crate::ui::keygen::KeygenScreen;
 use crate::ui::screen::Screen;
 use crate::work::WorkThread;
 
+#[cfg(windows)]
+const SHARED_FOLDERS_HINT: &str = "Folders that remotes can browse, download files from\nand upload files to, separated by ';'. Leave blank to\ndisable file transfers.";
+#[cfg(not(windows))]
+const SHARED_FOLDERS_HINT: &str = "Folders that remotes can browse, download files from\nand upload files to, separated by ':'. Leave blank to\ndisable file transfers.";
+const TUNNEL_ALLOWLIST_HINT: &str = "Hosts that remotes can forward TCP connections to,\nas host:port pairs separated by ','. Leave blank to\ndisable tunnels.";
+const INPUT_IDLE_TIMEOUT_HINT: &str = "Seconds that a remote can go without sending input\nbefore the keys and mouse buttons it's holding are\nreleased. Leave blank to only release them when the\nremote disconnects.";
+const INPUT_RECORDING_HINT: &str = "A file to record each session's input to, which can\nbe replayed with the replay command. This includes\neverything that remotes type, such as passwords, and\nreplaces the file each session. Leave blank to disable\nrecording.";
+
+/// The fields that control what remotes can do,
+/// as they were entered.
+#[derive(Default)]
+pub struct SessionFields {
+    shared_folders: Option<String>,
+    allow_terminal: bool,
+    tunnel_allowlist: Option<String>,
+    input_idle_timeout: Option<String>,
+    input_recording: Option<String>,
+}
+
+impl SessionFields {
+    /// Converts these fields into settings for
+    /// the signalling loop. The fields must
+    /// have already been validated.
+    pub fn into_settings(self) -> SessionSettings {
+        SessionSettings {
+            shared_folders: self
+                .shared_folders
+                .map(|folders| std::env::split_paths(&folders).collect())
+                .unwrap_or_default(),
+            terminal: self.allow_terminal,
+            tunnel_allowlist: self
+                .tunnel_allowlist
+                .and_then(|allowlist| parse_allowlist(&allowlist).ok())
+                .unwrap_or_default(),
+            input_idle_timeout: self.input_idle_timeout.and_then(|timeout| parse_idle_timeout(&timeout)),
+            input_recording: self.input_recording.map(|path| path.trim().into()),
+        }
+    }
+}
+
+/// Parses a whole number of seconds, which
+/// must be more than zero.
+fn parse_idle_timeout(timeout: &str) -> Option<Duration> {
+    match timeout.trim().parse::<u64>() {
+        Ok(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+        _ => None,
+    }
+}
+
 pub struct SetupScreen {
     work_thread: WorkThread,
     desktop_name: String,
     signalling_server_addr: String,
     extra_ca: Option<String>,
+    session: SessionFields,
     error_msg: Option<String>,
-    signalling_connection_init: Option<Receiver<Result<WSS>>>,
+    signalling_connection_init: Option<Receiver<Result<SignallingConnection>>>,
 }
 
 impl SetupScreen {
@@ -31,6 +87,7 @@ impl SetupScreen {
             desktop_name: names::Generator::default().next().unwrap(),
             signalling_server_addr: String::default(),
             extra_ca: None,
+            session: SessionFields::default(),
             error_msg: None,
             signalling_connection_init: None,
         }
@@ -43,12 +100,14 @@ impl SetupScreen {
         desktop_name: String,
         signalling_server_addr: String,
         extra_ca: Option<String>,
+        session: SessionFields,
     ) -> Self {
         Self {
             work_thread,
             desktop_name,
             signalling_server_addr,
             extra_ca,
+            session,
             error_msg: None,
             signalling_connection_init: None,
         }
@@ -75,27 +134,68 @@ impl SetupScreen {
     }
 
     /// Validates that:
-    /// - `desktop_name` is not empty and a valid CString
+    /// - `desktop_name` is a valid home name
     /// - `error_msg` is a valid [`IpAddr`]
+    /// - `shared_folders`, if set, are directories
+    ///   with distinct names
+    /// - `tunnel_allowlist`, if set, is a list of
+    ///   `host:port` pairs
+    /// - `input_idle_timeout`, if set, is a positive
+    ///   number of seconds
+    /// - `input_recording`, if set, is a file
+    ///   in a folder that exists
     /// If validation fails, this will set the `error_msg`
     /// and return false.
     ///
     /// [`IpAddr`]: std::net::ip::IpAddr
     fn validate_input(&mut self) -> bool {
         let mut valid = true;
-        let mut errors = vec![];
+        let mut errors: Vec<Cow<str>> = vec![];
 
-        if self.desktop_name.is_empty() {
-            valid = false;
-            errors.push("desktop name cannot be empty");
-        } else if CString::new(self.desktop_name.clone()).is_err() {
+        if let Err(err) = validate_home_name(&self.desktop_name) {
             valid = false;
-            errors.push("desktop name cannot contain '\\0'");
+            errors.push(err.message.unwrap_or_default());
         }
 
         if self.try_parse_signalling_server_addr().is_none() {
             valid = false;
-            errors.push("signalling server is not a valid ip:port");
+            errors.push("signalling server is not a valid ip:port".into());
+        }
+
+        if let Some(shared_folders) = &self.session.shared_folders {
+            let mut names = HashSet::new();
+            for folder in std::env::split_paths(shared_folders) {
+                if !folder.is_dir() {
+                    valid = false;
+                    errors.push(format!("shared folder {} is not a folder", folder.display()).into());
+                } else if !shared_folder_name(&folder).map_or(false, |name| names.insert(name.to_string())) {
+                    valid = false;
+                    errors.push(format!("shared folder {} needs a unique name", folder.display()).into());
+                }
+            }
+        }
+
+        if let Some(tunnel_allowlist) = &self.session.tunnel_allowlist {
+            if let Err(err) = parse_allowlist(tunnel_allowlist) {
+                valid = false;
+                errors.push(format!("forwarded host {}", err).into());
+            }
+        }
+
+        if let Some(input_idle_timeout) = &self.session.input_idle_timeout {
+            if parse_idle_timeout(input_idle_timeout).is_none() {
+                valid = false;
+                errors.push("input idle timeout is not a positive number of seconds".into());
+            }
+        }
+
+        if let Some(input_recording) = &self.session.input_recording {
+            let path = Path::new(input_recording.trim());
+            let folder = path.parent().filter(|folder| !folder.as_os_str().is_empty());
+            if path.is_dir() || !folder.map_or(true, Path::is_dir) {
+                valid = false;
+                errors.push(format!("input recording {} is not a file in a folder", path.display()).into());
+            }
         }
 
         if errors.is_empty() {
@@ -126,7 +226,7 @@ impl SetupScreen {
     /// Checks if the signalling connection is done
     /// initializing, returning the connection
     /// if initialization succeeded.
-    fn check_signalling_connection(&mut self) -> Option<WSS> {
+    fn check_signalling_connection(&mut self) -> Option<SignallingConnection> {
         match self.signalling_connection_init.as_mut().unwrap().try_recv() {
             // still waiting
             Ok(None) => None,
@@ -143,7 +243,7 @@ impl SetupScreen {
                 None
             }
             // received success
-            Ok(Some(Ok(wss))) => Some(wss),
+            Ok(Some(Ok(connection))) => Some(connection),
         }
     }
 }
@@ -169,6 +269,60 @@ impl Screen for SetupScreen {
             self.extra_ca = if editable_ca_field.trim().is_empty() { None } else { Some(editable_ca_field) };
         });
         ui.add_space(4.0);
+        ui.horizontal(|ui| {
+            ui.label("Shared Folders (Optional): ")
+                .on_hover_text(SHARED_FOLDERS_HINT);
+            let mut editable_folder_field = self.session.shared_folders.clone().unwrap_or_default();
+            ui.add_enabled(enabled, TextEdit::singleline(&mut editable_folder_field));
+            self.session.shared_folders = if editable_folder_field.trim().is_empty() {
+                None
+            } else {
+                Some(editable_folder_field)
+            };
+        });
+        ui.add_space(4.0);
+        ui.add_enabled(
+            enabled,
+            Checkbox::new(&mut self.session.allow_terminal, "Allow Remote Terminal"),
+        )
+        .on_hover_text("Lets remotes open a shell on this computer.");
+        ui.add_space(4.0);
+        ui.horizontal(|ui| {
+            ui.label("Forwarded Hosts (Optional): ")
+                .on_hover_text(TUNNEL_ALLOWLIST_HINT);
+            let mut editable_allowlist_field = self.session.tunnel_allowlist.clone().unwrap_or_default();
+            ui.add_enabled(enabled, TextEdit::singleline(&mut editable_allowlist_field));
+            self.session.tunnel_allowlist = if editable_allowlist_field.trim().is_empty() {
+                None
+            } else {
+                Some(editable_allowlist_field)
+            };
+        });
+        ui.add_space(4.0);
+        ui.horizontal(|ui| {
+            ui.label("Input Idle Timeout (Optional): ")
+                .on_hover_text(INPUT_IDLE_TIMEOUT_HINT);
+            let mut editable_timeout_field = self.session.input_idle_timeout.clone().unwrap_or_default();
+            ui.add_enabled(enabled, TextEdit::singleline(&mut editable_timeout_field));
+            self.session.input_idle_timeout = if editable_timeout_field.trim().is_empty() {
+                None
+            } else {
+                Some(editable_timeout_field)
+            };
+        });
+        ui.add_space(4.0);
+        ui.horizontal(|ui| {
+            ui.label("Input Recording (Optional): ")
+                .on_hover_text(INPUT_RECORDING_HINT);
+            let mut editable_recording_field = self.session.input_recording.clone().unwrap_or_default();
+            ui.add_enabled(enabled, TextEdit::singleline(&mut editable_recording_field));
+            self.session.input_recording = if editable_recording_field.trim().is_empty() {
+                None
+            } else {
+                Some(editable_recording_field)
+            };
+        });
+        ui.add_space(4.0);
         ui.add(Label::new(self.error_msg.as_ref().unwrap_or(&String::default())).text_color(super::ERROR_COLOR));
         ui.add_space(16.0);
         let start_clicked = ui.add_enabled(enabled, Button::new("Start!")).clicked();
@@ -179,13 +333,14 @@ impl Screen for SetupScreen {
         } else if !enabled {
             match self.check_signalling_connection() {
                 None => (self, false),
-                Some(wss) => (
+                Some(connection) => (
                     Box::new(KeygenScreen::new(
                         self.work_thread,
                         self.desktop_name,
                         self.signalling_server_addr,
                         self.extra_ca,
-                        wss,
+                        self.session,
+                        connection,
                     )),
                     true,
                 ),
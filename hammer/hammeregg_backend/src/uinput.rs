@@ -0,0 +1,173 @@
+//! Virtual input devices, created with Linux's uinput
+//! module. The kernel treats these like real hardware,
+//! so they work anywhere that a plugged-in device would.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+
+use anyhow::{anyhow, Context, Result};
+use nix::libc::{
+    input_absinfo, input_event, input_id, timeval, uinput_abs_setup, uinput_setup, O_NONBLOCK, UINPUT_MAX_NAME_SIZE,
+};
+use nix::{ioctl_none, ioctl_write_int, ioctl_write_ptr};
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_REL: u16 = 0x02;
+pub const EV_ABS: u16 = 0x03;
+pub const SYN_REPORT: u16 = 0x00;
+pub const BUS_USB: u16 = 0x03;
+pub const BUS_VIRTUAL: u16 = 0x06;
+pub const INPUT_PROP_DIRECT: u16 = 0x01;
+pub const BTN_TOUCH: u16 = 0x14a;
+pub const ABS_X: u16 = 0x00;
+pub const ABS_Y: u16 = 0x01;
+
+ioctl_none!(ui_dev_create, b'U', 1);
+ioctl_none!(ui_dev_destroy, b'U', 2);
+ioctl_write_ptr!(ui_dev_setup, b'U', 3, uinput_setup);
+ioctl_write_ptr!(ui_abs_setup, b'U', 4, uinput_abs_setup);
+ioctl_write_int!(ui_set_evbit, b'U', 100);
+ioctl_write_int!(ui_set_keybit, b'U', 101);
+ioctl_write_int!(ui_set_relbit, b'U', 102);
+ioctl_write_int!(ui_set_absbit, b'U', 103);
+ioctl_write_int!(ui_set_propbit, b'U', 110);
+
+/// An absolute axis that a device reports, such
+/// as a gamepad stick.
+#[derive(Clone, Copy, Debug)]
+pub struct AbsAxis {
+    pub code: u16,
+    pub min: i32,
+    pub max: i32,
+    /// Changes smaller than this are treated as noise.
+    pub fuzz: i32,
+    /// Values this close to the middle are reported as
+    /// the middle.
+    pub flat: i32,
+    /// Units per millimeter, or per radian for rotation
+    /// axes. Zero if the axis isn't a physical size.
+    pub resolution: i32,
+}
+
+/// Everything that a virtual device can report.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Capabilities<'a> {
+    /// Device properties, such as `INPUT_PROP_DIRECT`
+    /// for devices that are on the screen itself.
+    pub props: &'a [u16],
+    /// Key and button codes.
+    pub keys: &'a [u16],
+    /// Relative axes, such as a mouse's movement.
+    pub relative: &'a [u16],
+    pub axes: &'a [AbsAxis],
+}
+
+/// A virtual input device, which is removed
+/// when this is dropped.
+pub struct UinputDevice {
+    file: File,
+}
+
+impl UinputDevice {
+    /// Creates a device that reports `capabilities`.
+    pub fn create(name: &str, id: input_id, capabilities: Capabilities) -> Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(O_NONBLOCK)
+            .open("/dev/uinput")
+            .context("Couldn't open /dev/uinput")?;
+        let fd = file.as_raw_fd();
+
+        let mut setup = uinput_setup {
+            id,
+            name: [0; UINPUT_MAX_NAME_SIZE],
+            ff_effects_max: 0,
+        };
+        if name.len() >= setup.name.len() {
+            return Err(anyhow!("Device name {} is too long", name));
+        }
+        for (dest, &byte) in setup.name.iter_mut().zip(name.as_bytes()) {
+            *dest = byte as _;
+        }
+
+        let Capabilities {
+            props,
+            keys,
+            relative,
+            axes,
+        } = capabilities;
+        unsafe {
+            for &prop in props {
+                ui_set_propbit(fd, prop as _)?;
+            }
+            if !keys.is_empty() {
+                ui_set_evbit(fd, EV_KEY as _)?;
+                for &key in keys {
+                    ui_set_keybit(fd, key as _)?;
+                }
+            }
+            if !relative.is_empty() {
+                ui_set_evbit(fd, EV_REL as _)?;
+                for &axis in relative {
+                    ui_set_relbit(fd, axis as _)?;
+                }
+            }
+            if !axes.is_empty() {
+                ui_set_evbit(fd, EV_ABS as _)?;
+                for axis in axes {
+                    ui_set_absbit(fd, axis.code as _)?;
+                    let abs_setup = uinput_abs_setup {
+                        code: axis.code,
+                        absinfo: input_absinfo {
+                            value: 0,
+                            minimum: axis.min,
+                            maximum: axis.max,
+                            fuzz: axis.fuzz,
+                            flat: axis.flat,
+                            resolution: axis.resolution,
+                        },
+                    };
+                    ui_abs_setup(fd, &abs_setup)?;
+                }
+            }
+            ui_dev_setup(fd, &setup)?;
+            ui_dev_create(fd)?;
+        }
+        Ok(Self { file })
+    }
+
+    /// Queues an event, which takes effect on
+    /// the next [`sync`](Self::sync).
+    pub fn emit(&mut self, kind: u16, code: u16, value: i32) -> Result<()> {
+        let event = input_event {
+            time: timeval { tv_sec: 0, tv_usec: 0 },
+            type_: kind,
+            code,
+            value,
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &event as *const input_event as *const u8,
+                std::mem::size_of::<input_event>(),
+            )
+        };
+        self.file.write_all(bytes)?;
+        Ok(())
+    }
+
+    /// Applies every queued event at once.
+    pub fn sync(&mut self) -> Result<()> {
+        self.emit(EV_SYN, SYN_REPORT, 0)
+    }
+}
+
+impl Drop for UinputDevice {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = ui_dev_destroy(self.file.as_raw_fd());
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! Decodes and validates arbitrary bytes as every
+//! packet type with every codec, the same way Rooster
+//! and Desktop treat packets from the network.
+
+#![no_main]
+use hammeregg_core::{
+    deserialize_and_validate_packet, ClipboardPacket, FileTransferPacket, HandshakeInitPacket, HandshakePacket,
+    InputPacket, TerminalPacket, TunnelPacket, SUPPORTED_CODECS,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for codec in SUPPORTED_CODECS {
+        let _ = deserialize_and_validate_packet::<_, HandshakeInitPacket>(&codec, data);
+        let _ = deserialize_and_validate_packet::<_, HandshakePacket>(&codec, data);
+        let _ = deserialize_and_validate_packet::<_, InputPacket>(&codec, data);
+        let _ = deserialize_and_validate_packet::<_, ClipboardPacket>(&codec, data);
+        let _ = deserialize_and_validate_packet::<_, FileTransferPacket>(&codec, data);
+        let _ = deserialize_and_validate_packet::<_, TerminalPacket>(&codec, data);
+        let _ = deserialize_and_validate_packet::<_, TunnelPacket>(&codec, data);
+    }
+});
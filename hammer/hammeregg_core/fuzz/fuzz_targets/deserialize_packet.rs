@@ -0,0 +1,27 @@
+//! Decodes arbitrary bytes as every packet type with
+//! every codec. Handshake packets that decode must
+//! also survive a round trip.
+
+#![no_main]
+use hammeregg_core::{
+    deserialize_packet, serialize_packet, ClipboardPacket, FileTransferPacket, HandshakeInitPacket, HandshakePacket,
+    InputPacket, TerminalPacket, TunnelPacket, SUPPORTED_CODECS,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for codec in SUPPORTED_CODECS {
+        let _ = deserialize_packet::<_, HandshakeInitPacket>(&codec, data);
+        let _ = deserialize_packet::<_, InputPacket>(&codec, data);
+        let _ = deserialize_packet::<_, ClipboardPacket>(&codec, data);
+        let _ = deserialize_packet::<_, FileTransferPacket>(&codec, data);
+        let _ = deserialize_packet::<_, TerminalPacket>(&codec, data);
+        let _ = deserialize_packet::<_, TunnelPacket>(&codec, data);
+        if let Ok(packet) = deserialize_packet::<_, HandshakePacket>(&codec, data) {
+            let bytes = serialize_packet(&codec, &packet).expect("decoded packet failed to encode");
+            let decoded =
+                deserialize_packet::<_, HandshakePacket>(&codec, &bytes).expect("encoded packet failed to decode");
+            assert_eq!(packet, decoded);
+        }
+    }
+});
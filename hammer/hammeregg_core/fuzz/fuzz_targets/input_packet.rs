@@ -0,0 +1,14 @@
+//! Mirrors how Desktop decodes input packets from
+//! the WebRTC data channel. The first byte picks the
+//! negotiated codec and the rest is the message.
+
+#![no_main]
+use hammeregg_core::{deserialize_and_validate_packet, InputPacket, SUPPORTED_CODECS};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((selector, message)) = data.split_first() {
+        let codec = SUPPORTED_CODECS[*selector as usize % SUPPORTED_CODECS.len()];
+        let _ = deserialize_and_validate_packet::<_, InputPacket>(&codec, message);
+    }
+});
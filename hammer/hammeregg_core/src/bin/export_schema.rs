@@ -0,0 +1,23 @@
+//! Writes the protocol's JSON Schema to
+//! `hammeregg_core/schema/protocol.schema.json`,
+//! and the TypeScript types generated from it to
+//! `hammeregg_core/src/wasm_types.rs`.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use hammeregg_core::schema::{protocol_schema_json, protocol_typescript, SCHEMA_PATH, TYPESCRIPT_PATH};
+
+fn main() -> Result<()> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for (path, contents) in [
+        (SCHEMA_PATH, protocol_schema_json()),
+        (TYPESCRIPT_PATH, protocol_typescript()),
+    ] {
+        let path = root.join(path);
+        fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! Serialization formats for Hammeregg packets.
+//!
+//! The [`HandshakeInitPacket`] and its response are
+//! always serialized with BSON. All later packets,
+//! including those sent over WebRTC data channels,
+//! use the [`Codec`] negotiated during the handshake.
+//!
+//! [`HandshakeInitPacket`]: crate::HandshakeInitPacket
+
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A serialization format for Hammeregg packets.
+pub trait PacketCodec {
+    /// Serializes a packet to bytes.
+    fn encode<P>(&self, packet: &P) -> Result<Vec<u8>>
+    where
+        P: Serialize;
+
+    /// Deserializes a packet from bytes.
+    fn decode<'a, P>(&self, bytes: &'a [u8]) -> Result<P>
+    where
+        P: Deserialize<'a>;
+}
+
+/// The [BSON](https://bsonspec.org/) codec, which
+/// every Hammeregg implementation must support.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct BsonCodec;
+
+impl PacketCodec for BsonCodec {
+    fn encode<P>(&self, packet: &P) -> Result<Vec<u8>>
+    where
+        P: Serialize,
+    {
+        bson::to_vec(packet).context("Failed to serialize packet")
+    }
+
+    fn decode<'a, P>(&self, bytes: &'a [u8]) -> Result<P>
+    where
+        P: Deserialize<'a>,
+    {
+        bson::from_slice(bytes).context("Failed to deserialize packet")
+    }
+}
+
+/// The [CBOR](https://cbor.io/) codec.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct CborCodec;
+
+impl PacketCodec for CborCodec {
+    fn encode<P>(&self, packet: &P) -> Result<Vec<u8>>
+    where
+        P: Serialize,
+    {
+        serde_cbor::to_vec(packet).context("Failed to serialize packet")
+    }
+
+    fn decode<'a, P>(&self, bytes: &'a [u8]) -> Result<P>
+    where
+        P: Deserialize<'a>,
+    {
+        serde_cbor::from_slice(bytes).context("Failed to deserialize packet")
+    }
+}
+
+/// The [MessagePack](https://msgpack.org/) codec.
+/// Structs are serialized as maps and enum variants
+/// are serialized by name so that packets look
+/// mostly the same on the wire as they do in other
+/// codecs. Unlike other codecs, unit variants are
+/// serialized as a map from their name to nil.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct MessagePackCodec;
+
+impl PacketCodec for MessagePackCodec {
+    fn encode<P>(&self, packet: &P) -> Result<Vec<u8>>
+    where
+        P: Serialize,
+    {
+        let mut bytes = Vec::new();
+        packet
+            .serialize(
+                &mut rmp_serde::Serializer::new(&mut bytes)
+                    .with_struct_map()
+                    .with_string_variants(),
+            )
+            .context("Failed to serialize packet")?;
+        Ok(bytes)
+    }
+
+    fn decode<'a, P>(&self, bytes: &'a [u8]) -> Result<P>
+    where
+        P: Deserialize<'a>,
+    {
+        rmp_serde::from_slice(bytes).context("Failed to deserialize packet")
+    }
+}
+
+/// The codecs that can be negotiated during
+/// the initial signalling handshake.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Codec {
+    #[default]
+    Bson,
+    Cbor,
+    MessagePack,
+    /// A codec that this version of Hammeregg
+    /// doesn't know about. This is never chosen
+    /// during negotiation.
+    #[serde(other)]
+    #[schemars(skip)]
+    Unknown,
+}
+
+impl PacketCodec for Codec {
+    fn encode<P>(&self, packet: &P) -> Result<Vec<u8>>
+    where
+        P: Serialize,
+    {
+        match self {
+            Codec::Bson => BsonCodec.encode(packet),
+            Codec::Cbor => CborCodec.encode(packet),
+            Codec::MessagePack => MessagePackCodec.encode(packet),
+            Codec::Unknown => Err(anyhow!("Cannot serialize a packet with an unknown codec")),
+        }
+    }
+
+    fn decode<'a, P>(&self, bytes: &'a [u8]) -> Result<P>
+    where
+        P: Deserialize<'a>,
+    {
+        match self {
+            Codec::Bson => BsonCodec.decode(bytes),
+            Codec::Cbor => CborCodec.decode(bytes),
+            Codec::MessagePack => MessagePackCodec.decode(bytes),
+            Codec::Unknown => Err(anyhow!("Cannot deserialize a packet with an unknown codec")),
+        }
+    }
+}
+
+/// The codecs that this implementation of Hammeregg
+/// supports, in order of preference.
+pub const SUPPORTED_CODECS: [Codec; 3] = [Codec::MessagePack, Codec::Cbor, Codec::Bson];
+
+/// Picks the first codec in `preferred` that is also
+/// in `supported`. Falls back to [`Codec::Bson`] if the
+/// two lists have nothing in common, since every
+/// implementation must support BSON.
+pub fn negotiate_codec(preferred: &[Codec], supported: &[Codec]) -> Codec {
+    preferred
+        .iter()
+        .copied()
+        .find(|codec| *codec != Codec::Unknown && supported.contains(codec))
+        .unwrap_or(Codec::Bson)
+}
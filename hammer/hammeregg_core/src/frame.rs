@@ -0,0 +1,64 @@
+//! Length-delimited framing for stream transports.
+//!
+//! Transports that preserve message boundaries,
+//! such as WebSockets or WebRTC data channels, can
+//! send encoded packets as-is. Byte streams such as
+//! TCP or Unix sockets need to know where one packet
+//! ends and the next begins, so each packet is
+//! prefixed with its length as a big-endian `u32`.
+
+use std::io::{ErrorKind, Read, Write};
+
+use anyhow::{anyhow, Context, Result};
+
+/// The largest frame that [`read_frame`] will accept.
+/// This stops a misbehaving peer from making us
+/// allocate an arbitrarily large buffer.
+pub const MAX_FRAME_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Writes one length-prefixed frame to `writer`.
+pub fn write_frame<W>(writer: &mut W, bytes: &[u8]) -> Result<()>
+where
+    W: Write,
+{
+    let len = u32::try_from(bytes.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_SIZE)
+        .ok_or_else(|| anyhow!("Frame of {} bytes is too large", bytes.len()))?;
+    writer
+        .write_all(&len.to_be_bytes())
+        .context("Failed to write frame length")?;
+    writer.write_all(bytes).context("Failed to write frame")?;
+    Ok(())
+}
+
+/// Reads one length-prefixed frame from `reader`.
+/// Returns `Ok(None)` if the stream ended cleanly
+/// before the start of a frame, and an error if it
+/// ended anywhere inside one.
+pub fn read_frame<R>(reader: &mut R) -> Result<Option<Vec<u8>>>
+where
+    R: Read,
+{
+    let mut len_bytes = [0u8; 4];
+    let mut read = 0;
+    while read < len_bytes.len() {
+        // This can't use `read_exact`, since that doesn't
+        // say whether it ran out before reading anything
+        match reader.read(&mut len_bytes[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(anyhow!("Stream ended in the middle of a frame length")),
+            Ok(n) => read += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err).context("Failed to read frame length"),
+        }
+    }
+
+    let len = u32::from_be_bytes(len_bytes);
+    if len > MAX_FRAME_SIZE {
+        return Err(anyhow!("Frame of {} bytes is too large", len));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes).context("Failed to read frame")?;
+    Ok(Some(bytes))
+}
@@ -0,0 +1,17 @@
+//! The password that Egg uses to connect to
+//! Hammeregg Desktop.
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// The home public key and remote private key combo
+/// that serves as Hammeregg's password.
+///
+/// Both keys are stored as PEM strings in this struct,
+/// which is saved to disk as BSON.
+#[derive(Serialize, Deserialize, Zeroize)]
+#[zeroize(drop)]
+pub struct RemotePassword {
+    pub home_public_key: String,
+    pub remote_private_key: String,
+}
@@ -0,0 +1,173 @@
+//! Physical key codes, which identify a key by its
+//! position on the keyboard rather than by what it
+//! types. These are named after the values of the
+//! W3C [`KeyboardEvent.code`] property, so browsers
+//! can send them as-is.
+//!
+//! [`KeyboardEvent.code`]: https://www.w3.org/TR/uievents-code/
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+macro_rules! key_codes {
+    ($($code:ident),* $(,)?) => {
+        /// A physical key, named after its W3C
+        /// `KeyboardEvent.code` value. A key code types
+        /// whatever the home computer's keyboard layout
+        /// puts at that position, so `KeyQ` types `a`
+        /// on an AZERTY keyboard.
+        #[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum KeyCode {
+            $($code,)*
+        }
+
+        impl KeyCode {
+            /// Every key code, in declaration order.
+            pub const ALL: &'static [KeyCode] = &[$(KeyCode::$code,)*];
+        }
+    };
+}
+
+key_codes! {
+    // Writing system keys
+    Backquote,
+    Backslash,
+    BracketLeft,
+    BracketRight,
+    Comma,
+    Digit0,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    Equal,
+    IntlBackslash,
+    IntlRo,
+    IntlYen,
+    KeyA,
+    KeyB,
+    KeyC,
+    KeyD,
+    KeyE,
+    KeyF,
+    KeyG,
+    KeyH,
+    KeyI,
+    KeyJ,
+    KeyK,
+    KeyL,
+    KeyM,
+    KeyN,
+    KeyO,
+    KeyP,
+    KeyQ,
+    KeyR,
+    KeyS,
+    KeyT,
+    KeyU,
+    KeyV,
+    KeyW,
+    KeyX,
+    KeyY,
+    KeyZ,
+    Minus,
+    Period,
+    Quote,
+    Semicolon,
+    Slash,
+    // Functional keys
+    AltLeft,
+    AltRight,
+    Backspace,
+    CapsLock,
+    ContextMenu,
+    ControlLeft,
+    ControlRight,
+    Enter,
+    MetaLeft,
+    MetaRight,
+    ShiftLeft,
+    ShiftRight,
+    Space,
+    Tab,
+    Convert,
+    KanaMode,
+    Lang1,
+    Lang2,
+    NonConvert,
+    // Control pad keys
+    Delete,
+    End,
+    Help,
+    Home,
+    Insert,
+    PageDown,
+    PageUp,
+    // Arrow keys
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    ArrowUp,
+    // Numpad keys
+    NumLock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadComma,
+    NumpadDecimal,
+    NumpadDivide,
+    NumpadEnter,
+    NumpadEqual,
+    NumpadMultiply,
+    NumpadSubtract,
+    // Function keys
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    Pause,
+    PrintScreen,
+    ScrollLock,
+    // Media keys
+    AudioVolumeDown,
+    AudioVolumeMute,
+    AudioVolumeUp,
+    MediaPlayPause,
+    MediaStop,
+    MediaTrackNext,
+    MediaTrackPrevious,
+}
@@ -5,18 +5,52 @@
 //! live stream packets.
 //!
 //! All data structures in this crate are
-//! expected to be serialized with 🅱️son.
-//  Maintainers: keep this file synchronized with
-//  egg/src/hammeregg_core.ts
+//! expected to be serialized with a [`Codec`],
+//! which defaults to 🅱️son.
+//!
+//! This crate only deals in bytes and doesn't
+//! care how they're transported. Helpers for
+//! length-delimited streams live in [`frame`],
+//! and helpers for WebSockets live in
+//! `websocket` behind the `websocket` feature.
+//!
+//! Egg uses this crate by compiling it to
+//! WebAssembly with the `wasm` feature through
+//! `hammeregg_wasm`, see `wasm` for the bindings
+//! it exposes.
+//!
+//! Other clients can check themselves against the
+//! JSON Schema in `schema/` (see [`schema`]) and the
+//! golden encodings of every packet in `vectors/`.
+
+#![feature(derive_default_enum)]
+
+pub mod codec;
+pub mod frame;
+pub mod key;
+pub mod keycode;
+pub mod limits;
+pub mod schema;
+pub mod transfer;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
 
-use anyhow::{anyhow, Context, Result};
-use serde::{Deserialize, Serialize, Serializer};
-use tungstenite::Message;
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidationError};
 
+pub use crate::codec::{negotiate_codec, Codec, PacketCodec, SUPPORTED_CODECS};
+pub use crate::key::RemotePassword;
+pub use crate::keycode::KeyCode;
+pub use crate::transfer::{FileEntry, FileTransferPacket};
+
 /// The default port for Hammeregg signalling.
 pub const DEFAULT_HAMMEREGG_PORT: u16 = 7269;
 
@@ -27,29 +61,197 @@ pub const MAGIC: i64 = 0x_F0_9F_94_A8_F0_9F_A5_9A_u64 as i64;
 
 // Protocol Versions
 /// Version 1.0
-pub const VERSION_1_0: u32 = 0x_00_01__00_00;
+pub const VERSION_1_0: u32 = 0x0001_0000;
+/// Version 1.1, which sends errors as an [`ErrorCode`]
+/// and a detail message instead of a plain string.
+pub const VERSION_1_1: u32 = 0x0001_0001;
+
+/// The range of protocol versions that this
+/// implementation of Hammeregg can speak.
+/// Clients advertise this range in their
+/// [`HandshakeInitPacket`], and Rooster picks
+/// the highest version that both sides support.
+pub const SUPPORTED_VERSIONS: RangeInclusive<u32> = VERSION_1_0..=VERSION_1_1;
 
-/// A wrapper around a String that implements Error.
-#[derive(Serialize, Deserialize, Debug)]
-#[repr(transparent)]
-#[serde(transparent)]
-pub struct ErrorMsg(pub String);
+/// Picks the highest protocol version contained
+/// in both of the given version ranges, returning
+/// `None` if the two ranges do not overlap.
+pub fn negotiate_version(a: &RangeInclusive<u32>, b: &RangeInclusive<u32>) -> Option<u32> {
+    let highest = *a.end().min(b.end());
+    if highest >= *a.start().max(b.start()) {
+        Some(highest)
+    } else {
+        None
+    }
+}
+
+/// Default value for the `version` fields that
+/// a Rooster that predates version negotiation
+/// doesn't send.
+#[inline]
+fn default_version() -> u32 {
+    VERSION_1_0
+}
+
+/// Machine-readable reasons for why a request
+/// failed, sent over the wire as part of an
+/// [`ErrorMsg`].
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorCode {
+    /// The peers do not have any protocol
+    /// versions in common.
+    UnsupportedVersion,
+    /// The requested home desktop name is
+    /// already in use by another desktop.
+    NameTaken,
+    /// The requested home desktop is not
+    /// connected to the signalling server.
+    DesktopNotFound,
+    /// The home desktop couldn't decrypt the
+    /// remote's offer, which usually means that
+    /// the remote is using the wrong password.
+    DecryptFailed,
+    /// The remote's offer was decrypted but
+    /// is not a valid WebRTC offer.
+    OfferInvalid,
+    /// The sender is making too many requests
+    /// and should try again later.
+    RateLimited,
+    /// The sender is not allowed to make
+    /// this request.
+    Unauthorized,
+    /// The requested file does not exist.
+    FileNotFound,
+    /// A file chunk didn't match its hash.
+    ChecksumMismatch,
+    /// Any other error. Error codes that this
+    /// version of Hammeregg doesn't know about
+    /// are also deserialized as this code.
+    #[serde(other)]
+    Other,
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ErrorCode::UnsupportedVersion => "Unsupported protocol version",
+            ErrorCode::NameTaken => "Requested desktop name was already taken",
+            ErrorCode::DesktopNotFound => "Requested desktop not found",
+            ErrorCode::DecryptFailed => "Couldn't decrypt offer",
+            ErrorCode::OfferInvalid => "Invalid offer",
+            ErrorCode::RateLimited => "Too many requests",
+            ErrorCode::Unauthorized => "Unauthorized",
+            ErrorCode::FileNotFound => "File not found",
+            ErrorCode::ChecksumMismatch => "File chunk was corrupted",
+            ErrorCode::Other => "Unknown error",
+        })
+    }
+}
+
+/// An error sent over the wire, consisting of a
+/// machine-readable [`ErrorCode`] and an optional
+/// human-readable detail message.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Debug)]
+#[serde(from = "ErrorMsgRepr", into = "ErrorMsgRepr")]
+pub struct ErrorMsg {
+    pub code: ErrorCode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Whether this is sent as a plain string,
+    /// see [`ErrorMsg::for_version`].
+    #[serde(skip)]
+    legacy: bool,
+}
+
+impl ErrorMsg {
+    /// Creates an [`ErrorMsg`] without a detail message.
+    pub fn new(code: ErrorCode) -> Self {
+        Self {
+            code,
+            detail: None,
+            legacy: false,
+        }
+    }
+
+    /// Creates an [`ErrorMsg`] with a detail message.
+    pub fn with_detail<S: Into<String>>(code: ErrorCode, detail: S) -> Self {
+        Self {
+            code,
+            detail: Some(detail.into()),
+            legacy: false,
+        }
+    }
+
+    /// Gets this error in a form that a peer on protocol
+    /// `version` can read. Peers before [`VERSION_1_1`]
+    /// only read errors as plain strings, so they're sent
+    /// this error's message instead.
+    pub fn for_version(self, version: u32) -> Self {
+        Self {
+            legacy: version < VERSION_1_1,
+            ..self
+        }
+    }
+}
 
 impl Display for ErrorMsg {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.serialize_str(self.0.as_str())
+        match &self.detail {
+            Some(detail) => write!(f, "{}: {}", self.code, detail),
+            None => write!(f, "{}", self.code),
+        }
     }
 }
 
 impl Error for ErrorMsg {}
 
+/// Wire representations of an [`ErrorMsg`]. Peers
+/// that predate error codes send errors as plain
+/// strings, which are read as [`ErrorCode::Other`].
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ErrorMsgRepr {
+    Coded {
+        code: ErrorCode,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<String>,
+    },
+    Legacy(String),
+}
+
+impl From<ErrorMsgRepr> for ErrorMsg {
+    fn from(repr: ErrorMsgRepr) -> Self {
+        match repr {
+            ErrorMsgRepr::Coded { code, detail } => Self {
+                code,
+                detail,
+                legacy: false,
+            },
+            ErrorMsgRepr::Legacy(detail) => Self::with_detail(ErrorCode::Other, detail),
+        }
+    }
+}
+
+impl From<ErrorMsg> for ErrorMsgRepr {
+    fn from(error: ErrorMsg) -> Self {
+        if error.legacy {
+            ErrorMsgRepr::Legacy(error.to_string())
+        } else {
+            ErrorMsgRepr::Coded {
+                code: error.code,
+                detail: error.detail,
+            }
+        }
+    }
+}
+
 /// The body of the various packet types sent over
 /// the signalling server channel. Both the
 /// [`HomeInit`] and [`RemoteInit`] packets must
 /// also be wrapped in an [`HandshakeInitPacket`].
 /// All other packet types consist of just this
 /// enum.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Debug)]
 #[serde(tag = "type")]
 pub enum HandshakePacket {
     HomeInit {
@@ -57,18 +259,47 @@ pub enum HandshakePacket {
     },
     HomeInitResponse {
         response: Result<(), ErrorMsg>,
+        /// The protocol version negotiated for this
+        /// connection. If `response` is an error, this
+        /// is the highest version that Rooster supports.
+        #[serde(default = "default_version")]
+        version: u32,
+        /// The codec negotiated for all packets sent
+        /// after this one.
+        #[serde(default)]
+        codec: Codec,
     },
     RemoteInit {
         home_name: String,
     },
     RemoteInitResponse {
         response: Result<(), ErrorMsg>,
+        /// The protocol version negotiated for this
+        /// connection. If `response` is an error, this
+        /// is the highest version that Rooster supports.
+        #[serde(default = "default_version")]
+        version: u32,
+        /// The codec negotiated for all packets sent
+        /// after this one.
+        #[serde(default)]
+        codec: Codec,
     },
     RemoteOffer {
         peer: u32,
         key: Vec<u8>,
         iv: Vec<u8>,
         payload: Vec<u8>,
+        /// The codec that the remote will use on its
+        /// WebRTC data channels. Like `peer`, this is
+        /// filled in by Rooster.
+        #[serde(default)]
+        codec: Codec,
+        /// The protocol version that the remote
+        /// negotiated with Rooster, which the home
+        /// desktop also supports. Like `peer`, this
+        /// is filled in by Rooster.
+        #[serde(default = "default_version")]
+        version: u32,
     },
     HomeAnswerSuccess {
         peer: u32,
@@ -78,7 +309,24 @@ pub enum HandshakePacket {
     },
     HomeAnswerFailure {
         peer: u32,
-        error: String,
+        error: ErrorMsg,
+    },
+    /// An ICE candidate gathered by the remote after
+    /// it sent its offer, encrypted with the same AES
+    /// key as the offer. Like `peer`, this is filled
+    /// in by Rooster.
+    RemoteIceCandidate {
+        peer: u32,
+        iv: Vec<u8>,
+        payload: Vec<u8>,
+    },
+    /// An ICE candidate gathered by the home desktop
+    /// after it sent its answer, encrypted with the
+    /// same AES key as the answer.
+    HomeIceCandidate {
+        peer: u32,
+        iv: Vec<u8>,
+        payload: Vec<u8>,
     },
 }
 
@@ -88,27 +336,54 @@ pub enum HandshakePacket {
 /// an inner packet of type [`HomeInit`], and remote
 /// computers should send an inner packet of type
 /// [`RemoteInit`].
-#[derive(Serialize, Deserialize, Validate)]
+///
+/// The sender advertises the range of protocol
+/// versions that it supports with `min_version`
+/// and `version`. Clients that predate version
+/// negotiation only send `version`, in which case
+/// that is the only version they support.
+///
+/// The sender also advertises the codecs that it
+/// supports in `codecs`, in order of preference.
+/// This packet and its response are always
+/// serialized with BSON.
+#[derive(Serialize, Deserialize, JsonSchema, Validate, Clone, PartialEq, Eq, Debug)]
+#[validate(schema(function = "HandshakeInitPacket::validate_version_range"))]
 pub struct HandshakeInitPacket {
+    /// Always [`MAGIC`].
     #[validate(custom = "HandshakeInitPacket::validate_magic")]
     magic: i64,
-    #[validate(custom = "HandshakeInitPacket::validate_version")]
     pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub codecs: Vec<Codec>,
     #[validate(custom = "HandshakeInitPacket::validate_packet")]
+    #[validate]
     pub packet: HandshakePacket,
 }
 
 impl HandshakeInitPacket {
-    pub fn new(version: u32, packet: HandshakePacket) -> Result<Self> {
+    /// Creates a new [`HandshakeInitPacket`] that
+    /// advertises all [`SUPPORTED_CODECS`].
+    pub fn new(versions: RangeInclusive<u32>, packet: HandshakePacket) -> Result<Self> {
         let new = Self {
             magic: MAGIC,
-            version,
+            version: *versions.end(),
+            min_version: Some(*versions.start()),
+            codecs: SUPPORTED_CODECS.to_vec(),
             packet,
         };
         new.validate()?;
         Ok(new)
     }
 
+    /// Gets the range of protocol versions
+    /// supported by the sender of this packet.
+    pub fn versions(&self) -> RangeInclusive<u32> {
+        self.min_version.unwrap_or(self.version)..=self.version
+    }
+
     /// Validates that the magic number is correct
     /// in a [`HandshakeInitPacket`].
     #[inline]
@@ -120,14 +395,16 @@ impl HandshakeInitPacket {
         }
     }
 
-    /// Validates that the version is [`VERSION_1_0`]
-    /// in a [`HandshakeInitPacket`].
+    /// Validates that the advertised version range
+    /// in a [`HandshakeInitPacket`] is not empty.
+    /// Whether the range is actually supported is
+    /// decided later by [`negotiate_version`].
     #[inline]
-    fn validate_version(version: u32) -> Result<(), ValidationError> {
-        if version == VERSION_1_0 {
-            Ok(())
+    fn validate_version_range(&self) -> Result<(), ValidationError> {
+        if self.versions().is_empty() {
+            Err(ValidationError::new("Invalid version range!"))
         } else {
-            Err(ValidationError::new("Unsupported version!"))
+            Ok(())
         }
     }
 
@@ -146,37 +423,162 @@ impl HandshakeInitPacket {
 
 /// Keyboard and mouse input packets, sent by the
 /// remote computer over a WebRTC data channel.
-#[derive(Serialize, Deserialize)]
+/// Packets are applied in the order they're sent.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum InputPacket {
     KeyDown(KeyInput),
     KeyUp(KeyInput),
     MouseDown(MouseButton),
     MouseUp(MouseButton),
-    MouseMove { x: f32, y: f32 },
-    MouseScroll { x: i32, y: i32 },
+    MouseMove {
+        x: f32,
+        y: f32,
+    },
+    /// Moves the mouse by a number of pixels from
+    /// wherever it is, rather than to a position on
+    /// the screen. Applications that capture the mouse
+    /// and keep moving it back to the middle of the
+    /// screen, like games and 3D tools, need this.
+    MouseMoveRelative {
+        dx: i32,
+        dy: i32,
+    },
+    MouseScroll {
+        x: i32,
+        y: i32,
+    },
+    /// Text to type as-is, regardless of the home
+    /// computer's keyboard layout. This carries input
+    /// that doesn't map to a single key press, such as
+    /// composed IME input, dead keys and emoji.
+    Text(String),
+    /// Releases every key and mouse button that the
+    /// remote is holding down, such as when it loses
+    /// focus and won't see them being released. This
+    /// is an empty struct rather than a unit variant,
+    /// since BSON can't encode a bare string.
+    ReleaseAll {},
+    /// A button on the remote's gamepad being pressed
+    /// or released. Each remote has one gamepad.
+    GamepadButton {
+        button: GamepadButton,
+        pressed: bool,
+    },
+    /// An axis on the remote's gamepad moving. Sticks
+    /// go from -1 to 1, where negative values are left
+    /// or up, and triggers go from 0 to 1.
+    GamepadAxis {
+        axis: GamepadAxis,
+        value: f32,
+    },
+    /// A finger touching the screen. Each finger keeps
+    /// its `id` until it's lifted, and its coordinates
+    /// are normalized like [`InputPacket::MouseMove`]'s.
+    /// Pressure goes from 0 to 1.
+    Touch {
+        id: u32,
+        x: f32,
+        y: f32,
+        pressure: f32,
+        state: ContactState,
+    },
+    /// The remote's pen, with coordinates and pressure
+    /// like [`InputPacket::Touch`]'s. Tilt is in degrees
+    /// from -90 to 90, where positive values are tilted
+    /// right and towards the user.
+    Pen {
+        x: f32,
+        y: f32,
+        pressure: f32,
+        tilt_x: f32,
+        tilt_y: f32,
+        state: ContactState,
+    },
 }
 
 /// Keyboard input.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum KeyInput {
     SpecialKey(SpecialKeyInput),
     AlphaKey(char),
     RawKey(u16),
+    /// A physical key, which types the same key on
+    /// the home computer no matter which keyboard
+    /// layouts either computer uses.
+    Code(KeyCode),
 }
 
 /// Mouse buttons.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum MouseButton {
     Left,
     Middle,
     Right,
 }
 
+/// Gamepad buttons, named after the buttons on
+/// an Xbox controller. The triggers are axes.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    LeftBumper,
+    RightBumper,
+    Back,
+    Start,
+    Guide,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Gamepad axes.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+/// Whether a finger or pen is touching the screen.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContactState {
+    /// Near the screen without touching it.
+    /// Only pens can hover.
+    Hover,
+    /// Touching the screen.
+    Contact,
+    /// Moved away from the screen. A finger that's
+    /// lifted is done, and its id can be reused.
+    Lifted,
+}
+
+impl GamepadAxis {
+    /// Whether this is a trigger, which only
+    /// goes from 0 to 1.
+    pub fn is_trigger(self) -> bool {
+        matches!(self, GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger)
+    }
+}
+
 /// "Special" input keys, based on the keys that
-/// Enigo supports.
-#[derive(Serialize, Deserialize)]
+/// Enigo supports, followed by keys that Enigo
+/// can't press. The latter are named after their
+/// W3C `KeyboardEvent.key` values, except for the
+/// numpad keys, which are named after their
+/// `KeyboardEvent.code` values since their `key`
+/// values are the same as the main keyboard's.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum SpecialKeyInput {
     Alt,
     Backspace,
@@ -210,43 +612,258 @@ pub enum SpecialKeyInput {
     Space,
     Tab,
     UpArrow,
+    Insert,
+    PrintScreen,
+    ScrollLock,
+    NumLock,
+    Pause,
+    ContextMenu,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadEnter,
+    AudioVolumeMute,
+    AudioVolumeDown,
+    AudioVolumeUp,
+    MediaPlayPause,
+    MediaStop,
+    MediaTrackNext,
+    MediaTrackPrevious,
+}
+
+impl SpecialKeyInput {
+    /// Every special key, in declaration order.
+    pub const ALL: [SpecialKeyInput; 73] = [
+        SpecialKeyInput::Alt,
+        SpecialKeyInput::Backspace,
+        SpecialKeyInput::CapsLock,
+        SpecialKeyInput::Control,
+        SpecialKeyInput::Delete,
+        SpecialKeyInput::DownArrow,
+        SpecialKeyInput::End,
+        SpecialKeyInput::Escape,
+        SpecialKeyInput::F1,
+        SpecialKeyInput::F10,
+        SpecialKeyInput::F11,
+        SpecialKeyInput::F12,
+        SpecialKeyInput::F2,
+        SpecialKeyInput::F3,
+        SpecialKeyInput::F4,
+        SpecialKeyInput::F5,
+        SpecialKeyInput::F6,
+        SpecialKeyInput::F7,
+        SpecialKeyInput::F8,
+        SpecialKeyInput::F9,
+        SpecialKeyInput::Home,
+        SpecialKeyInput::LeftArrow,
+        SpecialKeyInput::Meta,
+        SpecialKeyInput::Option,
+        SpecialKeyInput::PageDown,
+        SpecialKeyInput::PageUp,
+        SpecialKeyInput::Return,
+        SpecialKeyInput::RightArrow,
+        SpecialKeyInput::Shift,
+        SpecialKeyInput::Space,
+        SpecialKeyInput::Tab,
+        SpecialKeyInput::UpArrow,
+        SpecialKeyInput::Insert,
+        SpecialKeyInput::PrintScreen,
+        SpecialKeyInput::ScrollLock,
+        SpecialKeyInput::NumLock,
+        SpecialKeyInput::Pause,
+        SpecialKeyInput::ContextMenu,
+        SpecialKeyInput::F13,
+        SpecialKeyInput::F14,
+        SpecialKeyInput::F15,
+        SpecialKeyInput::F16,
+        SpecialKeyInput::F17,
+        SpecialKeyInput::F18,
+        SpecialKeyInput::F19,
+        SpecialKeyInput::F20,
+        SpecialKeyInput::F21,
+        SpecialKeyInput::F22,
+        SpecialKeyInput::F23,
+        SpecialKeyInput::F24,
+        SpecialKeyInput::Numpad0,
+        SpecialKeyInput::Numpad1,
+        SpecialKeyInput::Numpad2,
+        SpecialKeyInput::Numpad3,
+        SpecialKeyInput::Numpad4,
+        SpecialKeyInput::Numpad5,
+        SpecialKeyInput::Numpad6,
+        SpecialKeyInput::Numpad7,
+        SpecialKeyInput::Numpad8,
+        SpecialKeyInput::Numpad9,
+        SpecialKeyInput::NumpadAdd,
+        SpecialKeyInput::NumpadSubtract,
+        SpecialKeyInput::NumpadMultiply,
+        SpecialKeyInput::NumpadDivide,
+        SpecialKeyInput::NumpadDecimal,
+        SpecialKeyInput::NumpadEnter,
+        SpecialKeyInput::AudioVolumeMute,
+        SpecialKeyInput::AudioVolumeDown,
+        SpecialKeyInput::AudioVolumeUp,
+        SpecialKeyInput::MediaPlayPause,
+        SpecialKeyInput::MediaStop,
+        SpecialKeyInput::MediaTrackNext,
+        SpecialKeyInput::MediaTrackPrevious,
+    ];
+}
+
+/// Clipboard contents, sent in both directions over
+/// a WebRTC data channel whenever the sender's
+/// clipboard changes. Only text is synchronized for
+/// now, other kinds of contents will get their own
+/// variants.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardPacket {
+    Text(String),
+}
+
+/// Terminal packets, sent in both directions over the
+/// optional `hammeregg-terminal` WebRTC data channel.
+/// The remote starts a shell with [`Open`], after which
+/// both sides send [`Data`] until the home desktop
+/// sends [`Exit`].
+///
+/// [`Open`]: TerminalPacket::Open
+/// [`Data`]: TerminalPacket::Data
+/// [`Exit`]: TerminalPacket::Exit
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminalPacket {
+    /// Asks the home desktop to start the user's
+    /// shell in a terminal of the given size.
+    Open { cols: u16, rows: u16 },
+    /// Bytes typed into the terminal by the remote,
+    /// or printed to the terminal by the shell.
+    Data(
+        #[serde(with = "serde_bytes")]
+        #[schemars(with = "Vec<u8>")]
+        Vec<u8>,
+    ),
+    /// Resizes the terminal.
+    Resize { cols: u16, rows: u16 },
+    /// Sent by the home desktop once the shell exits,
+    /// or if it couldn't be started. `error` is only
+    /// set in the latter case.
+    Exit {
+        success: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<ErrorMsg>,
+    },
 }
 
-/// Serializes a packet to a binary message
-/// containing BSON.
-pub fn serialize_packet<P>(packet: &P) -> Result<Message>
+/// Tunnel packets, sent in both directions over the
+/// optional `hammeregg-tunnel` WebRTC data channel,
+/// which forwards TCP connections from the remote to
+/// hosts that the home desktop can reach. Several
+/// connections share the channel, each with an id
+/// picked by the remote that is unique for the
+/// lifetime of its connection.
+///
+/// The remote opens a connection with [`Connect`],
+/// which the home desktop answers with [`Connected`],
+/// or with [`Close`] if it couldn't connect. Both sides
+/// then send [`Data`] until either side sends [`Close`].
+///
+/// [`Connect`]: TunnelPacket::Connect
+/// [`Connected`]: TunnelPacket::Connected
+/// [`Data`]: TunnelPacket::Data
+/// [`Close`]: TunnelPacket::Close
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TunnelPacket {
+    /// Asks the home desktop to connect to
+    /// `host:port`, which must be allowed by
+    /// the home desktop's settings.
+    Connect { id: u32, host: String, port: u16 },
+    /// Sent by the home desktop once it has
+    /// connected to the requested host.
+    Connected { id: u32 },
+    /// Bytes sent over a connection.
+    Data {
+        id: u32,
+        #[serde(with = "serde_bytes")]
+        #[schemars(with = "Vec<u8>")]
+        data: Vec<u8>,
+    },
+    /// Closes a connection. `error` is only set if the
+    /// connection failed, rather than being closed by
+    /// either end.
+    Close {
+        id: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<ErrorMsg>,
+    },
+}
+
+impl TunnelPacket {
+    /// Gets the id of the connection
+    /// that this packet belongs to.
+    pub fn id(&self) -> u32 {
+        match self {
+            TunnelPacket::Connect { id, .. }
+            | TunnelPacket::Connected { id }
+            | TunnelPacket::Data { id, .. }
+            | TunnelPacket::Close { id, .. } => *id,
+        }
+    }
+}
+
+/// Serializes a packet to bytes using
+/// the given codec.
+pub fn serialize_packet<C, P>(codec: &C, packet: &P) -> Result<Vec<u8>>
 where
+    C: PacketCodec,
     P: Serialize,
 {
-    Ok(Message::Binary(
-        bson::to_vec(packet).context("Failed to serialize packet")?,
-    ))
+    codec.encode(packet)
 }
 
-/// Deserializes a packet from a binary message
-/// containing BSON.
-pub fn deserialize_packet<'a, P>(data: &'a Message) -> Result<P>
+/// Deserializes a packet from bytes using
+/// the given codec.
+pub fn deserialize_packet<'a, C, P>(codec: &C, bytes: &'a [u8]) -> Result<P>
 where
+    C: PacketCodec,
     P: Deserialize<'a>,
 {
-    match data {
-        Message::Binary(bytes) => bson::from_slice(bytes.as_slice()).context("Failed to deserialize packet"),
-        _ => Err(anyhow!("Packet must be a binary message")),
-    }
+    codec.decode(bytes)
 }
 
-/// Deserializes a packet from a binary message
-/// containing BSON and validates the packet.
-pub fn deserialize_and_validate_packet<'a, P>(data: &'a Message) -> Result<P>
+/// Deserializes a packet from bytes using
+/// the given codec and validates the packet.
+pub fn deserialize_and_validate_packet<'a, C, P>(codec: &C, bytes: &'a [u8]) -> Result<P>
 where
+    C: PacketCodec,
     P: Deserialize<'a> + Validate,
 {
-    match data {
-        Message::Binary(bytes) => {
-            let packet = bson::from_slice::<P>(bytes.as_slice()).context("Failed to deserialize packet")?;
-            packet.validate()?;
-            Ok(packet)
-        }
-        _ => Err(anyhow!("Packet must be a binary message")),
-    }
+    let packet = deserialize_packet::<C, P>(codec, bytes)?;
+    packet.validate()?;
+    Ok(packet)
 }
@@ -0,0 +1,571 @@
+//! Limits on the size and shape of packets.
+//!
+//! Every packet received from the network should be
+//! validated against these limits before it's acted
+//! on, so that a hostile peer can't make Rooster or
+//! Desktop buffer or RSA-decrypt arbitrary blobs.
+
+use std::borrow::Cow;
+
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::transfer::{FileEntry, SHA256_SIZE};
+use crate::{
+    ClipboardPacket, ContactState, ErrorMsg, FileTransferPacket, HandshakePacket, InputPacket, KeyInput,
+    TerminalPacket, TunnelPacket,
+};
+
+/// Size in bits of the RSA keys that make up
+/// Hammeregg's password.
+pub const RSA_KEY_BITS: usize = 4096;
+
+/// Size in bytes of an AES key encrypted with
+/// one of the RSA keys in Hammeregg's password.
+pub const ENCRYPTED_KEY_SIZE: usize = RSA_KEY_BITS / 8;
+
+/// AES init vector size in bytes.
+pub const AES_IV_SIZE: usize = 12;
+
+/// Size in bytes of the tag that AES-GCM
+/// appends to every encrypted payload.
+pub const AES_TAG_SIZE: usize = 16;
+
+/// Maximum size in bytes of an encrypted session
+/// description. Real session descriptions are at
+/// most a few kilobytes, even with many ICE
+/// candidates.
+pub const MAX_PAYLOAD_SIZE: usize = 32 * 1024;
+
+/// Maximum length in characters of a home name.
+pub const MAX_HOME_NAME_LEN: usize = 64;
+
+/// Maximum length in characters of the detail
+/// message in an [`ErrorMsg`].
+pub const MAX_ERROR_DETAIL_LEN: usize = 1024;
+
+/// Maximum size in bytes of the text in a
+/// [`ClipboardPacket`]. This leaves room for the rest
+/// of the packet in a single 64 KiB data channel
+/// message.
+pub const MAX_CLIPBOARD_TEXT_SIZE: usize = 60 * 1024;
+
+/// Maximum size in bytes of the text in a
+/// single [`InputPacket::Text`] packet.
+pub const MAX_INPUT_TEXT_SIZE: usize = 4 * 1024;
+
+/// Maximum number of wheel clicks to scroll by in a
+/// single [`InputPacket::MouseScroll`] packet. Some
+/// input backends scroll one click at a time.
+pub const MAX_SCROLL_STEPS: u32 = 128;
+
+/// Maximum number of pixels to move the mouse by in a
+/// single [`InputPacket::MouseMoveRelative`] packet.
+pub const MAX_MOUSE_DELTA: u32 = 8192;
+
+/// Maximum size in bytes of the data in a file chunk.
+/// Senders should send chunks of exactly this size,
+/// except for the last chunk of a file.
+pub const MAX_FILE_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Maximum size in bytes of the data in a
+/// single [`TerminalPacket::Data`] packet.
+pub const MAX_TERMINAL_DATA_SIZE: usize = 16 * 1024;
+
+/// Maximum size in bytes of the data in a
+/// single [`TunnelPacket::Data`] packet.
+pub const MAX_TUNNEL_DATA_SIZE: usize = 16 * 1024;
+
+/// Maximum length in bytes of a host name to tunnel
+/// to, which is the longest that DNS allows.
+pub const MAX_TUNNEL_HOST_LEN: usize = 253;
+
+/// Maximum length in bytes of a path in a file transfer.
+pub const MAX_TRANSFER_PATH_LEN: usize = 4096;
+
+/// Maximum length in bytes of a file name
+/// in a folder listing.
+pub const MAX_FILE_NAME_LEN: usize = 255;
+
+/// Maximum number of entries in a single folder
+/// listing packet. Together with [`MAX_FILE_NAME_LEN`]
+/// this keeps every listing packet under 64 KiB.
+pub const MAX_LISTING_ENTRIES: usize = 128;
+
+/// Maximum size in bytes of a single encoded packet.
+/// This is much larger than [`MAX_PAYLOAD_SIZE`] since
+/// byte arrays are serialized as arrays of integers,
+/// which can take up to 11 bytes per byte in BSON.
+pub const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+fn error(code: &'static str, message: impl Into<Cow<'static, str>>) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    error
+}
+
+/// Validates that a home name is between 1 and
+/// [`MAX_HOME_NAME_LEN`] characters long and only
+/// contains letters, digits, spaces, `-`, `_` and `.`.
+pub fn validate_home_name(home_name: &str) -> Result<(), ValidationError> {
+    let len = home_name.chars().count();
+    if len == 0 {
+        Err(error("home_name_empty", "Home name cannot be empty"))
+    } else if len > MAX_HOME_NAME_LEN {
+        Err(error(
+            "home_name_too_long",
+            format!("Home name cannot be longer than {} characters", MAX_HOME_NAME_LEN),
+        ))
+    } else if let Some(c) = home_name
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.')))
+    {
+        Err(error("home_name_charset", format!("Home name cannot contain {:?}", c)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Validates that a path in a file transfer is between
+/// 1 and [`MAX_TRANSFER_PATH_LEN`] bytes long and stays
+/// inside the shared folder. Paths must be relative and
+/// `/`-separated, and can't contain empty, `.` or `..`
+/// components or any `\`, `:` or NUL characters.
+pub fn validate_transfer_path(path: &str) -> Result<(), ValidationError> {
+    if path.is_empty() {
+        Err(error("path_empty", "Path cannot be empty"))
+    } else if path.len() > MAX_TRANSFER_PATH_LEN {
+        Err(error(
+            "path_too_long",
+            format!("Path cannot be longer than {} bytes", MAX_TRANSFER_PATH_LEN),
+        ))
+    } else if let Some(c) = path.chars().find(|c| matches!(c, '\\' | ':' | '\0')) {
+        Err(error("path_charset", format!("Path cannot contain {:?}", c)))
+    } else if path.split('/').any(|component| matches!(component, "" | "." | "..")) {
+        Err(error(
+            "path_components",
+            "Path must be relative and cannot contain empty, . or .. components",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Validates that a file name in a folder listing is
+/// between 1 and [`MAX_FILE_NAME_LEN`] bytes long, isn't
+/// `.` or `..`, and doesn't contain any characters that
+/// [`validate_transfer_path`] would reject.
+pub fn validate_file_name(name: &str) -> Result<(), ValidationError> {
+    if name.is_empty() {
+        Err(error("name_empty", "File name cannot be empty"))
+    } else if name.len() > MAX_FILE_NAME_LEN {
+        Err(error(
+            "name_too_long",
+            format!("File name cannot be longer than {} bytes", MAX_FILE_NAME_LEN),
+        ))
+    } else if let Some(c) = name.chars().find(|c| matches!(c, '/' | '\\' | ':' | '\0')) {
+        Err(error("name_charset", format!("File name cannot contain {:?}", c)))
+    } else if matches!(name, "." | "..") {
+        Err(error("name_dots", "File name cannot be . or .."))
+    } else {
+        Ok(())
+    }
+}
+
+/// Validates that an encrypted key, init vector
+/// and payload are all sensibly sized.
+fn validate_encrypted(errors: &mut ValidationErrors, key: &[u8], iv: &[u8], payload: &[u8]) {
+    if key.len() != ENCRYPTED_KEY_SIZE {
+        errors.add(
+            "key",
+            error(
+                "key_length",
+                format!("Encrypted key must be {} bytes long", ENCRYPTED_KEY_SIZE),
+            ),
+        );
+    }
+    validate_encrypted_payload(errors, iv, payload);
+}
+
+/// Validates that an init vector and a payload
+/// encrypted with an already exchanged AES key
+/// are sensibly sized.
+fn validate_encrypted_payload(errors: &mut ValidationErrors, iv: &[u8], payload: &[u8]) {
+    if iv.len() != AES_IV_SIZE {
+        errors.add(
+            "iv",
+            error("iv_length", format!("Init vector must be {} bytes long", AES_IV_SIZE)),
+        );
+    }
+    if payload.len() < AES_TAG_SIZE || payload.len() > MAX_PAYLOAD_SIZE {
+        errors.add(
+            "payload",
+            error(
+                "payload_length",
+                format!(
+                    "Payload must be between {} and {} bytes long",
+                    AES_TAG_SIZE, MAX_PAYLOAD_SIZE
+                ),
+            ),
+        );
+    }
+}
+
+impl Validate for ErrorMsg {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(detail) = &self.detail {
+            if detail.chars().count() > MAX_ERROR_DETAIL_LEN {
+                errors.add(
+                    "detail",
+                    error(
+                        "detail_too_long",
+                        format!("Error detail cannot be longer than {} characters", MAX_ERROR_DETAIL_LEN),
+                    ),
+                );
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Validate for HandshakePacket {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self {
+            HandshakePacket::HomeInit { home_name } | HandshakePacket::RemoteInit { home_name } => {
+                if let Err(err) = validate_home_name(home_name) {
+                    errors.add("home_name", err);
+                }
+            }
+            HandshakePacket::HomeInitResponse { response, .. }
+            | HandshakePacket::RemoteInitResponse { response, .. } => {
+                if let Err(error) = response {
+                    return ValidationErrors::merge(Ok(()), "response", error.validate());
+                }
+            }
+            HandshakePacket::RemoteOffer { key, iv, payload, .. }
+            | HandshakePacket::HomeAnswerSuccess { key, iv, payload, .. } => {
+                validate_encrypted(&mut errors, key, iv, payload);
+            }
+            HandshakePacket::RemoteIceCandidate { iv, payload, .. }
+            | HandshakePacket::HomeIceCandidate { iv, payload, .. } => {
+                validate_encrypted_payload(&mut errors, iv, payload);
+            }
+            HandshakePacket::HomeAnswerFailure { error, .. } => {
+                return ValidationErrors::merge(Ok(()), "error", error.validate());
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Validates that a touch or pen is at finite
+/// coordinates with a pressure between 0 and 1.
+fn validate_contact(errors: &mut ValidationErrors, field: &'static str, x: f32, y: f32, pressure: f32) {
+    if !x.is_finite() || !y.is_finite() {
+        errors.add(
+            field,
+            error("contact_finite", "Touch and pen coordinates must be finite"),
+        );
+    }
+    if !(0.0..=1.0).contains(&pressure) {
+        errors.add(
+            field,
+            error("contact_pressure_range", "Pressure must be between 0 and 1"),
+        );
+    }
+}
+
+impl Validate for InputPacket {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self {
+            InputPacket::KeyDown(KeyInput::AlphaKey(c)) | InputPacket::KeyUp(KeyInput::AlphaKey(c)) => {
+                if c.is_control() {
+                    errors.add(
+                        "alpha_key",
+                        error("alpha_key_control", "Alpha keys cannot be control characters"),
+                    );
+                }
+            }
+            InputPacket::MouseMove { x, y } => {
+                if !x.is_finite() || !y.is_finite() {
+                    errors.add(
+                        "mouse_move",
+                        error("mouse_move_finite", "Mouse coordinates must be finite"),
+                    );
+                }
+            }
+            InputPacket::MouseMoveRelative { dx, dy } => {
+                if dx.unsigned_abs() > MAX_MOUSE_DELTA || dy.unsigned_abs() > MAX_MOUSE_DELTA {
+                    errors.add(
+                        "mouse_move_relative",
+                        error(
+                            "mouse_move_relative_range",
+                            format!("Mouse cannot move by more than {} pixels", MAX_MOUSE_DELTA),
+                        ),
+                    );
+                }
+            }
+            InputPacket::MouseScroll { x, y } => {
+                if x.unsigned_abs() > MAX_SCROLL_STEPS || y.unsigned_abs() > MAX_SCROLL_STEPS {
+                    errors.add(
+                        "mouse_scroll",
+                        error(
+                            "mouse_scroll_range",
+                            format!("Mouse cannot scroll by more than {} steps", MAX_SCROLL_STEPS),
+                        ),
+                    );
+                }
+            }
+            InputPacket::Text(text) => {
+                if text.is_empty() {
+                    errors.add("text", error("text_empty", "Text cannot be empty"));
+                } else if text.len() > MAX_INPUT_TEXT_SIZE {
+                    errors.add(
+                        "text",
+                        error(
+                            "text_too_long",
+                            format!("Text cannot be longer than {} bytes", MAX_INPUT_TEXT_SIZE),
+                        ),
+                    );
+                } else if text.chars().any(|c| c.is_control() && c != '\n' && c != '\t') {
+                    errors.add("text", error("text_control", "Text cannot contain control characters"));
+                }
+            }
+            InputPacket::Touch {
+                x, y, pressure, state, ..
+            } => {
+                validate_contact(&mut errors, "touch", *x, *y, *pressure);
+                if *state == ContactState::Hover {
+                    errors.add("touch", error("touch_hover", "Touches cannot hover"));
+                }
+            }
+            InputPacket::Pen {
+                x,
+                y,
+                pressure,
+                tilt_x,
+                tilt_y,
+                ..
+            } => {
+                validate_contact(&mut errors, "pen", *x, *y, *pressure);
+                if !(-90.0..=90.0).contains(tilt_x) || !(-90.0..=90.0).contains(tilt_y) {
+                    errors.add(
+                        "pen",
+                        error("pen_tilt_range", "Pen tilt must be between -90 and 90 degrees"),
+                    );
+                }
+            }
+            InputPacket::GamepadAxis { axis, value } => {
+                let min = if axis.is_trigger() { 0.0 } else { -1.0 };
+                if !(min..=1.0).contains(value) {
+                    errors.add(
+                        "gamepad_axis",
+                        error(
+                            "gamepad_axis_range",
+                            format!("Gamepad axis {:?} must be between {} and 1", axis, min),
+                        ),
+                    );
+                }
+            }
+            _ => {}
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Validate for ClipboardPacket {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self {
+            ClipboardPacket::Text(text) => {
+                if text.len() > MAX_CLIPBOARD_TEXT_SIZE {
+                    errors.add(
+                        "text",
+                        error(
+                            "text_too_long",
+                            format!("Clipboard text cannot be longer than {} bytes", MAX_CLIPBOARD_TEXT_SIZE),
+                        ),
+                    );
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Validate for FileEntry {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Err(err) = validate_file_name(&self.name) {
+            errors.add("name", err);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Validate for FileTransferPacket {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self {
+            FileTransferPacket::Download { path, .. }
+            | FileTransferPacket::Upload { path, .. }
+            | FileTransferPacket::Stat { path, .. } => {
+                if let Err(err) = validate_transfer_path(path) {
+                    errors.add("path", err);
+                }
+            }
+            FileTransferPacket::List { path, .. } => {
+                // An empty path lists the shared folders
+                if !path.is_empty() {
+                    if let Err(err) = validate_transfer_path(path) {
+                        errors.add("path", err);
+                    }
+                }
+            }
+            FileTransferPacket::Listing { entries, .. } => {
+                if entries.len() > MAX_LISTING_ENTRIES {
+                    errors.add(
+                        "entries",
+                        error(
+                            "entries_too_long",
+                            format!("Listings cannot have more than {} entries", MAX_LISTING_ENTRIES),
+                        ),
+                    );
+                } else if let Some(result) = entries.iter().map(FileEntry::validate).find(Result::is_err) {
+                    return ValidationErrors::merge(Ok(()), "entries", result);
+                }
+            }
+            FileTransferPacket::Entry { entry, .. } => {
+                return ValidationErrors::merge(Ok(()), "entry", entry.validate());
+            }
+            FileTransferPacket::Chunk { data, sha256, .. } => {
+                if data.len() > MAX_FILE_CHUNK_SIZE {
+                    errors.add(
+                        "data",
+                        error(
+                            "data_too_long",
+                            format!("Chunks cannot be longer than {} bytes", MAX_FILE_CHUNK_SIZE),
+                        ),
+                    );
+                }
+                if sha256.len() != SHA256_SIZE {
+                    errors.add(
+                        "sha256",
+                        error("sha256_length", format!("Hash must be {} bytes long", SHA256_SIZE)),
+                    );
+                }
+            }
+            FileTransferPacket::Cancel { error: Some(error), .. } => {
+                return ValidationErrors::merge(Ok(()), "error", error.validate());
+            }
+            _ => {}
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Validate for TerminalPacket {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self {
+            TerminalPacket::Open { cols, rows } | TerminalPacket::Resize { cols, rows } => {
+                if *cols == 0 || *rows == 0 {
+                    errors.add(
+                        "size",
+                        error("size_empty", "Terminals must have at least one row and column"),
+                    );
+                }
+            }
+            TerminalPacket::Data(data) => {
+                if data.len() > MAX_TERMINAL_DATA_SIZE {
+                    errors.add(
+                        "data",
+                        error(
+                            "data_too_long",
+                            format!("Terminal data cannot be longer than {} bytes", MAX_TERMINAL_DATA_SIZE),
+                        ),
+                    );
+                }
+            }
+            TerminalPacket::Exit { error: Some(error), .. } => {
+                return ValidationErrors::merge(Ok(()), "error", error.validate());
+            }
+            _ => {}
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Validate for TunnelPacket {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self {
+            TunnelPacket::Connect { host, port, .. } => {
+                if host.is_empty() {
+                    errors.add("host", error("host_empty", "Host cannot be empty"));
+                } else if host.len() > MAX_TUNNEL_HOST_LEN {
+                    errors.add(
+                        "host",
+                        error(
+                            "host_too_long",
+                            format!("Host cannot be longer than {} bytes", MAX_TUNNEL_HOST_LEN),
+                        ),
+                    );
+                }
+                if *port == 0 {
+                    errors.add("port", error("port_zero", "Port cannot be 0"));
+                }
+            }
+            TunnelPacket::Data { data, .. } => {
+                if data.len() > MAX_TUNNEL_DATA_SIZE {
+                    errors.add(
+                        "data",
+                        error(
+                            "data_too_long",
+                            format!("Tunnel data cannot be longer than {} bytes", MAX_TUNNEL_DATA_SIZE),
+                        ),
+                    );
+                }
+            }
+            TunnelPacket::Close { error: Some(error), .. } => {
+                return ValidationErrors::merge(Ok(()), "error", error.validate());
+            }
+            _ => {}
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
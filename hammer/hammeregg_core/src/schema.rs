@@ -0,0 +1,219 @@
+//! A JSON Schema describing every packet in the
+//! Hammeregg protocol, for use by alternative
+//! clients.
+//!
+//! The schema is checked in at
+//! `hammeregg_core/schema/protocol.schema.json`,
+//! along with the TypeScript types that the `wasm`
+//! bindings give Egg, which are generated from it.
+//! After changing any packet, regenerate both with
+//!
+//! ```sh
+//! cargo run -p hammeregg_core --bin export_schema
+//! ```
+
+use std::fmt::Write;
+
+use schemars::gen::SchemaSettings;
+use schemars::schema::{Metadata, RootSchema, SchemaObject};
+use serde_json::{Map, Value};
+
+use crate::{
+    ClipboardPacket, ContactState, FileTransferPacket, GamepadAxis, GamepadButton, HandshakeInitPacket,
+    HandshakePacket, InputPacket, KeyCode, KeyInput, MouseButton, SpecialKeyInput, TerminalPacket, TunnelPacket,
+};
+
+/// Path to the checked-in schema, relative
+/// to the `hammeregg_core` crate root.
+pub const SCHEMA_PATH: &str = "schema/protocol.schema.json";
+
+/// Path to the checked-in TypeScript types, relative
+/// to the `hammeregg_core` crate root.
+pub const TYPESCRIPT_PATH: &str = "src/wasm_types.rs";
+
+/// Generates a schema that defines every packet
+/// type in the protocol. The root schema doesn't
+/// match anything on its own, instead clients
+/// should refer to its `definitions`.
+pub fn protocol_schema() -> RootSchema {
+    let settings = SchemaSettings::draft07();
+    let meta_schema = settings.meta_schema.clone();
+    let mut gen = settings.into_generator();
+    gen.subschema_for::<HandshakeInitPacket>();
+    gen.subschema_for::<HandshakePacket>();
+    gen.subschema_for::<InputPacket>();
+    gen.subschema_for::<KeyInput>();
+    gen.subschema_for::<KeyCode>();
+    gen.subschema_for::<MouseButton>();
+    gen.subschema_for::<GamepadButton>();
+    gen.subschema_for::<GamepadAxis>();
+    gen.subschema_for::<ContactState>();
+    gen.subschema_for::<SpecialKeyInput>();
+    gen.subschema_for::<ClipboardPacket>();
+    gen.subschema_for::<FileTransferPacket>();
+    gen.subschema_for::<TerminalPacket>();
+    gen.subschema_for::<TunnelPacket>();
+
+    RootSchema {
+        meta_schema,
+        schema: SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                title: Some("Hammeregg Protocol".to_string()),
+                description: Some(format!(
+                    "Packets sent between Hammeregg Desktop, Rooster and Egg, as of protocol version {:#010x}.",
+                    crate::SUPPORTED_VERSIONS.end()
+                )),
+                ..Default::default()
+            })),
+            ..Default::default()
+        },
+        definitions: gen.take_definitions(),
+    }
+}
+
+/// Renders [`protocol_schema`] exactly as
+/// it's written to [`SCHEMA_PATH`].
+pub fn protocol_schema_json() -> String {
+    let mut json = serde_json::to_string_pretty(&protocol_schema()).expect("schema is always serializable");
+    json.push('\n');
+    json
+}
+
+/// Renders every definition in [`protocol_schema`]
+/// as a TypeScript type, wrapped in a
+/// `typescript_custom_section` exactly as it's
+/// written to [`TYPESCRIPT_PATH`].
+pub fn protocol_typescript() -> String {
+    let schema = serde_json::to_value(&protocol_schema()).expect("schema is always serializable");
+    let mut types = String::new();
+    for (name, definition) in schema["definitions"].as_object().expect("schema has definitions") {
+        types.push('\n');
+        write_doc_comment(&mut types, definition, "");
+        if let Some(properties) = definition.get("properties").and_then(Value::as_object) {
+            writeln!(types, "export interface {} {{", name).unwrap();
+            for (property, schema) in properties {
+                write_doc_comment(&mut types, schema, "    ");
+                writeln!(types, "    {}: {}", property_key(definition, property), ts_type(schema)).unwrap();
+            }
+            types.push_str("}\n");
+        } else {
+            let members = ts_union_members(definition);
+            if members.len() > 1 {
+                writeln!(types, "export type {} =", name).unwrap();
+                for member in members {
+                    writeln!(types, "    | {}", member).unwrap();
+                }
+            } else {
+                writeln!(types, "export type {} = {}", name, members.join("")).unwrap();
+            }
+        }
+    }
+    assert!(!types.contains("\"#"), "TypeScript types can't be put in a raw string");
+
+    format!(
+        "// Generated from the protocol schema by `export_schema`, don't edit it by hand.\n\n\
+         #[wasm_bindgen(typescript_custom_section)]\n\
+         const PROTOCOL_TYPES: &str = r#\"{}\"#;\n",
+        types
+    )
+}
+
+/// Writes a schema's description as a doc comment.
+fn write_doc_comment(out: &mut String, schema: &Value, indent: &str) {
+    if let Some(description) = schema.get("description").and_then(Value::as_str) {
+        let description = description.replace("*/", "*\\/");
+        if description.contains('\n') {
+            writeln!(out, "{}/**", indent).unwrap();
+            for line in description.lines() {
+                writeln!(out, "{} *{}{}", indent, if line.is_empty() { "" } else { " " }, line).unwrap();
+            }
+            writeln!(out, "{} */", indent).unwrap();
+        } else {
+            writeln!(out, "{}/** {} */", indent, description).unwrap();
+        }
+    }
+}
+
+/// Renders the key of an object property, which
+/// is optional unless the schema requires it.
+fn property_key(object: &Value, property: &str) -> String {
+    let required = object
+        .get("required")
+        .and_then(Value::as_array)
+        .map_or(false, |required| required.iter().any(|name| name == property));
+    if required {
+        property.to_string()
+    } else {
+        format!("{}?", property)
+    }
+}
+
+/// Renders a schema as a TypeScript type.
+fn ts_type(schema: &Value) -> String {
+    ts_union_members(schema).join(" | ")
+}
+
+/// Renders each of the types that a schema
+/// matches, so that unions can be split
+/// across several lines.
+fn ts_union_members(schema: &Value) -> Vec<String> {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        return vec![reference.trim_start_matches("#/definitions/").to_string()];
+    }
+    if let Some([schema]) = schema.get("allOf").and_then(Value::as_array).map(Vec::as_slice) {
+        return ts_union_members(schema);
+    }
+    if let Some(schemas) = schema
+        .get("oneOf")
+        .or_else(|| schema.get("anyOf"))
+        .and_then(Value::as_array)
+    {
+        return schemas.iter().flat_map(ts_union_members).collect();
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return values.iter().map(Value::to_string).collect();
+    }
+    match schema.get("type") {
+        Some(Value::String(instance_type)) => vec![ts_instance_type(schema, instance_type)],
+        Some(Value::Array(instance_types)) => instance_types
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|instance_type| ts_instance_type(schema, instance_type))
+            .collect(),
+        _ => vec!["unknown".to_string()],
+    }
+}
+
+/// Renders one of the JSON instance types
+/// that a schema matches.
+fn ts_instance_type(schema: &Value, instance_type: &str) -> String {
+    match instance_type {
+        "null" => "null".to_string(),
+        "boolean" => "boolean".to_string(),
+        "integer" | "number" => "number".to_string(),
+        "string" => "string".to_string(),
+        "array" => match schema.get("items") {
+            Some(items) if items.is_object() => match ts_union_members(items).as_slice() {
+                [item] => format!("{}[]", item),
+                items => format!("({})[]", items.join(" | ")),
+            },
+            _ => "unknown[]".to_string(),
+        },
+        "object" => {
+            let empty = Map::new();
+            let properties = schema.get("properties").and_then(Value::as_object).unwrap_or(&empty);
+            if properties.is_empty() {
+                return match schema.get("additionalProperties") {
+                    Some(values) if values.is_object() => format!("{{ [key: string]: {} }}", ts_type(values)),
+                    _ => "{}".to_string(),
+                };
+            }
+            let properties = properties
+                .iter()
+                .map(|(property, value)| format!("{}: {}", property_key(schema, property), ts_type(value)))
+                .collect::<Vec<_>>();
+            format!("{{ {} }}", properties.join("; "))
+        }
+        _ => "unknown".to_string(),
+    }
+}
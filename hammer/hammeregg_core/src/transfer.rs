@@ -0,0 +1,175 @@
+//! File transfer packets, sent in both directions
+//! over the `hammeregg-files` WebRTC data channel.
+//!
+//! Transfers are always started by the remote, which
+//! picks an id for each transfer that is unique for
+//! the lifetime of its connection. Files are sent in
+//! [`MAX_FILE_CHUNK_SIZE`] chunks, each with its own
+//! offset and SHA-256 hash, so that an interrupted
+//! transfer can be resumed from the last good chunk.
+//!
+//! A download goes like this:
+//! 1. The remote sends [`Download`] with the offset
+//!    to start at, which is 0 for a new download.
+//! 2. The home desktop answers with [`Accepted`] and
+//!    then sends every [`Chunk`] after that offset.
+//! 3. The remote answers with [`Complete`] once it
+//!    has written the last chunk.
+//!
+//! An upload goes the other way around:
+//! 1. The remote sends [`Upload`] with the size of
+//!    the whole file.
+//! 2. The home desktop answers with [`Accepted`],
+//!    whose offset is how much of the file it kept
+//!    from an earlier attempt.
+//! 3. The remote sends every [`Chunk`] after that
+//!    offset, and the home desktop answers with
+//!    [`Complete`] once it has written the last one.
+//!
+//! Either side can send [`Cancel`] at any point, after
+//! which the other side must stop sending chunks.
+//!
+//! The remote can also browse the shared folders before
+//! picking a file. It sends [`List`] to list a folder,
+//! which the home desktop answers with one or more
+//! [`Listing`] packets, or [`Stat`] to look up a single
+//! file, which the home desktop answers with [`Entry`].
+//! Failed requests are answered with [`Cancel`].
+//!
+//! [`Download`]: FileTransferPacket::Download
+//! [`Upload`]: FileTransferPacket::Upload
+//! [`Accepted`]: FileTransferPacket::Accepted
+//! [`Chunk`]: FileTransferPacket::Chunk
+//! [`Complete`]: FileTransferPacket::Complete
+//! [`Cancel`]: FileTransferPacket::Cancel
+//! [`List`]: FileTransferPacket::List
+//! [`Listing`]: FileTransferPacket::Listing
+//! [`Stat`]: FileTransferPacket::Stat
+//! [`Entry`]: FileTransferPacket::Entry
+//! [`MAX_FILE_CHUNK_SIZE`]: crate::limits::MAX_FILE_CHUNK_SIZE
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::ErrorMsg;
+
+/// Size in bytes of a SHA-256 hash.
+pub const SHA256_SIZE: usize = 32;
+
+/// A file or folder in one of the shared folders.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Debug)]
+pub struct FileEntry {
+    /// The name of the file, without the
+    /// path of the folder that it's in.
+    pub name: String,
+    pub is_dir: bool,
+    /// The size of the file in bytes,
+    /// which is 0 for folders.
+    pub size: u64,
+    /// When the file was last modified, in
+    /// milliseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+}
+
+/// File transfer packets. The home desktop can share
+/// several folders, so the first component of every
+/// path is the name of a shared folder and the rest
+/// is relative to that folder, with `/` as the
+/// separator. Only `List` can refer to a shared
+/// folder itself.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FileTransferPacket {
+    /// Asks the home desktop to send a file,
+    /// starting at `offset`.
+    Download { id: u32, path: String, offset: u64 },
+    /// Asks the home desktop to receive a file
+    /// that is `size` bytes long.
+    Upload { id: u32, path: String, size: u64 },
+    /// Accepts a transfer. `size` is the size of
+    /// the whole file, and `offset` is where the
+    /// first chunk will start.
+    Accepted { id: u32, size: u64, offset: u64 },
+    /// A piece of a file starting at `offset`.
+    Chunk {
+        id: u32,
+        offset: u64,
+        #[serde(with = "serde_bytes")]
+        #[schemars(with = "Vec<u8>")]
+        data: Vec<u8>,
+        /// The SHA-256 hash of `data`.
+        #[serde(with = "serde_bytes")]
+        #[schemars(with = "Vec<u8>")]
+        sha256: Vec<u8>,
+    },
+    /// Sent by the receiver of a file once it
+    /// has written every chunk.
+    Complete { id: u32 },
+    /// Asks the home desktop to list a folder. An
+    /// empty path lists the shared folders themselves.
+    List { id: u32, path: String },
+    /// Part of a folder listing. Listings longer than
+    /// [`MAX_LISTING_ENTRIES`] are split across several
+    /// packets, and `done` is only set on the last one.
+    ///
+    /// [`MAX_LISTING_ENTRIES`]: crate::limits::MAX_LISTING_ENTRIES
+    Listing {
+        id: u32,
+        entries: Vec<FileEntry>,
+        done: bool,
+    },
+    /// Asks the home desktop to look up
+    /// a single file or folder.
+    Stat { id: u32, path: String },
+    /// The file or folder asked for by a [`Stat`].
+    ///
+    /// [`Stat`]: FileTransferPacket::Stat
+    Entry { id: u32, entry: FileEntry },
+    /// Stops a transfer. `error` is only set if
+    /// the transfer failed, rather than being
+    /// cancelled by the user.
+    Cancel {
+        id: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<ErrorMsg>,
+    },
+}
+
+impl FileTransferPacket {
+    /// Creates a [`Chunk`] packet, hashing its data.
+    ///
+    /// [`Chunk`]: FileTransferPacket::Chunk
+    pub fn chunk(id: u32, offset: u64, data: Vec<u8>) -> Self {
+        let sha256 = Sha256::digest(&data).to_vec();
+        Self::Chunk {
+            id,
+            offset,
+            data,
+            sha256,
+        }
+    }
+
+    /// Gets the id of the transfer that
+    /// this packet belongs to.
+    pub fn id(&self) -> u32 {
+        match self {
+            FileTransferPacket::Download { id, .. }
+            | FileTransferPacket::Upload { id, .. }
+            | FileTransferPacket::Accepted { id, .. }
+            | FileTransferPacket::Chunk { id, .. }
+            | FileTransferPacket::Complete { id }
+            | FileTransferPacket::Cancel { id, .. }
+            | FileTransferPacket::List { id, .. }
+            | FileTransferPacket::Listing { id, .. }
+            | FileTransferPacket::Stat { id, .. }
+            | FileTransferPacket::Entry { id, .. } => *id,
+        }
+    }
+}
+
+/// Checks that a chunk's data matches its hash.
+pub fn verify_chunk(data: &[u8], sha256: &[u8]) -> bool {
+    Sha256::digest(data).as_slice() == sha256
+}
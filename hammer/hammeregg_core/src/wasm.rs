@@ -0,0 +1,280 @@
+//! WebAssembly bindings for Egg.
+//!
+//! Packets cross the boundary as plain JavaScript
+//! objects that look exactly like their serde
+//! representation, and leave it as bytes ready to be
+//! sent over a WebSocket or WebRTC data channel.
+//! `hammeregg_wasm` builds these bindings into
+//! the module that Egg loads.
+
+use std::fmt::Display;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use validator::Validate;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::codec::BsonCodec;
+use crate::{
+    deserialize_and_validate_packet, deserialize_packet, limits, serialize_packet, transfer, ClipboardPacket, Codec,
+    ErrorMsg, FileTransferPacket, HandshakeInitPacket, HandshakePacket, InputPacket, KeyCode, RemotePassword,
+    SpecialKeyInput, TerminalPacket, TunnelPacket, DEFAULT_HAMMEREGG_PORT, SUPPORTED_CODECS, SUPPORTED_VERSIONS,
+};
+
+// Every packet type, generated from the protocol's
+// JSON Schema by `export_schema`.
+include!("wasm_types.rs");
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export interface RemotePassword {
+    home_public_key: string
+    remote_private_key: string
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "Codec")]
+    pub type JsCodec;
+    #[wasm_bindgen(typescript_type = "Codec[]")]
+    pub type JsCodecs;
+    #[wasm_bindgen(typescript_type = "ErrorMsg | string")]
+    pub type JsErrorMsg;
+    #[wasm_bindgen(typescript_type = "HandshakePacket")]
+    pub type JsHandshakePacket;
+    #[wasm_bindgen(typescript_type = "InputPacket")]
+    pub type JsInputPacket;
+    #[wasm_bindgen(typescript_type = "SpecialKeyInput[]")]
+    pub type JsSpecialKeys;
+    #[wasm_bindgen(typescript_type = "KeyCode[]")]
+    pub type JsKeyCodes;
+    #[wasm_bindgen(typescript_type = "ClipboardPacket")]
+    pub type JsClipboardPacket;
+    #[wasm_bindgen(typescript_type = "FileTransferPacket")]
+    pub type JsFileTransferPacket;
+    #[wasm_bindgen(typescript_type = "TerminalPacket")]
+    pub type JsTerminalPacket;
+    #[wasm_bindgen(typescript_type = "TunnelPacket")]
+    pub type JsTunnelPacket;
+    #[wasm_bindgen(typescript_type = "RemotePassword")]
+    pub type JsRemotePassword;
+}
+
+/// Converts any error into a JavaScript exception.
+fn to_js_error<E: Display>(err: E) -> JsValue {
+    JsValue::from_str(&format!("{:#}", err))
+}
+
+/// Converts a JavaScript object into its Rust equivalent.
+fn from_js<T: DeserializeOwned>(value: &JsValue) -> Result<T, JsValue> {
+    value.into_serde().map_err(to_js_error)
+}
+
+/// Converts a Rust value into its JavaScript equivalent.
+fn to_js<T: Serialize, U: JsCast>(value: &T) -> Result<U, JsValue> {
+    Ok(JsValue::from_serde(value).map_err(to_js_error)?.unchecked_into())
+}
+
+/// The default port for Hammeregg signalling.
+#[wasm_bindgen(js_name = defaultHammereggPort)]
+pub fn default_hammeregg_port() -> u16 {
+    DEFAULT_HAMMEREGG_PORT
+}
+
+/// The codecs that Egg supports, in order of preference.
+#[wasm_bindgen(js_name = supportedCodecs)]
+pub fn supported_codecs() -> Result<JsCodecs, JsValue> {
+    to_js(&SUPPORTED_CODECS)
+}
+
+/// Every special key that Hammeregg Desktop can press.
+#[wasm_bindgen(js_name = specialKeys)]
+pub fn special_keys() -> Result<JsSpecialKeys, JsValue> {
+    to_js(&SpecialKeyInput::ALL.to_vec())
+}
+
+/// Every physical key that Hammeregg Desktop can press.
+#[wasm_bindgen(js_name = keyCodes)]
+pub fn key_codes() -> Result<JsKeyCodes, JsValue> {
+    to_js(&KeyCode::ALL)
+}
+
+/// Checks whether a home name is valid, returning
+/// a description of the problem if it isn't.
+#[wasm_bindgen(js_name = validateHomeName)]
+pub fn validate_home_name(home_name: &str) -> Option<String> {
+    limits::validate_home_name(home_name)
+        .err()
+        .map(|err| err.message.unwrap_or_default().into_owned())
+}
+
+/// Wraps a `RemoteInit` or `HomeInit` packet in a
+/// [`HandshakeInitPacket`] that advertises every
+/// supported version and codec, and serializes it.
+#[wasm_bindgen(js_name = encodeInitPacket)]
+pub fn encode_init_packet(packet: JsHandshakePacket) -> Result<Vec<u8>, JsValue> {
+    let packet = from_js::<HandshakePacket>(&packet)?;
+    let init_packet = HandshakeInitPacket::new(SUPPORTED_VERSIONS, packet).map_err(to_js_error)?;
+    serialize_packet(&BsonCodec, &init_packet).map_err(to_js_error)
+}
+
+/// Serializes a [`HandshakePacket`] with the given codec.
+#[wasm_bindgen(js_name = encodeHandshakePacket)]
+pub fn encode_handshake_packet(codec: JsCodec, packet: JsHandshakePacket) -> Result<Vec<u8>, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = from_js::<HandshakePacket>(&packet)?;
+    serialize_packet(&codec, &packet).map_err(to_js_error)
+}
+
+/// Deserializes a [`HandshakePacket`] with the given codec.
+#[wasm_bindgen(js_name = decodeHandshakePacket)]
+pub fn decode_handshake_packet(codec: JsCodec, bytes: &[u8]) -> Result<JsHandshakePacket, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = deserialize_packet::<_, HandshakePacket>(&codec, bytes).map_err(to_js_error)?;
+    to_js(&packet)
+}
+
+/// Serializes an [`InputPacket`] with the given codec.
+#[wasm_bindgen(js_name = encodeInputPacket)]
+pub fn encode_input_packet(codec: JsCodec, packet: JsInputPacket) -> Result<Vec<u8>, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = from_js::<InputPacket>(&packet)?;
+    serialize_packet(&codec, &packet).map_err(to_js_error)
+}
+
+/// The size in bytes of the longest text
+/// that fits in a single text input packet.
+#[wasm_bindgen(js_name = maxInputTextSize)]
+pub fn max_input_text_size() -> usize {
+    limits::MAX_INPUT_TEXT_SIZE
+}
+
+/// Validates and serializes a [`ClipboardPacket`] with
+/// the given codec. Clipboard contents can be large,
+/// so this throws if they won't fit in a single data
+/// channel message.
+#[wasm_bindgen(js_name = encodeClipboardPacket)]
+pub fn encode_clipboard_packet(codec: JsCodec, packet: JsClipboardPacket) -> Result<Vec<u8>, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = from_js::<ClipboardPacket>(&packet)?;
+    packet.validate().map_err(to_js_error)?;
+    serialize_packet(&codec, &packet).map_err(to_js_error)
+}
+
+/// Deserializes and validates a [`ClipboardPacket`]
+/// with the given codec.
+#[wasm_bindgen(js_name = decodeClipboardPacket)]
+pub fn decode_clipboard_packet(codec: JsCodec, bytes: &[u8]) -> Result<JsClipboardPacket, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = deserialize_and_validate_packet::<_, ClipboardPacket>(&codec, bytes).map_err(to_js_error)?;
+    to_js(&packet)
+}
+
+/// Serializes a [`FileTransferPacket`] with the given codec.
+#[wasm_bindgen(js_name = encodeFileTransferPacket)]
+pub fn encode_file_transfer_packet(codec: JsCodec, packet: JsFileTransferPacket) -> Result<Vec<u8>, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = from_js::<FileTransferPacket>(&packet)?;
+    serialize_packet(&codec, &packet).map_err(to_js_error)
+}
+
+/// Deserializes and validates a [`FileTransferPacket`]
+/// with the given codec.
+#[wasm_bindgen(js_name = decodeFileTransferPacket)]
+pub fn decode_file_transfer_packet(codec: JsCodec, bytes: &[u8]) -> Result<JsFileTransferPacket, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = deserialize_and_validate_packet::<_, FileTransferPacket>(&codec, bytes).map_err(to_js_error)?;
+    to_js(&packet)
+}
+
+/// Serializes a file chunk packet with the given
+/// codec, hashing its data. This skips converting the
+/// chunk to a JavaScript object, which is slow.
+#[wasm_bindgen(js_name = encodeFileChunk)]
+pub fn encode_file_chunk(codec: JsCodec, id: u32, offset: f64, data: Vec<u8>) -> Result<Vec<u8>, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = FileTransferPacket::chunk(id, offset as u64, data);
+    serialize_packet(&codec, &packet).map_err(to_js_error)
+}
+
+/// Checks that a file chunk's data matches its hash.
+#[wasm_bindgen(js_name = verifyFileChunk)]
+pub fn verify_file_chunk(data: &[u8], sha256: &[u8]) -> bool {
+    transfer::verify_chunk(data, sha256)
+}
+
+/// The size in bytes of every file chunk
+/// except for the last one in a file.
+#[wasm_bindgen(js_name = fileChunkSize)]
+pub fn file_chunk_size() -> usize {
+    limits::MAX_FILE_CHUNK_SIZE
+}
+
+/// Serializes a [`TerminalPacket`] with the given codec.
+#[wasm_bindgen(js_name = encodeTerminalPacket)]
+pub fn encode_terminal_packet(codec: JsCodec, packet: JsTerminalPacket) -> Result<Vec<u8>, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = from_js::<TerminalPacket>(&packet)?;
+    serialize_packet(&codec, &packet).map_err(to_js_error)
+}
+
+/// Serializes a terminal data packet with the given
+/// codec. This skips converting the data to a
+/// JavaScript object, which is slow.
+#[wasm_bindgen(js_name = encodeTerminalData)]
+pub fn encode_terminal_data(codec: JsCodec, data: Vec<u8>) -> Result<Vec<u8>, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    serialize_packet(&codec, &TerminalPacket::Data(data)).map_err(to_js_error)
+}
+
+/// Deserializes and validates a [`TerminalPacket`]
+/// with the given codec.
+#[wasm_bindgen(js_name = decodeTerminalPacket)]
+pub fn decode_terminal_packet(codec: JsCodec, bytes: &[u8]) -> Result<JsTerminalPacket, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = deserialize_and_validate_packet::<_, TerminalPacket>(&codec, bytes).map_err(to_js_error)?;
+    to_js(&packet)
+}
+
+/// Serializes a [`TunnelPacket`] with the given codec.
+#[wasm_bindgen(js_name = encodeTunnelPacket)]
+pub fn encode_tunnel_packet(codec: JsCodec, packet: JsTunnelPacket) -> Result<Vec<u8>, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = from_js::<TunnelPacket>(&packet)?;
+    serialize_packet(&codec, &packet).map_err(to_js_error)
+}
+
+/// Serializes a tunnel data packet with the given
+/// codec. This skips converting the data to a
+/// JavaScript object, which is slow.
+#[wasm_bindgen(js_name = encodeTunnelData)]
+pub fn encode_tunnel_data(codec: JsCodec, id: u32, data: Vec<u8>) -> Result<Vec<u8>, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    serialize_packet(&codec, &TunnelPacket::Data { id, data }).map_err(to_js_error)
+}
+
+/// Deserializes and validates a [`TunnelPacket`]
+/// with the given codec.
+#[wasm_bindgen(js_name = decodeTunnelPacket)]
+pub fn decode_tunnel_packet(codec: JsCodec, bytes: &[u8]) -> Result<JsTunnelPacket, JsValue> {
+    let codec = from_js::<Codec>(&codec)?;
+    let packet = deserialize_and_validate_packet::<_, TunnelPacket>(&codec, bytes).map_err(to_js_error)?;
+    to_js(&packet)
+}
+
+/// Deserializes a [`RemotePassword`] from the
+/// contents of a password file.
+#[wasm_bindgen(js_name = decodeRemotePassword)]
+pub fn decode_remote_password(bytes: &[u8]) -> Result<JsRemotePassword, JsValue> {
+    let password = deserialize_packet::<_, RemotePassword>(&BsonCodec, bytes).map_err(to_js_error)?;
+    to_js(&password)
+}
+
+/// Formats an [`ErrorMsg`] for display. Peers that
+/// predate error codes send errors as plain strings.
+#[wasm_bindgen(js_name = formatErrorMsg)]
+pub fn format_error_msg(error: JsErrorMsg) -> Result<String, JsValue> {
+    Ok(from_js::<ErrorMsg>(&error)?.to_string())
+}
@@ -0,0 +1,387 @@
+// Generated from the protocol schema by `export_schema`, don't edit it by hand.
+
+#[wasm_bindgen(typescript_custom_section)]
+const PROTOCOL_TYPES: &str = r#"
+/** Clipboard contents, sent in both directions over a WebRTC data channel whenever the sender's clipboard changes. Only text is synchronized for now, other kinds of contents will get their own variants. */
+export type ClipboardPacket = { text: string }
+
+/** The codecs that can be negotiated during the initial signalling handshake. */
+export type Codec =
+    | "Bson"
+    | "Cbor"
+    | "MessagePack"
+
+/** Whether a finger or pen is touching the screen. */
+export type ContactState =
+    | "Hover"
+    | "Contact"
+    | "Lifted"
+
+/** Machine-readable reasons for why a request failed, sent over the wire as part of an [`ErrorMsg`]. */
+export type ErrorCode =
+    | "UnsupportedVersion"
+    | "NameTaken"
+    | "DesktopNotFound"
+    | "DecryptFailed"
+    | "OfferInvalid"
+    | "RateLimited"
+    | "Unauthorized"
+    | "FileNotFound"
+    | "ChecksumMismatch"
+    | "Other"
+
+/** An error sent over the wire, consisting of a machine-readable [`ErrorCode`] and an optional human-readable detail message. */
+export interface ErrorMsg {
+    code: ErrorCode
+    detail?: string | null
+}
+
+/** A file or folder in one of the shared folders. */
+export interface FileEntry {
+    is_dir: boolean
+    /** When the file was last modified, in milliseconds since the Unix epoch. */
+    modified?: number | null
+    /** The name of the file, without the path of the folder that it's in. */
+    name: string
+    /** The size of the file in bytes, which is 0 for folders. */
+    size: number
+}
+
+/** File transfer packets. The home desktop can share several folders, so the first component of every path is the name of a shared folder and the rest is relative to that folder, with `/` as the separator. Only `List` can refer to a shared folder itself. */
+export type FileTransferPacket =
+    | { download: { id: number; offset: number; path: string } }
+    | { upload: { id: number; path: string; size: number } }
+    | { accepted: { id: number; offset: number; size: number } }
+    | { chunk: { data: number[]; id: number; offset: number; sha256: number[] } }
+    | { complete: { id: number } }
+    | { list: { id: number; path: string } }
+    | { listing: { done: boolean; entries: FileEntry[]; id: number } }
+    | { stat: { id: number; path: string } }
+    | { entry: { entry: FileEntry; id: number } }
+    | { cancel: { error?: ErrorMsg | null; id: number } }
+
+/** Gamepad axes. */
+export type GamepadAxis =
+    | "LeftStickX"
+    | "LeftStickY"
+    | "RightStickX"
+    | "RightStickY"
+    | "LeftTrigger"
+    | "RightTrigger"
+
+/** Gamepad buttons, named after the buttons on an Xbox controller. The triggers are axes. */
+export type GamepadButton =
+    | "A"
+    | "B"
+    | "X"
+    | "Y"
+    | "LeftBumper"
+    | "RightBumper"
+    | "Back"
+    | "Start"
+    | "Guide"
+    | "LeftStick"
+    | "RightStick"
+    | "DPadUp"
+    | "DPadDown"
+    | "DPadLeft"
+    | "DPadRight"
+
+/**
+ * Initial handshake packet, sent by both the home and remote computers to the signalling server as the first packet sent. Home computers should send an inner packet of type [`HomeInit`], and remote computers should send an inner packet of type [`RemoteInit`].
+ *
+ * The sender advertises the range of protocol versions that it supports with `min_version` and `version`. Clients that predate version negotiation only send `version`, in which case that is the only version they support.
+ *
+ * The sender also advertises the codecs that it supports in `codecs`, in order of preference. This packet and its response are always serialized with BSON.
+ */
+export interface HandshakeInitPacket {
+    codecs?: Codec[]
+    /** Always [`MAGIC`]. */
+    magic: number
+    min_version?: number | null
+    packet: HandshakePacket
+    version: number
+}
+
+/** The body of the various packet types sent over the signalling server channel. Both the [`HomeInit`] and [`RemoteInit`] packets must also be wrapped in an [`HandshakeInitPacket`]. All other packet types consist of just this enum. */
+export type HandshakePacket =
+    | { home_name: string; type: "HomeInit" }
+    | { codec?: Codec; response: Result_of_Null_or_ErrorMsg; type: "HomeInitResponse"; version?: number }
+    | { home_name: string; type: "RemoteInit" }
+    | { codec?: Codec; response: Result_of_Null_or_ErrorMsg; type: "RemoteInitResponse"; version?: number }
+    | { codec?: Codec; iv: number[]; key: number[]; payload: number[]; peer: number; type: "RemoteOffer"; version?: number }
+    | { iv: number[]; key: number[]; payload: number[]; peer: number; type: "HomeAnswerSuccess" }
+    | { error: ErrorMsg; peer: number; type: "HomeAnswerFailure" }
+    | { iv: number[]; payload: number[]; peer: number; type: "RemoteIceCandidate" }
+    | { iv: number[]; payload: number[]; peer: number; type: "HomeIceCandidate" }
+
+/** Keyboard and mouse input packets, sent by the remote computer over a WebRTC data channel. Packets are applied in the order they're sent. */
+export type InputPacket =
+    | { key_down: KeyInput }
+    | { key_up: KeyInput }
+    | { mouse_down: MouseButton }
+    | { mouse_up: MouseButton }
+    | { mouse_move: { x: number; y: number } }
+    | { mouse_move_relative: { dx: number; dy: number } }
+    | { mouse_scroll: { x: number; y: number } }
+    | { text: string }
+    | { release_all: {} }
+    | { gamepad_button: { button: GamepadButton; pressed: boolean } }
+    | { gamepad_axis: { axis: GamepadAxis; value: number } }
+    | { touch: { id: number; pressure: number; state: ContactState; x: number; y: number } }
+    | { pen: { pressure: number; state: ContactState; tilt_x: number; tilt_y: number; x: number; y: number } }
+
+/** A physical key, named after its W3C `KeyboardEvent.code` value. A key code types whatever the home computer's keyboard layout puts at that position, so `KeyQ` types `a` on an AZERTY keyboard. */
+export type KeyCode =
+    | "Backquote"
+    | "Backslash"
+    | "BracketLeft"
+    | "BracketRight"
+    | "Comma"
+    | "Digit0"
+    | "Digit1"
+    | "Digit2"
+    | "Digit3"
+    | "Digit4"
+    | "Digit5"
+    | "Digit6"
+    | "Digit7"
+    | "Digit8"
+    | "Digit9"
+    | "Equal"
+    | "IntlBackslash"
+    | "IntlRo"
+    | "IntlYen"
+    | "KeyA"
+    | "KeyB"
+    | "KeyC"
+    | "KeyD"
+    | "KeyE"
+    | "KeyF"
+    | "KeyG"
+    | "KeyH"
+    | "KeyI"
+    | "KeyJ"
+    | "KeyK"
+    | "KeyL"
+    | "KeyM"
+    | "KeyN"
+    | "KeyO"
+    | "KeyP"
+    | "KeyQ"
+    | "KeyR"
+    | "KeyS"
+    | "KeyT"
+    | "KeyU"
+    | "KeyV"
+    | "KeyW"
+    | "KeyX"
+    | "KeyY"
+    | "KeyZ"
+    | "Minus"
+    | "Period"
+    | "Quote"
+    | "Semicolon"
+    | "Slash"
+    | "AltLeft"
+    | "AltRight"
+    | "Backspace"
+    | "CapsLock"
+    | "ContextMenu"
+    | "ControlLeft"
+    | "ControlRight"
+    | "Enter"
+    | "MetaLeft"
+    | "MetaRight"
+    | "ShiftLeft"
+    | "ShiftRight"
+    | "Space"
+    | "Tab"
+    | "Convert"
+    | "KanaMode"
+    | "Lang1"
+    | "Lang2"
+    | "NonConvert"
+    | "Delete"
+    | "End"
+    | "Help"
+    | "Home"
+    | "Insert"
+    | "PageDown"
+    | "PageUp"
+    | "ArrowDown"
+    | "ArrowLeft"
+    | "ArrowRight"
+    | "ArrowUp"
+    | "NumLock"
+    | "Numpad0"
+    | "Numpad1"
+    | "Numpad2"
+    | "Numpad3"
+    | "Numpad4"
+    | "Numpad5"
+    | "Numpad6"
+    | "Numpad7"
+    | "Numpad8"
+    | "Numpad9"
+    | "NumpadAdd"
+    | "NumpadComma"
+    | "NumpadDecimal"
+    | "NumpadDivide"
+    | "NumpadEnter"
+    | "NumpadEqual"
+    | "NumpadMultiply"
+    | "NumpadSubtract"
+    | "Escape"
+    | "F1"
+    | "F2"
+    | "F3"
+    | "F4"
+    | "F5"
+    | "F6"
+    | "F7"
+    | "F8"
+    | "F9"
+    | "F10"
+    | "F11"
+    | "F12"
+    | "F13"
+    | "F14"
+    | "F15"
+    | "F16"
+    | "F17"
+    | "F18"
+    | "F19"
+    | "F20"
+    | "F21"
+    | "F22"
+    | "F23"
+    | "F24"
+    | "Pause"
+    | "PrintScreen"
+    | "ScrollLock"
+    | "AudioVolumeDown"
+    | "AudioVolumeMute"
+    | "AudioVolumeUp"
+    | "MediaPlayPause"
+    | "MediaStop"
+    | "MediaTrackNext"
+    | "MediaTrackPrevious"
+
+/** Keyboard input. */
+export type KeyInput =
+    | { special_key: SpecialKeyInput }
+    | { alpha_key: string }
+    | { raw_key: number }
+    | { code: KeyCode }
+
+/** Mouse buttons. */
+export type MouseButton =
+    | "Left"
+    | "Middle"
+    | "Right"
+
+export type Result_of_Null_or_ErrorMsg =
+    | { Ok: null }
+    | { Err: ErrorMsg }
+
+/** "Special" input keys, based on the keys that Enigo supports, followed by keys that Enigo can't press. The latter are named after their W3C `KeyboardEvent.key` values, except for the numpad keys, which are named after their `KeyboardEvent.code` values since their `key` values are the same as the main keyboard's. */
+export type SpecialKeyInput =
+    | "Alt"
+    | "Backspace"
+    | "CapsLock"
+    | "Control"
+    | "Delete"
+    | "DownArrow"
+    | "End"
+    | "Escape"
+    | "F1"
+    | "F10"
+    | "F11"
+    | "F12"
+    | "F2"
+    | "F3"
+    | "F4"
+    | "F5"
+    | "F6"
+    | "F7"
+    | "F8"
+    | "F9"
+    | "Home"
+    | "LeftArrow"
+    | "Meta"
+    | "Option"
+    | "PageDown"
+    | "PageUp"
+    | "Return"
+    | "RightArrow"
+    | "Shift"
+    | "Space"
+    | "Tab"
+    | "UpArrow"
+    | "Insert"
+    | "PrintScreen"
+    | "ScrollLock"
+    | "NumLock"
+    | "Pause"
+    | "ContextMenu"
+    | "F13"
+    | "F14"
+    | "F15"
+    | "F16"
+    | "F17"
+    | "F18"
+    | "F19"
+    | "F20"
+    | "F21"
+    | "F22"
+    | "F23"
+    | "F24"
+    | "Numpad0"
+    | "Numpad1"
+    | "Numpad2"
+    | "Numpad3"
+    | "Numpad4"
+    | "Numpad5"
+    | "Numpad6"
+    | "Numpad7"
+    | "Numpad8"
+    | "Numpad9"
+    | "NumpadAdd"
+    | "NumpadSubtract"
+    | "NumpadMultiply"
+    | "NumpadDivide"
+    | "NumpadDecimal"
+    | "NumpadEnter"
+    | "AudioVolumeMute"
+    | "AudioVolumeDown"
+    | "AudioVolumeUp"
+    | "MediaPlayPause"
+    | "MediaStop"
+    | "MediaTrackNext"
+    | "MediaTrackPrevious"
+
+/**
+ * Terminal packets, sent in both directions over the optional `hammeregg-terminal` WebRTC data channel. The remote starts a shell with [`Open`], after which both sides send [`Data`] until the home desktop sends [`Exit`].
+ *
+ * [`Open`]: TerminalPacket::Open [`Data`]: TerminalPacket::Data [`Exit`]: TerminalPacket::Exit
+ */
+export type TerminalPacket =
+    | { open: { cols: number; rows: number } }
+    | { data: number[] }
+    | { resize: { cols: number; rows: number } }
+    | { exit: { error?: ErrorMsg | null; success: boolean } }
+
+/**
+ * Tunnel packets, sent in both directions over the optional `hammeregg-tunnel` WebRTC data channel, which forwards TCP connections from the remote to hosts that the home desktop can reach. Several connections share the channel, each with an id picked by the remote that is unique for the lifetime of its connection.
+ *
+ * The remote opens a connection with [`Connect`], which the home desktop answers with [`Connected`], or with [`Close`] if it couldn't connect. Both sides then send [`Data`] until either side sends [`Close`].
+ *
+ * [`Connect`]: TunnelPacket::Connect [`Connected`]: TunnelPacket::Connected [`Data`]: TunnelPacket::Data [`Close`]: TunnelPacket::Close
+ */
+export type TunnelPacket =
+    | { connect: { host: string; id: number; port: number } }
+    | { connected: { id: number } }
+    | { data: { data: number[]; id: number } }
+    | { close: { error?: ErrorMsg | null; id: number } }
+"#;
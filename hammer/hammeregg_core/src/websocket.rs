@@ -0,0 +1,45 @@
+//! Helpers for sending packets as WebSocket messages.
+//! Packets are always sent as binary messages.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tungstenite::Message;
+use validator::Validate;
+
+use crate::{deserialize_and_validate_packet, deserialize_packet, serialize_packet, PacketCodec};
+
+/// Serializes a packet to a binary message
+/// using the given codec.
+pub fn serialize_message<C, P>(codec: &C, packet: &P) -> Result<Message>
+where
+    C: PacketCodec,
+    P: Serialize,
+{
+    Ok(Message::Binary(serialize_packet(codec, packet)?))
+}
+
+/// Deserializes a packet from a binary message
+/// using the given codec.
+pub fn deserialize_message<'a, C, P>(codec: &C, data: &'a Message) -> Result<P>
+where
+    C: PacketCodec,
+    P: Deserialize<'a>,
+{
+    match data {
+        Message::Binary(bytes) => deserialize_packet(codec, bytes),
+        _ => Err(anyhow!("Packet must be a binary message")),
+    }
+}
+
+/// Deserializes a packet from a binary message
+/// using the given codec and validates the packet.
+pub fn deserialize_and_validate_message<'a, C, P>(codec: &C, data: &'a Message) -> Result<P>
+where
+    C: PacketCodec,
+    P: Deserialize<'a> + Validate,
+{
+    match data {
+        Message::Binary(bytes) => deserialize_and_validate_packet(codec, bytes),
+        _ => Err(anyhow!("Packet must be a binary message")),
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{self, ErrorKind, Read};
+
+use hammeregg_core::frame::{read_frame, write_frame, MAX_FRAME_SIZE};
+
+/// Fails its first read with [`ErrorKind::Interrupted`],
+/// then hands out one byte at a time.
+struct InterruptedReader<R> {
+    inner: R,
+    interrupted: bool,
+}
+
+impl<R: Read> Read for InterruptedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.interrupted {
+            self.interrupted = true;
+            return Err(ErrorKind::Interrupted.into());
+        }
+        let len = buf.len().min(1);
+        self.inner.read(&mut buf[..len])
+    }
+}
+
+#[test]
+fn frames_round_trip() {
+    let mut stream = vec![];
+    write_frame(&mut stream, b"hammer").unwrap();
+    write_frame(&mut stream, b"").unwrap();
+    write_frame(&mut stream, "🥚".as_bytes()).unwrap();
+    assert_eq!(&stream[..10], b"\0\0\0\x06hammer");
+
+    let mut reader = &stream[..];
+    assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"hammer");
+    assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"");
+    assert_eq!(read_frame(&mut reader).unwrap().unwrap(), "🥚".as_bytes());
+    assert_eq!(read_frame(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn end_of_stream_between_frames_is_clean() {
+    assert_eq!(read_frame(&mut &b""[..]).unwrap(), None);
+}
+
+#[test]
+fn end_of_stream_inside_a_frame_is_an_error() {
+    // In the length prefix
+    assert!(read_frame(&mut &b"\0\0"[..]).is_err());
+    // After the length prefix, before the whole frame
+    assert!(read_frame(&mut &b"\0\0\0\x05egg"[..]).is_err());
+    assert!(read_frame(&mut &b"\0\0\0\x05"[..]).is_err());
+}
+
+#[test]
+fn oversized_frames_are_rejected() {
+    let len = MAX_FRAME_SIZE + 1;
+    assert!(read_frame(&mut &len.to_be_bytes()[..]).is_err());
+
+    let mut stream = vec![];
+    assert!(write_frame(&mut stream, &vec![0; len as usize]).is_err());
+    assert!(stream.is_empty());
+}
+
+#[test]
+fn interrupted_reads_are_retried() {
+    let mut stream = vec![];
+    write_frame(&mut stream, b"egg").unwrap();
+
+    let mut reader = InterruptedReader {
+        inner: &stream[..],
+        interrupted: false,
+    };
+    assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"egg");
+    assert_eq!(read_frame(&mut reader).unwrap(), None);
+}
@@ -0,0 +1,27 @@
+use hammeregg_core::limits::{MAX_MOUSE_DELTA, MAX_SCROLL_STEPS};
+use hammeregg_core::InputPacket;
+use validator::Validate;
+
+#[test]
+fn scrolling_is_bounded() {
+    let max = MAX_SCROLL_STEPS as i32;
+    assert!(InputPacket::MouseScroll { x: -max, y: max }.validate().is_ok());
+    assert!(InputPacket::MouseScroll { x: 0, y: max + 1 }.validate().is_err());
+    assert!(InputPacket::MouseScroll { x: -max - 1, y: 0 }.validate().is_err());
+    assert!(InputPacket::MouseScroll { x: 0, y: i32::MIN }.validate().is_err());
+}
+
+#[test]
+fn relative_mouse_movement_is_bounded() {
+    let max = MAX_MOUSE_DELTA as i32;
+    assert!(InputPacket::MouseMoveRelative { dx: max, dy: -max }.validate().is_ok());
+    assert!(InputPacket::MouseMoveRelative { dx: max + 1, dy: 0 }
+        .validate()
+        .is_err());
+    assert!(InputPacket::MouseMoveRelative { dx: 0, dy: -max - 1 }
+        .validate()
+        .is_err());
+    assert!(InputPacket::MouseMoveRelative { dx: i32::MIN, dy: 0 }
+        .validate()
+        .is_err());
+}
@@ -0,0 +1,26 @@
+use std::fs;
+use std::path::Path;
+
+use hammeregg_core::schema::{protocol_schema_json, protocol_typescript, SCHEMA_PATH, TYPESCRIPT_PATH};
+
+#[test]
+fn checked_in_schema_is_up_to_date() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(SCHEMA_PATH);
+    let checked_in = fs::read_to_string(&path).expect("failed to read checked-in schema");
+    assert!(
+        checked_in == protocol_schema_json(),
+        "{} is out of date, regenerate it with `cargo run -p hammeregg_core --bin export_schema`",
+        SCHEMA_PATH
+    );
+}
+
+#[test]
+fn checked_in_typescript_is_up_to_date() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(TYPESCRIPT_PATH);
+    let checked_in = fs::read_to_string(&path).expect("failed to read checked-in TypeScript types");
+    assert!(
+        checked_in == protocol_typescript(),
+        "{} is out of date, regenerate it with `cargo run -p hammeregg_core --bin export_schema`",
+        TYPESCRIPT_PATH
+    );
+}
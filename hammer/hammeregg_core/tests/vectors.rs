@@ -0,0 +1,648 @@
+//! Checks every packet against the golden wire-format
+//! vectors in `hammeregg_core/vectors`. Each vector holds
+//! a packet's JSON representation and its encoding in
+//! every codec. If one of these tests fails, the wire
+//! format has changed and other clients will break.
+
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::fs;
+use std::path::Path;
+
+use hammeregg_core::limits::{AES_IV_SIZE, AES_TAG_SIZE, ENCRYPTED_KEY_SIZE};
+use hammeregg_core::schema::protocol_schema;
+use hammeregg_core::{
+    ClipboardPacket, Codec, ContactState, ErrorCode, ErrorMsg, FileEntry, FileTransferPacket, GamepadAxis,
+    GamepadButton, HandshakeInitPacket, HandshakePacket, InputPacket, KeyCode, KeyInput, MouseButton, PacketCodec,
+    SpecialKeyInput, TerminalPacket, TunnelPacket, SUPPORTED_CODECS, VERSION_1_0, VERSION_1_1,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use validator::Validate;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Vector {
+    name: String,
+    value: Value,
+    encoded: Encoded,
+}
+
+/// Hex-encoded bytes for each codec.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct Encoded {
+    bson: String,
+    cbor: String,
+    message_pack: String,
+}
+
+impl Encoded {
+    fn get(&self, codec: Codec) -> &str {
+        match codec {
+            Codec::Bson => &self.bson,
+            Codec::Cbor => &self.cbor,
+            Codec::MessagePack => &self.message_pack,
+            Codec::Unknown => unreachable!(),
+        }
+    }
+}
+
+fn handshake_variant(packet: &HandshakePacket) -> &'static str {
+    match packet {
+        HandshakePacket::HomeInit { .. } => "HomeInit",
+        HandshakePacket::HomeInitResponse { .. } => "HomeInitResponse",
+        HandshakePacket::RemoteInit { .. } => "RemoteInit",
+        HandshakePacket::RemoteInitResponse { .. } => "RemoteInitResponse",
+        HandshakePacket::RemoteOffer { .. } => "RemoteOffer",
+        HandshakePacket::HomeAnswerSuccess { .. } => "HomeAnswerSuccess",
+        HandshakePacket::HomeAnswerFailure { .. } => "HomeAnswerFailure",
+        HandshakePacket::RemoteIceCandidate { .. } => "RemoteIceCandidate",
+        HandshakePacket::HomeIceCandidate { .. } => "HomeIceCandidate",
+    }
+}
+
+fn input_variant(packet: &InputPacket) -> &'static str {
+    match packet {
+        InputPacket::KeyDown(_) => "KeyDown",
+        InputPacket::KeyUp(_) => "KeyUp",
+        InputPacket::MouseDown(_) => "MouseDown",
+        InputPacket::MouseUp(_) => "MouseUp",
+        InputPacket::MouseMove { .. } => "MouseMove",
+        InputPacket::MouseMoveRelative { .. } => "MouseMoveRelative",
+        InputPacket::MouseScroll { .. } => "MouseScroll",
+        InputPacket::Text(_) => "Text",
+        InputPacket::ReleaseAll {} => "ReleaseAll",
+        InputPacket::GamepadButton { .. } => "GamepadButton",
+        InputPacket::GamepadAxis { .. } => "GamepadAxis",
+        InputPacket::Touch { .. } => "Touch",
+        InputPacket::Pen { .. } => "Pen",
+    }
+}
+
+fn clipboard_variant(packet: &ClipboardPacket) -> &'static str {
+    match packet {
+        ClipboardPacket::Text(_) => "Text",
+    }
+}
+
+fn transfer_variant(packet: &FileTransferPacket) -> &'static str {
+    match packet {
+        FileTransferPacket::Download { .. } => "Download",
+        FileTransferPacket::Upload { .. } => "Upload",
+        FileTransferPacket::Accepted { .. } => "Accepted",
+        FileTransferPacket::Chunk { .. } => "Chunk",
+        FileTransferPacket::Complete { .. } => "Complete",
+        FileTransferPacket::Cancel { .. } => "Cancel",
+        FileTransferPacket::List { .. } => "List",
+        FileTransferPacket::Listing { .. } => "Listing",
+        FileTransferPacket::Stat { .. } => "Stat",
+        FileTransferPacket::Entry { .. } => "Entry",
+    }
+}
+
+fn terminal_variant(packet: &TerminalPacket) -> &'static str {
+    match packet {
+        TerminalPacket::Open { .. } => "Open",
+        TerminalPacket::Data(_) => "Data",
+        TerminalPacket::Resize { .. } => "Resize",
+        TerminalPacket::Exit { .. } => "Exit",
+    }
+}
+
+fn tunnel_variant(packet: &TunnelPacket) -> &'static str {
+    match packet {
+        TunnelPacket::Connect { .. } => "Connect",
+        TunnelPacket::Connected { .. } => "Connected",
+        TunnelPacket::Data { .. } => "Data",
+        TunnelPacket::Close { .. } => "Close",
+    }
+}
+
+/// Counts up from `start`, wrapping around. This stands in
+/// for encrypted data, which has to be the right size for
+/// packets to be valid.
+fn bytes(start: u8, len: usize) -> Vec<u8> {
+    (0..len).map(|i| start.wrapping_add(i as u8)).collect()
+}
+
+fn handshake_packets() -> Vec<(&'static str, HandshakePacket)> {
+    vec![
+        (
+            "home_init",
+            HandshakePacket::HomeInit {
+                home_name: "eggshell".to_string(),
+            },
+        ),
+        (
+            "home_init_response_ok",
+            HandshakePacket::HomeInitResponse {
+                response: Ok(()),
+                version: VERSION_1_1,
+                codec: Codec::MessagePack,
+            },
+        ),
+        (
+            "home_init_response_err",
+            HandshakePacket::HomeInitResponse {
+                response: Err(ErrorMsg::with_detail(ErrorCode::NameTaken, "eggshell is taken")),
+                version: VERSION_1_1,
+                codec: Codec::Bson,
+            },
+        ),
+        (
+            "remote_init",
+            HandshakePacket::RemoteInit {
+                home_name: "eggshell".to_string(),
+            },
+        ),
+        (
+            "remote_init_response_ok",
+            HandshakePacket::RemoteInitResponse {
+                response: Ok(()),
+                version: VERSION_1_1,
+                codec: Codec::Cbor,
+            },
+        ),
+        (
+            "remote_init_response_err",
+            HandshakePacket::RemoteInitResponse {
+                response: Err(ErrorMsg::new(ErrorCode::DesktopNotFound)),
+                version: VERSION_1_1,
+                codec: Codec::Bson,
+            },
+        ),
+        (
+            "remote_offer",
+            HandshakePacket::RemoteOffer {
+                peer: 7,
+                key: bytes(1, ENCRYPTED_KEY_SIZE),
+                iv: bytes(5, AES_IV_SIZE),
+                payload: bytes(128, AES_TAG_SIZE + 3),
+                codec: Codec::MessagePack,
+                version: VERSION_1_1,
+            },
+        ),
+        (
+            "home_answer_success",
+            HandshakePacket::HomeAnswerSuccess {
+                peer: 7,
+                key: bytes(4, ENCRYPTED_KEY_SIZE),
+                iv: bytes(7, AES_IV_SIZE),
+                payload: bytes(255, AES_TAG_SIZE + 3),
+            },
+        ),
+        (
+            "home_answer_failure",
+            HandshakePacket::HomeAnswerFailure {
+                peer: 7,
+                error: ErrorMsg::with_detail(ErrorCode::DecryptFailed, "Failed to decrypt AES key"),
+            },
+        ),
+        (
+            "remote_ice_candidate",
+            HandshakePacket::RemoteIceCandidate {
+                peer: 7,
+                iv: bytes(8, AES_IV_SIZE),
+                payload: bytes(11, AES_TAG_SIZE + 3),
+            },
+        ),
+        (
+            "home_ice_candidate",
+            HandshakePacket::HomeIceCandidate {
+                peer: 7,
+                iv: bytes(10, AES_IV_SIZE),
+                payload: bytes(13, AES_TAG_SIZE + 3),
+            },
+        ),
+    ]
+}
+
+fn handshake_init_packets() -> Vec<(&'static str, HandshakeInitPacket)> {
+    vec![
+        (
+            "home_init",
+            HandshakeInitPacket::new(
+                VERSION_1_0..=VERSION_1_1,
+                HandshakePacket::HomeInit {
+                    home_name: "eggshell".to_string(),
+                },
+            )
+            .unwrap(),
+        ),
+        (
+            "remote_init",
+            HandshakeInitPacket::new(
+                VERSION_1_1..=VERSION_1_1,
+                HandshakePacket::RemoteInit {
+                    home_name: "eggshell".to_string(),
+                },
+            )
+            .unwrap(),
+        ),
+    ]
+}
+
+/// Packets with errors for peers on [`VERSION_1_0`],
+/// which are sent as plain strings, along with the
+/// packets that they decode to.
+fn legacy_error_packets() -> Vec<(&'static str, HandshakePacket, HandshakePacket)> {
+    vec![
+        (
+            "home_init_response_err",
+            HandshakePacket::HomeInitResponse {
+                response: Err(ErrorMsg::with_detail(ErrorCode::NameTaken, "eggshell is taken").for_version(VERSION_1_0)),
+                version: VERSION_1_1,
+                codec: Codec::Bson,
+            },
+            HandshakePacket::HomeInitResponse {
+                response: Err(ErrorMsg::with_detail(
+                    ErrorCode::Other,
+                    "Requested desktop name was already taken: eggshell is taken",
+                )),
+                version: VERSION_1_1,
+                codec: Codec::Bson,
+            },
+        ),
+        (
+            "home_answer_failure",
+            HandshakePacket::HomeAnswerFailure {
+                peer: 7,
+                error: ErrorMsg::new(ErrorCode::DecryptFailed).for_version(VERSION_1_0),
+            },
+            HandshakePacket::HomeAnswerFailure {
+                peer: 7,
+                error: ErrorMsg::with_detail(ErrorCode::Other, "Couldn't decrypt offer"),
+            },
+        ),
+    ]
+}
+
+fn input_packets() -> Vec<(&'static str, InputPacket)> {
+    vec![
+        (
+            "key_down_special",
+            InputPacket::KeyDown(KeyInput::SpecialKey(SpecialKeyInput::Return)),
+        ),
+        ("key_down_alpha", InputPacket::KeyDown(KeyInput::AlphaKey('h'))),
+        ("key_down_raw", InputPacket::KeyDown(KeyInput::RawKey(172))),
+        (
+            "key_up_special",
+            InputPacket::KeyUp(KeyInput::SpecialKey(SpecialKeyInput::Shift)),
+        ),
+        ("key_up_alpha", InputPacket::KeyUp(KeyInput::AlphaKey('~'))),
+        ("key_down_code", InputPacket::KeyDown(KeyInput::Code(KeyCode::KeyW))),
+        ("key_up_code", InputPacket::KeyUp(KeyInput::Code(KeyCode::ShiftLeft))),
+        ("mouse_down", InputPacket::MouseDown(MouseButton::Left)),
+        ("mouse_up", InputPacket::MouseUp(MouseButton::Right)),
+        ("mouse_move", InputPacket::MouseMove { x: 0.25, y: 0.75 }),
+        ("mouse_move_relative", InputPacket::MouseMoveRelative { dx: 12, dy: -5 }),
+        ("mouse_scroll", InputPacket::MouseScroll { x: -3, y: 120 }),
+        ("text", InputPacket::Text("こんにちは 🥚\n".to_string())),
+        ("release_all", InputPacket::ReleaseAll {}),
+        (
+            "gamepad_button",
+            InputPacket::GamepadButton {
+                button: GamepadButton::A,
+                pressed: true,
+            },
+        ),
+        (
+            "gamepad_axis",
+            InputPacket::GamepadAxis {
+                axis: GamepadAxis::LeftStickY,
+                value: -0.5,
+            },
+        ),
+        (
+            "touch",
+            InputPacket::Touch {
+                id: 3,
+                x: 0.5,
+                y: 0.25,
+                pressure: 0.5,
+                state: ContactState::Contact,
+            },
+        ),
+        (
+            "pen",
+            InputPacket::Pen {
+                x: 0.75,
+                y: 0.5,
+                pressure: 0.25,
+                tilt_x: -30.0,
+                tilt_y: 15.0,
+                state: ContactState::Hover,
+            },
+        ),
+    ]
+}
+
+fn clipboard_packets() -> Vec<(&'static str, ClipboardPacket)> {
+    vec![("text", ClipboardPacket::Text("hunter2 🔨🥚".to_string()))]
+}
+
+fn transfer_packets() -> Vec<(&'static str, FileTransferPacket)> {
+    vec![
+        (
+            "download",
+            FileTransferPacket::Download {
+                id: 1,
+                path: "eggs/scrambled.txt".to_string(),
+                offset: 0,
+            },
+        ),
+        (
+            "upload",
+            FileTransferPacket::Upload {
+                id: 2,
+                path: "eggs/🥚.png".to_string(),
+                size: 1_000_000,
+            },
+        ),
+        (
+            "accepted",
+            FileTransferPacket::Accepted {
+                id: 2,
+                size: 1_000_000,
+                offset: 16384,
+            },
+        ),
+        ("chunk", FileTransferPacket::chunk(1, 16384, b"hammer time".to_vec())),
+        ("complete", FileTransferPacket::Complete { id: 1 }),
+        ("cancel", FileTransferPacket::Cancel { id: 2, error: None }),
+        (
+            "cancel_with_error",
+            FileTransferPacket::Cancel {
+                id: 2,
+                error: Some(ErrorMsg::with_detail(ErrorCode::FileNotFound, "eggs/🥚.png")),
+            },
+        ),
+        (
+            "list",
+            FileTransferPacket::List {
+                id: 3,
+                path: "eggs".to_string(),
+            },
+        ),
+        (
+            "list_shared_folders",
+            FileTransferPacket::List {
+                id: 4,
+                path: String::new(),
+            },
+        ),
+        (
+            "listing",
+            FileTransferPacket::Listing {
+                id: 3,
+                entries: vec![
+                    FileEntry {
+                        name: "scrambled.txt".to_string(),
+                        is_dir: false,
+                        size: 42,
+                        modified: Some(1_634_515_200_000),
+                    },
+                    FileEntry {
+                        name: "nest".to_string(),
+                        is_dir: true,
+                        size: 0,
+                        modified: None,
+                    },
+                ],
+                done: true,
+            },
+        ),
+        (
+            "stat",
+            FileTransferPacket::Stat {
+                id: 5,
+                path: "eggs/scrambled.txt".to_string(),
+            },
+        ),
+        (
+            "entry",
+            FileTransferPacket::Entry {
+                id: 5,
+                entry: FileEntry {
+                    name: "scrambled.txt".to_string(),
+                    is_dir: false,
+                    size: 42,
+                    modified: Some(1_634_515_200_000),
+                },
+            },
+        ),
+    ]
+}
+
+fn terminal_packets() -> Vec<(&'static str, TerminalPacket)> {
+    vec![
+        ("open", TerminalPacket::Open { cols: 80, rows: 24 }),
+        ("data", TerminalPacket::Data(b"sudo systemctl restart nginx\r".to_vec())),
+        ("resize", TerminalPacket::Resize { cols: 120, rows: 40 }),
+        (
+            "exit",
+            TerminalPacket::Exit {
+                success: true,
+                error: None,
+            },
+        ),
+        (
+            "exit_with_error",
+            TerminalPacket::Exit {
+                success: false,
+                error: Some(ErrorMsg::new(ErrorCode::Unauthorized)),
+            },
+        ),
+    ]
+}
+
+fn tunnel_packets() -> Vec<(&'static str, TunnelPacket)> {
+    vec![
+        (
+            "connect",
+            TunnelPacket::Connect {
+                id: 3,
+                host: "nas.local".to_string(),
+                port: 443,
+            },
+        ),
+        ("connected", TunnelPacket::Connected { id: 3 }),
+        (
+            "data",
+            TunnelPacket::Data {
+                id: 3,
+                data: b"GET / HTTP/1.1\r\n".to_vec(),
+            },
+        ),
+        ("close", TunnelPacket::Close { id: 3, error: None }),
+        (
+            "close_with_error",
+            TunnelPacket::Close {
+                id: 4,
+                error: Some(ErrorMsg::with_detail(
+                    ErrorCode::Unauthorized,
+                    "localhost:22 isn't allowed",
+                )),
+            },
+        ),
+    ]
+}
+
+fn read_vectors(file: &str) -> Vec<Vector> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("vectors").join(file);
+    let json = fs::read_to_string(&path).unwrap_or_else(|err| panic!("failed to read {}: {}", path.display(), err));
+    serde_json::from_str(&json).unwrap_or_else(|err| panic!("failed to parse {}: {}", path.display(), err))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn check_vectors<P>(file: &str, packets: Vec<(&'static str, P)>)
+where
+    P: Serialize + DeserializeOwned + Clone + PartialEq + Debug + Validate,
+{
+    let packets = packets
+        .into_iter()
+        .map(|(name, packet)| (name, packet.clone(), packet))
+        .collect();
+    check_vectors_decoding_to(file, packets);
+}
+
+/// Checks vectors for packets that don't decode to
+/// themselves, each given along with what it decodes to.
+fn check_vectors_decoding_to<P>(file: &str, packets: Vec<(&'static str, P, P)>)
+where
+    P: Serialize + DeserializeOwned + PartialEq + Debug + Validate,
+{
+    let vectors = read_vectors(file);
+    let expected_names = packets.iter().map(|(name, ..)| *name).collect::<BTreeSet<_>>();
+    let actual_names = vectors
+        .iter()
+        .map(|vector| vector.name.as_str())
+        .collect::<BTreeSet<_>>();
+    assert_eq!(expected_names, actual_names, "{} has the wrong set of vectors", file);
+
+    for (name, packet, decoded_packet) in packets {
+        let vector = vectors.iter().find(|vector| vector.name == name).unwrap();
+        assert_eq!(
+            serde_json::to_value(&packet).unwrap(),
+            vector.value,
+            "{}: JSON representation changed",
+            name
+        );
+
+        for codec in SUPPORTED_CODECS {
+            let hex = vector.encoded.get(codec);
+            assert_eq!(
+                to_hex(&codec.encode(&packet).unwrap()),
+                hex,
+                "{}: {:?} encoding changed",
+                name,
+                codec
+            );
+            let decoded = codec.decode::<P>(&from_hex(hex)).unwrap();
+            assert_eq!(decoded, decoded_packet, "{}: {:?} decoding changed", name, codec);
+            // Vectors are what other clients test against,
+            // so they have to be packets that would be accepted
+            if let Err(err) = decoded.validate() {
+                panic!("{}: {:?} decoded to an invalid packet: {}", name, codec, err);
+            }
+        }
+    }
+}
+
+/// Counts the variants of a packet enum
+/// from its definition in the schema.
+fn schema_variant_count(name: &str) -> usize {
+    let schema = serde_json::to_value(protocol_schema()).unwrap();
+    schema["definitions"][name]["oneOf"].as_array().map_or(1, Vec::len)
+}
+
+#[test]
+fn every_variant_has_a_vector() {
+    let handshake_variants = handshake_packets()
+        .iter()
+        .map(|(_, packet)| handshake_variant(packet))
+        .collect::<BTreeSet<_>>();
+    assert_eq!(handshake_variants.len(), schema_variant_count("HandshakePacket"));
+
+    let input_variants = input_packets()
+        .iter()
+        .map(|(_, packet)| input_variant(packet))
+        .collect::<BTreeSet<_>>();
+    assert_eq!(input_variants.len(), schema_variant_count("InputPacket"));
+
+    let clipboard_variants = clipboard_packets()
+        .iter()
+        .map(|(_, packet)| clipboard_variant(packet))
+        .collect::<BTreeSet<_>>();
+    assert_eq!(clipboard_variants.len(), schema_variant_count("ClipboardPacket"));
+
+    let transfer_variants = transfer_packets()
+        .iter()
+        .map(|(_, packet)| transfer_variant(packet))
+        .collect::<BTreeSet<_>>();
+    assert_eq!(transfer_variants.len(), schema_variant_count("FileTransferPacket"));
+
+    let terminal_variants = terminal_packets()
+        .iter()
+        .map(|(_, packet)| terminal_variant(packet))
+        .collect::<BTreeSet<_>>();
+    assert_eq!(terminal_variants.len(), schema_variant_count("TerminalPacket"));
+
+    let tunnel_variants = tunnel_packets()
+        .iter()
+        .map(|(_, packet)| tunnel_variant(packet))
+        .collect::<BTreeSet<_>>();
+    assert_eq!(tunnel_variants.len(), schema_variant_count("TunnelPacket"));
+}
+
+#[test]
+fn handshake_packets_match_vectors() {
+    check_vectors("handshake.json", handshake_packets());
+}
+
+#[test]
+fn handshake_init_packets_match_vectors() {
+    check_vectors("handshake_init.json", handshake_init_packets());
+}
+
+#[test]
+fn legacy_error_packets_match_vectors() {
+    check_vectors_decoding_to("legacy_errors.json", legacy_error_packets());
+}
+
+#[test]
+fn input_packets_match_vectors() {
+    check_vectors("input.json", input_packets());
+}
+
+#[test]
+fn clipboard_packets_match_vectors() {
+    check_vectors("clipboard.json", clipboard_packets());
+}
+
+#[test]
+fn transfer_packets_match_vectors() {
+    check_vectors("transfer.json", transfer_packets());
+}
+
+#[test]
+fn terminal_packets_match_vectors() {
+    check_vectors("terminal.json", terminal_packets());
+}
+
+#[test]
+fn tunnel_packets_match_vectors() {
+    check_vectors("tunnel.json", tunnel_packets());
+}
@@ -5,6 +5,7 @@ use std::fmt::Display;
 use std::fs::File;
 use std::io::BufReader;
 use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -12,9 +13,12 @@ use anyhow::{anyhow, Context, Result};
 use futures::channel::mpsc::{unbounded, UnboundedSender};
 use futures::future::Either;
 use futures::{future, pin_mut, SinkExt, StreamExt, TryStreamExt};
+use hammeregg_core::codec::BsonCodec;
+use hammeregg_core::limits::MAX_PACKET_SIZE;
+use hammeregg_core::websocket::{deserialize_and_validate_message, serialize_message};
 use hammeregg_core::{
-    deserialize_and_validate_packet, deserialize_packet, serialize_packet, ErrorMsg, HandshakeInitPacket,
-    HandshakePacket, DEFAULT_HAMMEREGG_PORT,
+    negotiate_codec, negotiate_version, Codec, ErrorCode, ErrorMsg, HandshakeInitPacket, HandshakePacket,
+    DEFAULT_HAMMEREGG_PORT, SUPPORTED_CODECS, SUPPORTED_VERSIONS,
 };
 use log::LevelFilter;
 use parking_lot::Mutex;
@@ -22,38 +26,57 @@ use tokio::net::{TcpListener, TcpStream};
 use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
 use tokio_rustls::server::TlsStream;
 use tokio_rustls::TlsAcceptor;
-use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use tokio_tungstenite::WebSocketStream;
 
 /// A wrapper around a desktop and a set of peers
 /// where each peer has a unique id.
+///
+/// Packets are passed around undecoded so that
+/// each connection can encode them with its own
+/// negotiated [`Codec`].
 pub struct DesktopAndPeers {
-    pub desktop_tx: UnboundedSender<Message>,
+    pub desktop_tx: UnboundedSender<HandshakePacket>,
+    /// The protocol versions that both the desktop
+    /// and Rooster support. Remotes must negotiate
+    /// a version within this range.
+    pub versions: RangeInclusive<u32>,
+    /// The codecs that both the desktop and Rooster
+    /// support. Remotes must negotiate one of these
+    /// since their data channels connect directly to
+    /// the desktop.
+    pub codecs: Vec<Codec>,
     id_counter: u32,
-    peers: HashMap<u32, UnboundedSender<Message>>,
+    peers: HashMap<u32, UnboundedSender<HandshakePacket>>,
 }
 
 impl DesktopAndPeers {
-    pub fn new(desktop_tx: UnboundedSender<Message>) -> Self {
+    pub fn new(
+        desktop_tx: UnboundedSender<HandshakePacket>,
+        versions: RangeInclusive<u32>,
+        codecs: Vec<Codec>,
+    ) -> Self {
         Self {
             desktop_tx,
+            versions,
+            codecs,
             id_counter: 0,
             peers: HashMap::new(),
         }
     }
 
-    pub fn insert_peer(&mut self, peer: UnboundedSender<Message>) -> u32 {
+    pub fn insert_peer(&mut self, peer: UnboundedSender<HandshakePacket>) -> u32 {
         let id = self.id_counter;
         self.peers.insert(id, peer);
         self.id_counter += 1;
         id
     }
 
-    pub fn get_peer(&self, id: &u32) -> Option<&UnboundedSender<Message>> {
+    pub fn get_peer(&self, id: &u32) -> Option<&UnboundedSender<HandshakePacket>> {
         self.peers.get(id)
     }
 
-    pub fn get_peer_mut(&mut self, id: &u32) -> Option<&mut UnboundedSender<Message>> {
+    pub fn get_peer_mut(&mut self, id: &u32) -> Option<&mut UnboundedSender<HandshakePacket>> {
         self.peers.get_mut(id)
     }
 
@@ -61,7 +84,7 @@ impl DesktopAndPeers {
         self.peers.remove(id);
     }
 
-    pub fn peers(&self) -> &HashMap<u32, UnboundedSender<Message>> {
+    pub fn peers(&self) -> &HashMap<u32, UnboundedSender<HandshakePacket>> {
         &self.peers
     }
 }
@@ -166,23 +189,32 @@ async fn handle_connection(acceptor: TlsAcceptor, stream: TcpStream, desktops: D
             .accept(stream)
             .await
             .context("Error during the TLS handshake occurred")?;
-        let mut socket = tokio_tungstenite::accept_async(tls_stream)
+        let ws_config = WebSocketConfig {
+            max_message_size: Some(MAX_PACKET_SIZE),
+            max_frame_size: Some(MAX_PACKET_SIZE),
+            ..Default::default()
+        };
+        let mut socket = tokio_tungstenite::accept_async_with_config(tls_stream, Some(ws_config))
             .await
             .context("Error during the websocket handshake occurred")?;
 
-        match deserialize_and_validate_packet::<HandshakeInitPacket>(
+        // The init packet is always BSON since no codec has been negotiated yet
+        let init_packet = deserialize_and_validate_message::<_, HandshakeInitPacket>(
+            &BsonCodec,
             &socket
                 .next()
                 .await
                 .context("Handshake failed: could not read packet")??,
-        )?
-        .packet
-        {
+        )?;
+        let versions = init_packet.versions();
+        let codecs = init_packet.codecs;
+
+        match init_packet.packet {
             HandshakePacket::HomeInit { home_name } => {
-                handle_home_init(desktops, socket, home_name).await?;
+                handle_home_init(desktops, socket, home_name, versions, codecs).await?;
             }
             HandshakePacket::RemoteInit { home_name } => {
-                handle_remote_init(desktops, socket, home_name).await?;
+                handle_remote_init(desktops, socket, home_name, versions, codecs).await?;
             }
             _ => Err(anyhow!(
                 "Handshake failed: client did not send a valid HandshakeInitPacket"
@@ -194,38 +226,93 @@ async fn handle_connection(acceptor: TlsAcceptor, stream: TcpStream, desktops: D
     }
 }
 
-async fn handle_home_init(desktops: Desktops, mut socket: WSS, home_name: String) -> Result<()> {
-    if desktops.lock().contains_key(&home_name) {
+async fn handle_home_init(
+    desktops: Desktops,
+    mut socket: WSS,
+    home_name: String,
+    versions: RangeInclusive<u32>,
+    codecs: Vec<Codec>,
+) -> Result<()> {
+    // Errors are sent in a form that the newest version the
+    // peer supports can read, since the peer doesn't learn
+    // which version was negotiated if its init fails
+    let version = negotiate_version(&versions, &SUPPORTED_VERSIONS);
+    let codec = negotiate_codec(&codecs, &SUPPORTED_CODECS);
+    if version.is_none() {
+        // oops we don't speak the same language
+        socket
+            .send(serialize_message(
+                &BsonCodec,
+                &HandshakePacket::HomeInitResponse {
+                    response: Err(ErrorMsg::new(ErrorCode::UnsupportedVersion).for_version(*versions.end())),
+                    version: *SUPPORTED_VERSIONS.end(),
+                    codec: Codec::Bson,
+                },
+            )?)
+            .await?;
+    } else if desktops.lock().contains_key(&home_name) {
         // oops there's already another computer with this name
         socket
-            .send(serialize_packet(&HandshakePacket::HomeInitResponse {
-                response: Err(ErrorMsg("Requested desktop name was already taken".to_string())),
-            })?)
+            .send(serialize_message(
+                &BsonCodec,
+                &HandshakePacket::HomeInitResponse {
+                    response: Err(ErrorMsg::new(ErrorCode::NameTaken).for_version(*versions.end())),
+                    version: *SUPPORTED_VERSIONS.end(),
+                    codec: Codec::Bson,
+                },
+            )?)
             .await?;
     } else {
+        let version = version.unwrap();
+
         // Initial handshake complete!
         socket
-            .send(serialize_packet(&HandshakePacket::HomeInitResponse {
-                response: Ok(()),
-            })?)
+            .send(serialize_message(
+                &BsonCodec,
+                &HandshakePacket::HomeInitResponse {
+                    response: Ok(()),
+                    version,
+                    codec,
+                },
+            )?)
             .await?;
-        log::info!("Home desktop '{}' connected", home_name);
+        log::info!(
+            "Home desktop '{}' connected with protocol version {:#010x} and codec {:?}",
+            home_name,
+            version,
+            codec
+        );
+
+        // Remotes may only use versions that both this desktop and Rooster support
+        let common_versions = *versions.start().max(SUPPORTED_VERSIONS.start())..=version;
+        // Desktops that predate codec negotiation only support BSON
+        let common_codecs = if codecs.is_empty() {
+            vec![Codec::Bson]
+        } else {
+            codecs.into_iter().filter(|x| SUPPORTED_CODECS.contains(x)).collect()
+        };
 
         let (tx, rx) = unbounded();
         // Insert sender into desktop map
-        desktops.lock().insert(home_name.clone(), DesktopAndPeers::new(tx));
+        desktops.lock().insert(
+            home_name.clone(),
+            DesktopAndPeers::new(tx, common_versions, common_codecs),
+        );
 
         let (send, recv) = socket.split();
 
         // Listen to incoming requests to send back home
-        let send_home = rx.map(Ok).forward(send);
+        let send_home = rx
+            .map(|packet| serialize_message(&codec, &packet))
+            .forward(send.sink_map_err(anyhow::Error::from));
 
         // Listen to incoming requests to send to peers
         let send_peer = recv
             .map(|res| res.context("Signalling failed: could not read packet"))
             .try_for_each(|packet| {
-                match try {
-                    match deserialize_packet::<HandshakePacket>(&packet)? {
+                let result: Result<()> = try {
+                    let packet = deserialize_and_validate_message::<_, HandshakePacket>(&codec, &packet)?;
+                    match packet {
                         HandshakePacket::HomeAnswerSuccess { peer, .. }
                         | HandshakePacket::HomeAnswerFailure { peer, .. } => {
                             let mut desktop_map = desktops.lock();
@@ -237,14 +324,29 @@ async fn handle_home_init(desktops: Desktops, mut socket: WSS, home_name: String
                                 Err(anyhow!("Signalling failed: peer does not exist (any longer)"))?
                             }
                         }
+                        HandshakePacket::HomeIceCandidate { peer, .. } => {
+                            let mut desktop_map = desktops.lock();
+                            let maybe_peer = desktop_map.get_mut(&home_name).unwrap().get_peer_mut(&peer);
+                            if let Some(peer) = maybe_peer {
+                                peer.unbounded_send(packet).context("Couldn't send packet")?
+                            } else {
+                                // Candidates can still trickle in after the remote
+                                // has connected and hung up, so this isn't an error
+                                log::debug!("Dropping ICE candidate for disconnected peer {}", peer);
+                            }
+                        }
                         _ => Err(anyhow!(
-                            "Signalling failed: did not get a HomeAnswerSuccess or HomeAnswerResponse packet"
+                            "Signalling failed: did not get a HomeAnswerSuccess, HomeAnswerResponse or HomeIceCandidate packet"
                         ))?,
                     }
-                } {
-                    Ok(_) => future::ok(()),
-                    Err(err) => future::err(err),
+                };
+                // One bad packet shouldn't take down the home desktop
+                // and every remote connected to it, so only transport
+                // errors end the loop
+                if let Err(err) = result {
+                    log::warn!("Skipping packet from home desktop '{}': {:?}", home_name, err);
                 }
+                future::ok(())
             });
 
         pin_mut!(send_home, send_peer);
@@ -265,20 +367,61 @@ async fn handle_home_init(desktops: Desktops, mut socket: WSS, home_name: String
     Ok(())
 }
 
-async fn handle_remote_init(desktops: Desktops, mut socket: WSS, home_name: String) -> Result<()> {
-    if !desktops.lock().contains_key(&home_name) {
+async fn handle_remote_init(
+    desktops: Desktops,
+    mut socket: WSS,
+    home_name: String,
+    versions: RangeInclusive<u32>,
+    codecs: Vec<Codec>,
+) -> Result<()> {
+    let desktop_versions = desktops.lock().get(&home_name).map(|desktop| desktop.versions.clone());
+    let version = desktop_versions
+        .as_ref()
+        .and_then(|desktop_versions| negotiate_version(&versions, desktop_versions));
+    if desktop_versions.is_none() {
         // oops desktop does not exist
         socket
-            .send(serialize_packet(&HandshakePacket::RemoteInitResponse {
-                response: Err(ErrorMsg("Requested desktop not found".to_string())),
-            })?)
+            .send(serialize_message(
+                &BsonCodec,
+                &HandshakePacket::RemoteInitResponse {
+                    response: Err(ErrorMsg::new(ErrorCode::DesktopNotFound).for_version(*versions.end())),
+                    version: *SUPPORTED_VERSIONS.end(),
+                    codec: Codec::Bson,
+                },
+            )?)
+            .await?;
+    } else if version.is_none() {
+        // oops the remote and the desktop don't speak the same language
+        socket
+            .send(serialize_message(
+                &BsonCodec,
+                &HandshakePacket::RemoteInitResponse {
+                    response: Err(ErrorMsg::new(ErrorCode::UnsupportedVersion).for_version(*versions.end())),
+                    version: *desktop_versions.unwrap().end(),
+                    codec: Codec::Bson,
+                },
+            )?)
             .await?;
     } else {
+        let version = version.unwrap();
+        // The remote's data channels connect directly to the
+        // desktop, so the desktop must support its codec too
+        let codec = desktops
+            .lock()
+            .get(&home_name)
+            .map(|desktop| negotiate_codec(&codecs, &desktop.codecs))
+            .unwrap_or(Codec::Bson);
+
         // Initial handshake complete!
         socket
-            .send(serialize_packet(&HandshakePacket::RemoteInitResponse {
-                response: Ok(()),
-            })?)
+            .send(serialize_message(
+                &BsonCodec,
+                &HandshakePacket::RemoteInitResponse {
+                    response: Ok(()),
+                    version,
+                    codec,
+                },
+            )?)
             .await?;
 
         let (tx, rx) = unbounded();
@@ -289,44 +432,72 @@ async fn handle_remote_init(desktops: Desktops, mut socket: WSS, home_name: Stri
             .context("Desktop disappeared during remote init?")?
             .insert_peer(tx);
 
-        log::info!("Remote with id {} connected", id);
+        log::info!(
+            "Remote with id {} connected with protocol version {:#010x} and codec {:?}",
+            id,
+            version,
+            codec
+        );
 
         let (send, recv) = socket.split();
 
         // Listen to incoming requests to send back to the remote
-        let send_remote = rx.map(Ok).forward(send);
+        let send_remote = rx
+            .map(|packet| serialize_message(&codec, &packet))
+            .forward(send.sink_map_err(anyhow::Error::from));
 
         // Listen to incoming requests to send home
         let send_home = recv
             .map(|res| res.context("Signalling failed: could not read packet"))
             .try_for_each(|packet| {
-                match try {
-                    match deserialize_packet::<HandshakePacket>(&packet)? {
+                let result: Result<()> = try {
+                    match deserialize_and_validate_message::<_, HandshakePacket>(&codec, &packet)? {
                         HandshakePacket::RemoteOffer { key, iv, payload, .. } => {
                             let mut desktop_map = desktops.lock();
                             let desktop = desktop_map
                                 .get_mut(&home_name)
                                 .context("Desktop does not exist any longer")?;
 
-                            // Since remote doesn't know their peer id we need to fill it in
-                            let filled_packet = serialize_packet(&HandshakePacket::RemoteOffer {
+                            // Since remote doesn't know their peer id and the desktop
+                            // doesn't know their negotiated codec and version we need
+                            // to fill them in
+                            let filled_packet = HandshakePacket::RemoteOffer {
                                 peer: id,
                                 key,
                                 iv,
                                 payload,
-                            })?;
+                                codec,
+                                version,
+                            };
+
+                            desktop
+                                .desktop_tx
+                                .unbounded_send(filled_packet)
+                                .context("Couldn't send packet")?;
+                        }
+                        HandshakePacket::RemoteIceCandidate { iv, payload, .. } => {
+                            let mut desktop_map = desktops.lock();
+                            let desktop = desktop_map
+                                .get_mut(&home_name)
+                                .context("Desktop does not exist any longer")?;
+
+                            let filled_packet = HandshakePacket::RemoteIceCandidate { peer: id, iv, payload };
 
                             desktop
                                 .desktop_tx
                                 .unbounded_send(filled_packet)
                                 .context("Couldn't send packet")?;
                         }
-                        _ => Err(anyhow!("Signalling failed: did not get a RemoteOffer packet"))?,
+                        _ => Err(anyhow!(
+                            "Signalling failed: did not get a RemoteOffer or RemoteIceCandidate packet"
+                        ))?,
                     }
-                } {
-                    Ok(_) => future::ok(()),
-                    Err(err) => future::err(err),
+                };
+                // Only transport errors disconnect the remote
+                if let Err(err) = result {
+                    log::warn!("Skipping packet from remote {}: {:?}", id, err);
                 }
+                future::ok(())
             });
 
         pin_mut!(send_remote, send_home);
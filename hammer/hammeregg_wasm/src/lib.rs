@@ -0,0 +1,11 @@
+//! # Hammeregg Wasm
+//!
+//! The WebAssembly module that Egg loads, which
+//! is just the bindings from `hammeregg_core`'s
+//! `wasm` feature. Build it from `egg` with
+//!
+//! ```sh
+//! yarn build:core
+//! ```
+
+pub use hammeregg_core::wasm::*;
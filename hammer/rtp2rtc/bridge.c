@@ -14,3 +14,37 @@ void HammerRTP2RTCInputCallbackBridge(hammer_rtp2rtc_input_callback callback,
                                       void* user_data) {
     callback(input_packet, input_packet_len, user_data);
 }
+
+void HammerRTP2RTCClipboardCallbackBridge(hammer_rtp2rtc_clipboard_callback callback,
+                                          void* clipboard_packet,
+                                          size_t clipboard_packet_len,
+                                          void* user_data) {
+    callback(clipboard_packet, clipboard_packet_len, user_data);
+}
+
+void HammerRTP2RTCFilesCallbackBridge(hammer_rtp2rtc_files_callback callback,
+                                      void* files_packet,
+                                      size_t files_packet_len,
+                                      void* user_data) {
+    callback(files_packet, files_packet_len, user_data);
+}
+
+void HammerRTP2RTCTerminalCallbackBridge(hammer_rtp2rtc_terminal_callback callback,
+                                         void* terminal_packet,
+                                         size_t terminal_packet_len,
+                                         void* user_data) {
+    callback(terminal_packet, terminal_packet_len, user_data);
+}
+
+void HammerRTP2RTCTunnelCallbackBridge(hammer_rtp2rtc_tunnel_callback callback,
+                                       void* tunnel_packet,
+                                       size_t tunnel_packet_len,
+                                       void* user_data) {
+    callback(tunnel_packet, tunnel_packet_len, user_data);
+}
+
+void HammerRTP2RTCCandidateCallbackBridge(hammer_rtp2rtc_candidate_callback callback,
+                                          const char* candidate,
+                                          void* user_data) {
+    callback(candidate, user_data);
+}
@@ -3,6 +3,11 @@
 
 typedef void (*hammer_rtp2rtc_ports_callback)(uint16_t video, uint16_t audio, void* user_data);
 typedef void (*hammer_rtp2rtc_input_callback)(void* input_packet, size_t input_packet_len, void* user_data);
+typedef void (*hammer_rtp2rtc_clipboard_callback)(void* clipboard_packet, size_t clipboard_packet_len, void* user_data);
+typedef void (*hammer_rtp2rtc_files_callback)(void* files_packet, size_t files_packet_len, void* user_data);
+typedef void (*hammer_rtp2rtc_terminal_callback)(void* terminal_packet, size_t terminal_packet_len, void* user_data);
+typedef void (*hammer_rtp2rtc_tunnel_callback)(void* tunnel_packet, size_t tunnel_packet_len, void* user_data);
+typedef void (*hammer_rtp2rtc_candidate_callback)(const char* candidate, void* user_data);
 
 void HammerRTP2RTCPortsCallbackBridge(
     hammer_rtp2rtc_ports_callback callback,
@@ -14,3 +19,27 @@ void HammerRTP2RTCInputCallbackBridge(hammer_rtp2rtc_input_callback callback,
                                       void* input_packet,
                                       size_t input_packet_len,
                                       void* user_data);
+
+void HammerRTP2RTCClipboardCallbackBridge(hammer_rtp2rtc_clipboard_callback callback,
+                                          void* clipboard_packet,
+                                          size_t clipboard_packet_len,
+                                          void* user_data);
+
+void HammerRTP2RTCFilesCallbackBridge(hammer_rtp2rtc_files_callback callback,
+                                      void* files_packet,
+                                      size_t files_packet_len,
+                                      void* user_data);
+
+void HammerRTP2RTCTerminalCallbackBridge(hammer_rtp2rtc_terminal_callback callback,
+                                         void* terminal_packet,
+                                         size_t terminal_packet_len,
+                                         void* user_data);
+
+void HammerRTP2RTCTunnelCallbackBridge(hammer_rtp2rtc_tunnel_callback callback,
+                                       void* tunnel_packet,
+                                       size_t tunnel_packet_len,
+                                       void* user_data);
+
+void HammerRTP2RTCCandidateCallbackBridge(hammer_rtp2rtc_candidate_callback callback,
+                                          const char* candidate,
+                                          void* user_data);
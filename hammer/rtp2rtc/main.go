@@ -12,6 +12,7 @@ import (
 	"net"
 	"os"
 	"runtime/cgo"
+	"time"
 	"unsafe"
 
 	"github.com/pion/webrtc/v3"
@@ -27,6 +28,10 @@ const Nullptr = C.uintptr_t(0)
 // Buffer size for all IO connections.
 const NetBufferSize = 1024
 
+// How many bytes can be queued on the files, terminal
+// or tunnel channels before sending to them blocks.
+const MaxBufferedAmount = 1024 * 1024
+
 func LogInfo(format string, args ...interface{}) {
 	fmt.Printf("[Hammer/Pion] %s\n", fmt.Sprintf(format, args...))
 }
@@ -37,13 +42,17 @@ func LogError(format string, args ...interface{}) {
 }
 
 type PeerConnection struct {
-	Connection   *webrtc.PeerConnection
-	VideoTrack   *webrtc.TrackLocalStaticRTP
-	VideoSender  *webrtc.RTPSender
-	AudioTrack   *webrtc.TrackLocalStaticRTP
-	AudioSender  *webrtc.RTPSender
-	InputChannel *webrtc.DataChannel
-	StopNotifier *chan struct{}
+	Connection       *webrtc.PeerConnection
+	VideoTrack       *webrtc.TrackLocalStaticRTP
+	VideoSender      *webrtc.RTPSender
+	AudioTrack       *webrtc.TrackLocalStaticRTP
+	AudioSender      *webrtc.RTPSender
+	InputChannel     *webrtc.DataChannel
+	ClipboardChannel *webrtc.DataChannel
+	FilesChannel     *webrtc.DataChannel
+	TerminalChannel  *webrtc.DataChannel
+	TunnelChannel    *webrtc.DataChannel
+	StopNotifier     *chan struct{}
 }
 
 //export hammer_rtp2rtc_init
@@ -89,16 +98,48 @@ func hammer_rtp2rtc_init() C.uintptr_t {
 		return Nullptr
 	}
 
+	// clipboard channel
+	var clipboardNegotiationId uint16 = 1
+	clipboardChannel, err := connection.CreateDataChannel("hammeregg-clipboard", &webrtc.DataChannelInit{ID: &clipboardNegotiationId, Negotiated: &yesNegotiation})
+	if err != nil {
+		return Nullptr
+	}
+
+	// file transfer channel
+	var filesNegotiationId uint16 = 2
+	filesChannel, err := connection.CreateDataChannel("hammeregg-files", &webrtc.DataChannelInit{ID: &filesNegotiationId, Negotiated: &yesNegotiation})
+	if err != nil {
+		return Nullptr
+	}
+
+	// terminal channel
+	var terminalNegotiationId uint16 = 3
+	terminalChannel, err := connection.CreateDataChannel("hammeregg-terminal", &webrtc.DataChannelInit{ID: &terminalNegotiationId, Negotiated: &yesNegotiation})
+	if err != nil {
+		return Nullptr
+	}
+
+	// tunnel channel
+	var tunnelNegotiationId uint16 = 4
+	tunnelChannel, err := connection.CreateDataChannel("hammeregg-tunnel", &webrtc.DataChannelInit{ID: &tunnelNegotiationId, Negotiated: &yesNegotiation})
+	if err != nil {
+		return Nullptr
+	}
+
 	stopNotifier := make(chan struct{})
 
 	peerConnection := PeerConnection{
-		Connection:   connection,
-		VideoTrack:   videoTrack,
-		VideoSender:  videoSender,
-		AudioTrack:   audioTrack,
-		AudioSender:  audioSender,
-		InputChannel: inputChannel,
-		StopNotifier: &stopNotifier,
+		Connection:       connection,
+		VideoTrack:       videoTrack,
+		VideoSender:      videoSender,
+		AudioTrack:       audioTrack,
+		AudioSender:      audioSender,
+		InputChannel:     inputChannel,
+		ClipboardChannel: clipboardChannel,
+		FilesChannel:     filesChannel,
+		TerminalChannel:  terminalChannel,
+		TunnelChannel:    tunnelChannel,
+		StopNotifier:     &stopNotifier,
 	}
 
 	return C.uintptr_t(cgo.NewHandle(peerConnection))
@@ -141,10 +182,8 @@ func hammer_rtp2rtc_signal_offer(connection C.uintptr_t, descPtr C.uintptr_t) *C
 		return nil
 	}
 
-	// wait for ICE
-	<-webrtc.GatheringCompletePromise(peerConnection.Connection)
-
-	// return answer
+	// return answer, ICE candidates are trickled
+	// to the candidate callback as they're gathered
 	outAnswer, err := json.Marshal(peerConnection.Connection.LocalDescription())
 	if err != nil {
 		return nil
@@ -152,6 +191,53 @@ func hammer_rtp2rtc_signal_offer(connection C.uintptr_t, descPtr C.uintptr_t) *C
 	return C.CString(string(outAnswer))
 }
 
+//export hammer_rtp2rtc_on_ice_candidate
+func hammer_rtp2rtc_on_ice_candidate(
+	connection C.uintptr_t,
+	candidate_callback C.hammer_rtp2rtc_candidate_callback,
+	candidate_callback_user_data unsafe.Pointer,
+) {
+	peerConnection := cgo.Handle(connection).Value().(PeerConnection)
+
+	peerConnection.Connection.OnICECandidate(func(candidate *webrtc.ICECandidate) {
+		// a nil candidate means that gathering is done
+		if candidate == nil {
+			return
+		}
+
+		outCandidate, err := json.Marshal(candidate.ToJSON())
+		if err != nil {
+			LogError("Couldn't serialize ICE candidate: %s", err)
+			return
+		}
+		outCandidatePtr := C.CString(string(outCandidate))
+		defer C.free(unsafe.Pointer(outCandidatePtr))
+
+		C.HammerRTP2RTCCandidateCallbackBridge(
+			candidate_callback,
+			outCandidatePtr,
+			candidate_callback_user_data,
+		)
+	})
+}
+
+//export hammer_rtp2rtc_add_ice_candidate
+func hammer_rtp2rtc_add_ice_candidate(connection C.uintptr_t, candidatePtr *C.char) C.int {
+	peerConnection := cgo.Handle(connection).Value().(PeerConnection)
+	candidateBytes := []byte(C.GoString(candidatePtr))
+	candidate := webrtc.ICECandidateInit{}
+
+	// deserialize from json
+	if err := json.Unmarshal(candidateBytes, &candidate); err != nil {
+		return 0
+	}
+
+	if err := peerConnection.Connection.AddICECandidate(candidate); err != nil {
+		return 0
+	}
+	return 1
+}
+
 //export hammer_rtp2rtc_start
 func hammer_rtp2rtc_start(
 	connection C.uintptr_t,
@@ -159,6 +245,14 @@ func hammer_rtp2rtc_start(
 	ports_callback_user_data unsafe.Pointer,
 	input_callback C.hammer_rtp2rtc_input_callback,
 	input_callback_user_data unsafe.Pointer,
+	clipboard_callback C.hammer_rtp2rtc_clipboard_callback,
+	clipboard_callback_user_data unsafe.Pointer,
+	files_callback C.hammer_rtp2rtc_files_callback,
+	files_callback_user_data unsafe.Pointer,
+	terminal_callback C.hammer_rtp2rtc_terminal_callback,
+	terminal_callback_user_data unsafe.Pointer,
+	tunnel_callback C.hammer_rtp2rtc_tunnel_callback,
+	tunnel_callback_user_data unsafe.Pointer,
 ) {
 	LogInfo("start()")
 	peerConnection := cgo.Handle(connection).Value().(PeerConnection)
@@ -274,6 +368,11 @@ func hammer_rtp2rtc_start(
 	// Read packets from the input channel and forward
 	// them to the callback
 	peerConnection.InputChannel.OnMessage(func(msg webrtc.DataChannelMessage) {
+		// empty messages aren't valid packets, and have no
+		// first byte to hand to the callback
+		if len(msg.Data) == 0 {
+			return
+		}
 		C.HammerRTP2RTCInputCallbackBridge(
 			input_callback,
 			unsafe.Pointer(&msg.Data[0]),
@@ -290,10 +389,158 @@ func hammer_rtp2rtc_start(
 		}
 	}()
 
+	// Read packets from the clipboard channel and forward
+	// them to the callback
+	peerConnection.ClipboardChannel.OnMessage(func(msg webrtc.DataChannelMessage) {
+		if len(msg.Data) == 0 {
+			return
+		}
+		C.HammerRTP2RTCClipboardCallbackBridge(
+			clipboard_callback,
+			unsafe.Pointer(&msg.Data[0]),
+			C.size_t(len(msg.Data)),
+			clipboard_callback_user_data,
+		)
+	})
+
+	defer func() {
+		// Make sure to close the clipboard channel before returning
+		if err = peerConnection.ClipboardChannel.Close(); err != nil {
+			LogError("Couldn't close clipboard channel: %s", err)
+			panic(err)
+		}
+	}()
+
+	// Read packets from the files channel and forward
+	// them to the callback
+	peerConnection.FilesChannel.OnMessage(func(msg webrtc.DataChannelMessage) {
+		if len(msg.Data) == 0 {
+			return
+		}
+		C.HammerRTP2RTCFilesCallbackBridge(
+			files_callback,
+			unsafe.Pointer(&msg.Data[0]),
+			C.size_t(len(msg.Data)),
+			files_callback_user_data,
+		)
+	})
+
+	defer func() {
+		// Make sure to close the files channel before returning
+		if err = peerConnection.FilesChannel.Close(); err != nil {
+			LogError("Couldn't close files channel: %s", err)
+			panic(err)
+		}
+	}()
+
+	// Read packets from the terminal channel and forward
+	// them to the callback
+	peerConnection.TerminalChannel.OnMessage(func(msg webrtc.DataChannelMessage) {
+		if len(msg.Data) == 0 {
+			return
+		}
+		C.HammerRTP2RTCTerminalCallbackBridge(
+			terminal_callback,
+			unsafe.Pointer(&msg.Data[0]),
+			C.size_t(len(msg.Data)),
+			terminal_callback_user_data,
+		)
+	})
+
+	defer func() {
+		// Make sure to close the terminal channel before returning
+		if err = peerConnection.TerminalChannel.Close(); err != nil {
+			LogError("Couldn't close terminal channel: %s", err)
+			panic(err)
+		}
+	}()
+
+	// Read packets from the tunnel channel and forward
+	// them to the callback
+	peerConnection.TunnelChannel.OnMessage(func(msg webrtc.DataChannelMessage) {
+		if len(msg.Data) == 0 {
+			return
+		}
+		C.HammerRTP2RTCTunnelCallbackBridge(
+			tunnel_callback,
+			unsafe.Pointer(&msg.Data[0]),
+			C.size_t(len(msg.Data)),
+			tunnel_callback_user_data,
+		)
+	})
+
+	defer func() {
+		// Make sure to close the tunnel channel before returning
+		if err = peerConnection.TunnelChannel.Close(); err != nil {
+			LogError("Couldn't close tunnel channel: %s", err)
+			panic(err)
+		}
+	}()
+
 	// Wait for the stop notifier to be called
 	<-*peerConnection.StopNotifier
 }
 
+//export hammer_rtp2rtc_send_clipboard
+func hammer_rtp2rtc_send_clipboard(
+	connection C.uintptr_t,
+	clipboard_packet unsafe.Pointer,
+	clipboard_packet_len C.size_t,
+) C.int {
+	peerConnection := cgo.Handle(connection).Value().(PeerConnection)
+
+	// fails if the channel isn't open yet or is already closed
+	if err := peerConnection.ClipboardChannel.Send(C.GoBytes(clipboard_packet, C.int(clipboard_packet_len))); err != nil {
+		return 0
+	}
+	return 1
+}
+
+//export hammer_rtp2rtc_send_files
+func hammer_rtp2rtc_send_files(
+	connection C.uintptr_t,
+	files_packet unsafe.Pointer,
+	files_packet_len C.size_t,
+) C.int {
+	peerConnection := cgo.Handle(connection).Value().(PeerConnection)
+	return sendBuffered(peerConnection.FilesChannel, C.GoBytes(files_packet, C.int(files_packet_len)))
+}
+
+//export hammer_rtp2rtc_send_terminal
+func hammer_rtp2rtc_send_terminal(
+	connection C.uintptr_t,
+	terminal_packet unsafe.Pointer,
+	terminal_packet_len C.size_t,
+) C.int {
+	peerConnection := cgo.Handle(connection).Value().(PeerConnection)
+	return sendBuffered(peerConnection.TerminalChannel, C.GoBytes(terminal_packet, C.int(terminal_packet_len)))
+}
+
+//export hammer_rtp2rtc_send_tunnel
+func hammer_rtp2rtc_send_tunnel(
+	connection C.uintptr_t,
+	tunnel_packet unsafe.Pointer,
+	tunnel_packet_len C.size_t,
+) C.int {
+	peerConnection := cgo.Handle(connection).Value().(PeerConnection)
+	return sendBuffered(peerConnection.TunnelChannel, C.GoBytes(tunnel_packet, C.int(tunnel_packet_len)))
+}
+
+// Sends a message on a data channel, blocking while the
+// channel is backed up so that messages aren't queued
+// faster than they're sent. Returns 0 if the channel
+// isn't open yet or is already closed.
+func sendBuffered(channel *webrtc.DataChannel, data []byte) C.int {
+	for channel.ReadyState() == webrtc.DataChannelStateOpen && channel.BufferedAmount() > MaxBufferedAmount {
+		time.Sleep(10 * time.Millisecond)
+	}
+
+	if err := channel.Send(data); err != nil {
+		return 0
+	}
+	return 1
+}
+
 //export hammer_rtp2rtc_stop
 func hammer_rtp2rtc_stop(connection C.uintptr_t) {
 	peerConnection := cgo.Handle(connection).Value().(PeerConnection)